@@ -0,0 +1,314 @@
+use crate::ignore::TemporaryIgnores;
+use crate::maintenance::Maintenance;
+use crate::notify::Dispatcher;
+use crate::{storage, EndpointsData};
+use chrono::{Duration, Utc};
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
+use tokio::sync::Notify;
+
+// Optional [admin] config for the local control socket
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AdminConfig {
+    pub socket: PathBuf,
+}
+
+// What the admin socket can see and control in the running monitor
+pub struct Admin {
+    dispatcher: Arc<Dispatcher>,
+    maintenance: Arc<Maintenance>,
+    ignores: Arc<TemporaryIgnores>,
+    current: Mutex<Option<EndpointsData>>,
+    poll_now: Notify,
+}
+
+// JSON-RPC error codes
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const PARSE_ERROR: i64 = -32700;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+impl Admin {
+    pub fn new(dispatcher: Arc<Dispatcher>) -> Self {
+        Admin {
+            dispatcher,
+            maintenance: Arc::default(),
+            ignores: Arc::default(),
+            current: Mutex::new(None),
+            poll_now: Notify::new(),
+        }
+    }
+
+    pub fn with_maintenance(mut self, maintenance: Arc<Maintenance>) -> Self {
+        self.maintenance = maintenance;
+        self
+    }
+
+    pub fn with_ignores(mut self, ignores: Arc<TemporaryIgnores>) -> Self {
+        self.ignores = ignores;
+        self
+    }
+
+    // Record the latest reading, for get_state and snapshot
+    pub fn update(&self, data: &EndpointsData) {
+        *self.current.lock().unwrap() = Some(data.clone());
+    }
+
+    // Completes once poll_now has been called
+    pub async fn poll_requested(&self) {
+        self.poll_now.notified().await
+    }
+
+    // Answer one JSON-RPC request
+    pub fn handle(&self, line: &str) -> Value {
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return error(Value::Null, PARSE_ERROR, &e.to_string()),
+        };
+
+        let result = match request.method.as_str() {
+            "get_state" => Ok(json!({
+                "muted": self.dispatcher.is_muted(),
+                "maintenance": self.maintenance.patterns(),
+                "ignored": self.ignores.active(Utc::now()),
+                "endpoints": self.current.lock().unwrap().as_ref().map(|data| &data.endpoints),
+            })),
+            "poll_now" => {
+                self.poll_now.notify_one();
+                Ok(json!("polling"))
+            }
+            "mute" => {
+                self.dispatcher.set_muted(true);
+                Ok(json!("muted"))
+            }
+            "unmute" => {
+                self.dispatcher.set_muted(false);
+                Ok(json!("unmuted"))
+            }
+            "snapshot" => self.snapshot(&request.params),
+            "add_maintenance" => self.add_maintenance(&request.params),
+            "remove_maintenance" => self.remove_maintenance(&request.params),
+            "ignore" => self.ignore(&request.params),
+            "unignore" => self.unignore(&request.params),
+            _ => Err((
+                METHOD_NOT_FOUND,
+                format!("unknown method {}", request.method),
+            )),
+        };
+
+        match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": request.id, "result": result}),
+            Err((code, message)) => error(request.id, code, &message),
+        }
+    }
+
+    // Write the latest reading to {"path": ...}
+    fn snapshot(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let Some(path) = params.get("path").and_then(Value::as_str) else {
+            return Err((INVALID_PARAMS, "snapshot needs a path".to_string()));
+        };
+        let current = self.current.lock().unwrap().clone().unwrap_or_default();
+        let serialized =
+            serde_json::to_vec_pretty(&current).map_err(|e| (SERVER_ERROR, e.to_string()))?;
+        storage::write_file(PathBuf::from(path).as_path(), &serialized, false)
+            .map_err(|e| (SERVER_ERROR, e.to_string()))?;
+        Ok(json!({"path": path, "endpoints": current.endpoints.len()}))
+    }
+
+    // Put {"endpoint": ...}, a name or regex, under maintenance
+    fn add_maintenance(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let pattern = endpoint_param(params)?;
+        self.maintenance
+            .add(pattern)
+            .map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+        info!("{} is under maintenance", pattern);
+        Ok(json!(self.maintenance.patterns()))
+    }
+
+    fn remove_maintenance(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let pattern = endpoint_param(params)?;
+        if !self.maintenance.remove(pattern) {
+            return Err((
+                INVALID_PARAMS,
+                format!("{} is not under maintenance", pattern),
+            ));
+        }
+        info!("{} is no longer under maintenance", pattern);
+        Ok(json!(self.maintenance.patterns()))
+    }
+
+    // Ignore {"endpoint": ...} for {"ttl_seconds": ...}
+    fn ignore(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let endpoint = endpoint_param(params)?;
+        let Some(ttl_seconds) = params.get("ttl_seconds").and_then(Value::as_i64) else {
+            return Err((INVALID_PARAMS, "ignore needs ttl_seconds".to_string()));
+        };
+        let until = Utc::now() + Duration::seconds(ttl_seconds);
+        self.ignores.ignore(endpoint, until);
+        info!("{} is ignored until {}", endpoint, until.to_rfc3339());
+        Ok(json!(self.ignores.active(Utc::now())))
+    }
+
+    fn unignore(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let endpoint = endpoint_param(params)?;
+        if !self.ignores.remove(endpoint) {
+            return Err((INVALID_PARAMS, format!("{} is not ignored", endpoint)));
+        }
+        info!("{} is no longer ignored", endpoint);
+        Ok(json!(self.ignores.active(Utc::now())))
+    }
+}
+
+fn endpoint_param(params: &Value) -> Result<&str, (i64, String)> {
+    params
+        .get("endpoint")
+        .and_then(Value::as_str)
+        .ok_or((INVALID_PARAMS, "needs an endpoint".to_string()))
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+// Serve newline-delimited JSON-RPC on the socket until the process exits
+pub async fn serve(config: AdminConfig, admin: Arc<Admin>) {
+    // A socket left behind by an earlier run would stop the bind
+    let _ = std::fs::remove_file(&config.socket);
+    let listener = match UnixListener::bind(&config.socket) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!(
+                "Failed to start the admin socket on {}: {}",
+                config.socket.display(),
+                e
+            );
+            return;
+        }
+    };
+
+    info!("Admin socket listening on {}", config.socket.display());
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Admin socket accept failed: {}", e);
+                continue;
+            }
+        };
+        let admin = admin.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let mut response = admin.handle(&line).to_string();
+                response.push('\n');
+                if writer.write_all(response.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Formatter;
+    use crate::notify::RecordingNotifier;
+    use crate::Endpoint;
+
+    #[tokio::test]
+    async fn test_mute_and_get_state() {
+        let notifier = Arc::new(RecordingNotifier::new(Formatter::Plain));
+        let dispatcher = Arc::new(Dispatcher::new(vec![Box::new(notifier.clone())], None));
+        let admin = Admin::new(dispatcher.clone());
+        admin.update(&EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: "Not in use".into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        });
+
+        let muted = admin.handle(r#"{"jsonrpc": "2.0", "id": 1, "method": "mute"}"#);
+        assert_eq!(muted["result"], "muted");
+        dispatcher.send_text("while muted").await;
+        assert!(notifier.sent().is_empty());
+
+        let state = admin.handle(r#"{"jsonrpc": "2.0", "id": 2, "method": "get_state"}"#);
+        assert_eq!(state["id"], 2);
+        assert_eq!(state["result"]["muted"], true);
+        assert_eq!(state["result"]["endpoints"][0]["endpoint"], "500/500");
+
+        admin.handle(r#"{"jsonrpc": "2.0", "id": 3, "method": "unmute"}"#);
+        dispatcher.send_text("after unmute").await;
+        assert_eq!(notifier.sent(), vec!["after unmute"]);
+
+        let unknown = admin.handle(r#"{"jsonrpc": "2.0", "id": 4, "method": "reboot"}"#);
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_ignore_at_runtime() {
+        let dispatcher = Arc::new(Dispatcher::new(Vec::new(), None));
+        let ignores = Arc::new(TemporaryIgnores::default());
+        let admin = Admin::new(dispatcher).with_ignores(ignores.clone());
+
+        let ignored = admin.handle(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "ignore", "params": {"endpoint": "Voipfone", "ttl_seconds": 7200}}"#,
+        );
+        assert!(ignored["result"]["Voipfone"].is_string());
+        assert!(ignores.until("Voipfone", Utc::now()).is_some());
+        let state = admin.handle(r#"{"jsonrpc": "2.0", "id": 2, "method": "get_state"}"#);
+        assert!(state["result"]["ignored"]["Voipfone"].is_string());
+
+        let no_ttl = admin.handle(
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "ignore", "params": {"endpoint": "Voipfone"}}"#,
+        );
+        assert_eq!(no_ttl["error"]["code"], INVALID_PARAMS);
+
+        admin.handle(
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "unignore", "params": {"endpoint": "Voipfone"}}"#,
+        );
+        assert!(ignores.until("Voipfone", Utc::now()).is_none());
+    }
+
+    #[test]
+    fn test_maintenance_at_runtime() {
+        let dispatcher = Arc::new(Dispatcher::new(Vec::new(), None));
+        let maintenance = Arc::new(Maintenance::default());
+        let admin = Admin::new(dispatcher).with_maintenance(maintenance.clone());
+
+        let added = admin.handle(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "add_maintenance", "params": {"endpoint": "Voipfone"}}"#,
+        );
+        assert_eq!(added["result"][0], "Voipfone");
+        assert!(maintenance.matching("Voipfone").is_some());
+
+        admin.handle(
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "remove_maintenance", "params": {"endpoint": "Voipfone"}}"#,
+        );
+        assert!(maintenance.matching("Voipfone").is_none());
+        let missing = admin.handle(
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "remove_maintenance", "params": {"endpoint": "Voipfone"}}"#,
+        );
+        assert_eq!(missing["error"]["code"], INVALID_PARAMS);
+    }
+}
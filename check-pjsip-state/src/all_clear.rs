@@ -0,0 +1,146 @@
+use crate::locale::{Locale, Phrase};
+use crate::severity::StateClassifier;
+use crate::EndpointsData;
+use chrono::{DateTime, Duration, Utc};
+
+// Posts an occasional "all healthy" message during long quiet spells, so
+// silence in the channel can't be mistaken for a dead monitor
+pub struct AllClear {
+    interval: Duration,
+    quiet_since: DateTime<Utc>,
+    // The dispatcher's count of deliveries when last seen
+    sent: u64,
+}
+
+impl AllClear {
+    pub fn new(seconds: u64, now: DateTime<Utc>) -> Self {
+        AllClear {
+            interval: Duration::seconds(seconds as i64),
+            quiet_since: now,
+            sent: 0,
+        }
+    }
+
+    // Restart the quiet period
+    pub fn reset(&mut self, now: DateTime<Utc>) {
+        self.quiet_since = now;
+    }
+
+    // Restart it if anything was delivered since last seen, whether it
+    // was a change or any other notification
+    pub fn delivered(&mut self, sent: u64, now: DateTime<Utc>) {
+        if sent != self.sent {
+            self.sent = sent;
+            self.reset(now);
+        }
+    }
+
+    // The message to send once it's been quiet for the whole interval and
+    // every endpoint is healthy
+    pub fn check(
+        &mut self,
+        data: &EndpointsData,
+        classifier: &StateClassifier,
+        now: DateTime<Utc>,
+        locale: Locale,
+    ) -> Option<String> {
+        if now - self.quiet_since < self.interval {
+            return None;
+        }
+        let healthy = data
+            .endpoints
+            .iter()
+            .all(|endpoint| classifier.is_healthy(endpoint.state.as_str()));
+        if !healthy {
+            return None;
+        }
+
+        self.reset(now);
+        Some(locale.fill(Phrase::AllClear, &[&data.endpoints.len()]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn data(state: &str) -> EndpointsData {
+        EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: state.into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_all_clear_fires_after_quiet_interval_and_resets() {
+        let classifier = StateClassifier::default();
+        let start = Utc::now();
+        let mut all_clear = AllClear::new(3600, start);
+        let healthy = data("Not in use");
+
+        assert_eq!(
+            all_clear.check(
+                &healthy,
+                &classifier,
+                start + Duration::minutes(59),
+                Locale::En
+            ),
+            None
+        );
+        assert_eq!(
+            all_clear.check(
+                &healthy,
+                &classifier,
+                start + Duration::minutes(60),
+                Locale::En
+            ),
+            Some("All clear: all 1 endpoints healthy".to_string())
+        );
+
+        // A notification part way through the next interval starts it
+        // again, once
+        all_clear.delivered(1, start + Duration::minutes(90));
+        all_clear.delivered(1, start + Duration::minutes(100));
+        assert_eq!(
+            all_clear.check(
+                &healthy,
+                &classifier,
+                start + Duration::minutes(120),
+                Locale::En
+            ),
+            None
+        );
+        assert!(all_clear
+            .check(
+                &healthy,
+                &classifier,
+                start + Duration::minutes(150),
+                Locale::En
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn test_no_all_clear_while_unhealthy() {
+        let classifier = StateClassifier::default();
+        let start = Utc::now();
+        let mut all_clear = AllClear::new(60, start);
+
+        assert_eq!(
+            all_clear.check(
+                &data("Unavailable"),
+                &classifier,
+                start + Duration::hours(1),
+                Locale::En
+            ),
+            None
+        );
+    }
+}
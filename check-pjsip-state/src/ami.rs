@@ -0,0 +1,724 @@
+use crate::collector::endpoint_id;
+use log::{info, warn};
+use serde::Deserialize;
+use std::fmt;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+// [ami] config, for source = "ami": the Asterisk Manager Interface to
+// list endpoints through, so the monitor can run away from the PBX
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AmiConfig {
+    pub host: String,
+    #[serde(default = "default_ami_port")]
+    pub port: u16,
+    pub username: String,
+    pub secret: String,
+    // Give up on a poll that takes longer than this
+    #[serde(default = "default_ami_timeout_seconds")]
+    pub timeout_seconds: u64,
+    // Also keep a connection open for state change events, checking as
+    // soon as one arrives rather than waiting for the next poll
+    #[serde(default)]
+    pub events: bool,
+    // Poll for an event that doesn't say what changed no sooner than this
+    // after the last poll
+    #[serde(default = "default_event_poll_spacing_seconds")]
+    pub event_poll_spacing_seconds: u64,
+}
+
+fn default_ami_port() -> u16 {
+    5038
+}
+
+fn default_ami_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_event_poll_spacing_seconds() -> u64 {
+    5
+}
+
+// Events that can mean an endpoint's state changed
+const STATE_EVENTS: [&str; 3] = ["ContactStatus", "PeerStatus", "DeviceStateChange"];
+
+// How long to wait before reconnecting a dropped event connection
+const EVENTS_RECONNECT: Duration = Duration::from_secs(5);
+
+// Events waiting for the monitor to take them; more than this and the poll
+// already due will catch up
+const EVENTS_QUEUED: usize = 64;
+
+// A state change event, and the endpoint and state it reports where it's
+// a PJSIP device's state
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateEvent {
+    pub name: String,
+    pub subject: String,
+    pub device_state: Option<(String, String)>,
+}
+
+impl fmt::Display for StateEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} for {}", self.name, self.subject)
+    }
+}
+
+// DeviceStateChange's State as `pjsip list endpoints` writes it
+fn list_state(state: &str) -> Option<&'static str> {
+    Some(match state {
+        "UNKNOWN" => "Unknown",
+        "NOT_INUSE" => "Not in use",
+        "INUSE" => "In use",
+        "BUSY" => "Busy",
+        "INVALID" => "Invalid",
+        "UNAVAILABLE" => "Unavailable",
+        "RINGING" => "Ringing",
+        "RINGINUSE" => "Ring+Inuse",
+        "ONHOLD" => "On Hold",
+        _ => return None,
+    })
+}
+
+fn state_event(event: &Message) -> Option<StateEvent> {
+    let name = field(event, "Event").filter(|name| STATE_EVENTS.contains(name))?;
+    let subject = ["Device", "Peer", "EndpointName", "AOR"]
+        .iter()
+        .find_map(|key| field(event, key))
+        .unwrap_or("an endpoint");
+    let device_state = match (field(event, "Device"), field(event, "State")) {
+        (Some(device), Some(state)) if name == "DeviceStateChange" => device
+            .strip_prefix("PJSIP/")
+            .zip(list_state(state))
+            .map(|(endpoint, state)| (endpoint.to_string(), state.to_string())),
+        _ => None,
+    };
+    Some(StateEvent {
+        name: name.to_string(),
+        subject: subject.to_string(),
+        device_state,
+    })
+}
+
+// One AMI message, its "Key: Value" lines in order
+type Message = Vec<(String, String)>;
+
+fn field<'a>(message: &'a Message, key: &str) -> Option<&'a str> {
+    message
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.as_str())
+}
+
+// The endpoints from a PJSIPShowEndpoints event list, written out as
+// `pjsip list endpoints` would, so they're parsed like any other poll
+fn endpoints_dump(events: &[Message]) -> String {
+    let mut dump = String::new();
+    let mut count = 0;
+    for event in events {
+        if field(event, "Event") != Some("EndpointList") {
+            continue;
+        }
+        let (Some(name), Some(state)) = (field(event, "ObjectName"), field(event, "DeviceState"))
+        else {
+            continue;
+        };
+        // A comma-separated list of the endpoint's channels
+        let active = field(event, "ActiveChannels")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|channel| !channel.trim().is_empty())
+            .count();
+        dump.push_str(&format!(
+            " Endpoint:  {:<52} {:<12} {} of inf\n",
+            name, state, active
+        ));
+        count += 1;
+    }
+    dump.push_str(&format!("\nObjects found: {}\n", count));
+    dump
+}
+
+// The registrations from a PJSIPShowRegistrationsOutbound event list,
+// written out as `pjsip list registrations` would
+fn registrations_dump(events: &[Message]) -> String {
+    let mut dump = String::new();
+    let mut count = 0;
+    for event in events {
+        if field(event, "Event") != Some("OutboundRegistrationDetail") {
+            continue;
+        }
+        let (Some(name), Some(server_uri), Some(status)) = (
+            field(event, "ObjectName"),
+            field(event, "ServerUri"),
+            field(event, "Status"),
+        ) else {
+            continue;
+        };
+        let auth = field(event, "OutboundAuth").unwrap_or_default();
+        dump.push_str(&format!(
+            " {:<55} {:<16} {}\n",
+            format!("{}/{}", name, server_uri),
+            auth,
+            status
+        ));
+        count += 1;
+    }
+    dump.push_str(&format!("\nObjects found: {}\n", count));
+    dump
+}
+
+// The events from PJSIPShowEndpoint, written out as the parts of `pjsip show
+// endpoint` that parse_endpoint_detail reads
+fn endpoint_detail_dump(events: &[Message]) -> String {
+    let mut dump = String::new();
+    for event in events {
+        match field(event, "Event") {
+            Some("EndpointDetail") => {
+                for (name, key) in [
+                    ("Context", "context"),
+                    ("Callerid", "callerid"),
+                    ("Auth", "auth"),
+                    ("OutboundAuth", "outbound_auth"),
+                ] {
+                    if let Some(value) = field(event, name) {
+                        dump.push_str(&format!(" {:<34} : {}\n", key, value));
+                    }
+                }
+            }
+            Some("ContactStatusDetail") => {
+                let (Some(aor), Some(uri), Some(status)) = (
+                    field(event, "AOR"),
+                    field(event, "URI"),
+                    field(event, "Status"),
+                ) else {
+                    continue;
+                };
+                // In microseconds, where the CLI shows milliseconds
+                let rtt = field(event, "RoundtripUsec")
+                    .and_then(|usec| usec.parse::<f64>().ok())
+                    .filter(|usec| *usec > 0.0)
+                    .map_or_else(|| "nan".to_string(), |usec| format!("{:.3}", usec / 1000.0));
+                dump.push_str(&format!(
+                    "      Contact:  {:<50} {:<10} {:<7} {:>10}\n",
+                    format!("{}/{}", aor, uri),
+                    field(event, "ID").unwrap_or("-"),
+                    status,
+                    rtt
+                ));
+            }
+            _ => {}
+        }
+    }
+    dump
+}
+
+// Talks to AMI, logging in afresh for each poll so a restarted asterisk
+// needs nothing special
+pub struct AmiClient {
+    config: AmiConfig,
+    // The endpoints as last listed, with the states events have reported
+    // since
+    listing: Mutex<Vec<Message>>,
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+}
+
+impl Connection {
+    // Connect and read past the "Asterisk Call Manager/x.y.z" banner
+    async fn open(config: &AmiConfig) -> io::Result<Self> {
+        let stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+        let mut connection = Connection {
+            reader: BufReader::new(stream),
+        };
+        let mut banner = String::new();
+        connection.reader.read_line(&mut banner).await?;
+        Ok(connection)
+    }
+
+    async fn send(&mut self, action: &[(&str, &str)]) -> io::Result<()> {
+        let mut request = String::new();
+        for (key, value) in action {
+            request.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        request.push_str("\r\n");
+        self.reader.get_mut().write_all(request.as_bytes()).await
+    }
+
+    // The next message, up to its blank line
+    async fn next(&mut self) -> io::Result<Message> {
+        let mut message = Message::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "AMI closed the connection",
+                ));
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                if message.is_empty() {
+                    continue;
+                }
+                return Ok(message);
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                message.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+
+    // The response to an action, failing if asterisk refused it
+    async fn response(&mut self) -> io::Result<Message> {
+        loop {
+            let message = self.next().await?;
+            match field(&message, "Response") {
+                Some(response) if response.eq_ignore_ascii_case("error") => {
+                    let reason = field(&message, "Message").unwrap_or("no reason given");
+                    return Err(io::Error::other(format!("AMI error: {}", reason)));
+                }
+                Some(_) => return Ok(message),
+                // Events from before the response aren't ours
+                None => continue,
+            }
+        }
+    }
+}
+
+impl AmiClient {
+    pub fn new(config: &AmiConfig) -> Self {
+        AmiClient {
+            config: config.clone(),
+            listing: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn wants_events(&self) -> bool {
+        self.config.events
+    }
+
+    pub fn event_poll_spacing(&self) -> Duration {
+        Duration::from_secs(self.config.event_poll_spacing_seconds)
+    }
+
+    // Listen for state change events until the receiver is dropped,
+    // reconnecting whenever the connection is lost
+    pub fn watch_events(&self) -> mpsc::Receiver<StateEvent> {
+        let (sender, receiver) = mpsc::channel(EVENTS_QUEUED);
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            while !sender.is_closed() {
+                match listen(&config, &sender).await {
+                    Ok(()) => return,
+                    Err(e) => warn!(
+                        "Lost the AMI event connection, reconnecting in {}s: {}",
+                        EVENTS_RECONNECT.as_secs(),
+                        e
+                    ),
+                }
+                tokio::time::sleep(EVENTS_RECONNECT).await;
+            }
+        });
+        receiver
+    }
+
+    // The same output as `pjsip list endpoints`, from AMI
+    pub async fn list_endpoints(&self) -> io::Result<String> {
+        let events = self
+            .list("PJSIPShowEndpoints", &[], "EndpointListComplete")
+            .await?;
+        let dump = endpoints_dump(&events);
+        *self.listing.lock().unwrap() = events;
+        Ok(dump)
+    }
+
+    // The last listing with the endpoint's state as the event reports it,
+    // written out as list_endpoints does, or None if the event doesn't say
+    // or the endpoint wasn't listed
+    pub fn reading_after(&self, event: &StateEvent) -> Option<String> {
+        let (endpoint, state) = event.device_state.as_ref()?;
+        let mut listing = self.listing.lock().unwrap();
+        let listed = listing.iter_mut().find(|listed| {
+            field(listed, "Event") == Some("EndpointList")
+                && field(listed, "ObjectName") == Some(endpoint.as_str())
+        })?;
+        match listed
+            .iter_mut()
+            .find(|(key, _)| key.eq_ignore_ascii_case("DeviceState"))
+        {
+            Some((_, value)) => *value = state.clone(),
+            None => listed.push(("DeviceState".to_string(), state.clone())),
+        }
+        Some(endpoints_dump(&listing))
+    }
+
+    // The same output as `pjsip list registrations`, from AMI
+    pub async fn list_registrations(&self) -> io::Result<String> {
+        let events = self
+            .list(
+                "PJSIPShowRegistrationsOutbound",
+                &[],
+                "OutboundRegistrationDetailComplete",
+            )
+            .await?;
+        Ok(registrations_dump(&events))
+    }
+
+    // Much of what `pjsip show endpoint <id>` gives, from AMI, for an
+    // endpoint as listed, with or without its caller ID
+    pub async fn show_endpoint(&self, endpoint: &str) -> io::Result<String> {
+        let events = self
+            .list(
+                "PJSIPShowEndpoint",
+                &[("Endpoint", endpoint_id(endpoint))],
+                "EndpointDetailComplete",
+            )
+            .await?;
+        Ok(endpoint_detail_dump(&events))
+    }
+
+    // The events an action lists, up to the one that completes the list
+    async fn list(
+        &self,
+        action: &str,
+        params: &[(&str, &str)],
+        complete: &str,
+    ) -> io::Result<Vec<Message>> {
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        tokio::time::timeout(timeout, self.fetch(action, params, complete))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "AMI timed out"))?
+    }
+
+    async fn fetch(
+        &self,
+        action: &str,
+        params: &[(&str, &str)],
+        complete: &str,
+    ) -> io::Result<Vec<Message>> {
+        let mut connection = Connection::open(&self.config).await?;
+        connection
+            .send(&[
+                ("Action", "Login"),
+                ("Username", &self.config.username),
+                ("Secret", &self.config.secret),
+                ("Events", "off"),
+            ])
+            .await?;
+        connection.response().await?;
+
+        let mut headers = vec![("Action", action), ("ActionID", "check-pjsip-state")];
+        headers.extend_from_slice(params);
+        connection.send(&headers).await?;
+        let events = match connection.response().await {
+            Ok(_) => {
+                let mut events = Vec::new();
+                loop {
+                    let event = connection.next().await?;
+                    if field(&event, "Event") == Some(complete) {
+                        break;
+                    }
+                    events.push(event);
+                }
+                events
+            }
+            // What asterisk says when there are none to list, e.g. "No
+            // endpoints found"
+            Err(e) if e.to_string().contains("found") => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        // Logging off is a courtesy; the reading is already complete
+        let _ = connection.send(&[("Action", "Logoff")]).await;
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+pub fn test_config(port: u16) -> AmiConfig {
+    AmiConfig {
+        host: "127.0.0.1".to_string(),
+        port,
+        username: "monitor".to_string(),
+        secret: "s3cret".to_string(),
+        timeout_seconds: 5,
+        events: true,
+        event_poll_spacing_seconds: 5,
+    }
+}
+
+#[cfg(test)]
+impl AmiClient {
+    // A client that has already listed these endpoints and states
+    pub fn listed(config: &AmiConfig, endpoints: &[(&str, &str)]) -> Self {
+        let client = AmiClient::new(config);
+        *client.listing.lock().unwrap() = endpoints
+            .iter()
+            .map(|(name, state)| {
+                vec![
+                    ("Event".to_string(), "EndpointList".to_string()),
+                    ("ObjectName".to_string(), name.to_string()),
+                    ("DeviceState".to_string(), state.to_string()),
+                ]
+            })
+            .collect();
+        client
+    }
+}
+
+// Log in with events on and pass state changes along, returning once
+// nobody is listening
+async fn listen(config: &AmiConfig, sender: &mpsc::Sender<StateEvent>) -> io::Result<()> {
+    let mut connection = Connection::open(config).await?;
+    connection
+        .send(&[
+            ("Action", "Login"),
+            ("Username", &config.username),
+            ("Secret", &config.secret),
+            ("Events", "system,call"),
+        ])
+        .await?;
+    connection.response().await?;
+    info!("Listening for AMI events from {}", config.host);
+
+    loop {
+        let Some(event) = state_event(&connection.next().await?) else {
+            continue;
+        };
+        match sender.try_send(event) {
+            Err(mpsc::error::TrySendError::Closed(_)) => return Ok(()),
+            // Plenty is already waiting to be checked
+            Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_endpoints_are_listed_through_ami() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream
+                .write_all(b"Asterisk Call Manager/7.0.3\r\n")
+                .await
+                .unwrap();
+            let mut received = String::new();
+            let mut buf = [0; 1024];
+            // Login, then PJSIPShowEndpoints
+            for reply in [
+                "Response: Success\r\nMessage: Authentication accepted\r\n\r\n",
+                concat!(
+                    "Response: Success\r\nActionID: check-pjsip-state\r\nEventList: start\r\n\r\n",
+                    "Event: EndpointList\r\nObjectName: 500\r\nDeviceState: Not in use\r\nActiveChannels: \r\n\r\n",
+                    "Event: EndpointList\r\nObjectName: Voipfone\r\nDeviceState: In use\r\nActiveChannels: PJSIP/Voipfone-00000001,PJSIP/Voipfone-00000002\r\n\r\n",
+                    "Event: EndpointListComplete\r\nEventList: Complete\r\nListItems: 2\r\n\r\n",
+                ),
+            ] {
+                while !received.ends_with("\r\n\r\n") {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    received.push_str(std::str::from_utf8(&buf[..read]).unwrap());
+                }
+                stream.write_all(reply.as_bytes()).await.unwrap();
+                received.push('.');
+            }
+            received
+        });
+
+        let client = AmiClient::new(&AmiConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: "monitor".to_string(),
+            secret: "s3cret".to_string(),
+            timeout_seconds: 5,
+            events: false,
+            event_poll_spacing_seconds: 5,
+        });
+        let dump = client.list_endpoints().await.unwrap();
+        let requests = server.await.unwrap();
+        assert!(requests.contains("Action: Login\r\nUsername: monitor\r\nSecret: s3cret"));
+        assert!(requests.contains("Action: PJSIPShowEndpoints"));
+
+        let data = crate::get_pjsip_endpoints(&dump);
+        assert_eq!(data.endpoints.len(), 2);
+        assert_eq!(data.endpoints[0].endpoint, "500");
+        assert_eq!(data.endpoints[0].state, "Not in use");
+        assert_eq!(data.endpoints[1].endpoint, "Voipfone");
+        assert_eq!(data.endpoints[1].state, "In use");
+        assert_eq!(data.endpoints[1].active_channels, 2);
+        assert!(dump.contains("Objects found: 2"));
+    }
+
+    #[tokio::test]
+    async fn test_state_events_are_passed_on() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream
+                .write_all(b"Asterisk Call Manager/7.0.3\r\n")
+                .await
+                .unwrap();
+            let mut received = String::new();
+            let mut buf = [0; 1024];
+            while !received.ends_with("\r\n\r\n") {
+                let read = stream.read(&mut buf).await.unwrap();
+                received.push_str(std::str::from_utf8(&buf[..read]).unwrap());
+            }
+            assert!(received.contains("Events: system,call"));
+            let reply = concat!(
+                "Response: Success\r\nMessage: Authentication accepted\r\n\r\n",
+                "Event: Newchannel\r\nChannel: PJSIP/500-00000001\r\n\r\n",
+                "Event: DeviceStateChange\r\nDevice: PJSIP/500\r\nState: UNAVAILABLE\r\n\r\n",
+            );
+            stream.write_all(reply.as_bytes()).await.unwrap();
+            // Hold the connection open
+            let _ = stream.read(&mut buf).await;
+        });
+
+        let client = AmiClient::new(&test_config(port));
+        assert!(client.wants_events());
+        let mut events = client.watch_events();
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.to_string(), "DeviceStateChange for PJSIP/500");
+        assert_eq!(
+            event.device_state,
+            Some(("500".to_string(), "Unavailable".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_device_states_are_read_into_the_last_listing() {
+        let client = AmiClient::listed(
+            &test_config(5038),
+            &[("500", "Not in use"), ("Voipfone", "Not in use")],
+        );
+        let event = |pairs: &[(&str, &str)]| {
+            let message: Message = pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            state_event(&message).unwrap()
+        };
+
+        let unavailable = event(&[
+            ("Event", "DeviceStateChange"),
+            ("Device", "PJSIP/500"),
+            ("State", "UNAVAILABLE"),
+        ]);
+        let data = crate::get_pjsip_endpoints(&client.reading_after(&unavailable).unwrap());
+        assert_eq!(data.endpoints[0].state, "Unavailable");
+        assert_eq!(data.endpoints[1].state, "Not in use");
+
+        // Later readings build on it
+        let in_use = event(&[
+            ("Event", "DeviceStateChange"),
+            ("Device", "PJSIP/Voipfone"),
+            ("State", "INUSE"),
+        ]);
+        let data = crate::get_pjsip_endpoints(&client.reading_after(&in_use).unwrap());
+        assert_eq!(data.endpoints[0].state, "Unavailable");
+        assert_eq!(data.endpoints[1].state, "In use");
+
+        // Nothing to go on without a listed PJSIP device and its state
+        for pairs in [
+            &[
+                ("Event", "DeviceStateChange"),
+                ("Device", "PJSIP/599"),
+                ("State", "INUSE"),
+            ][..],
+            &[
+                ("Event", "DeviceStateChange"),
+                ("Device", "Queue:support"),
+                ("State", "INUSE"),
+            ],
+            &[
+                ("Event", "ContactStatus"),
+                ("EndpointName", "500"),
+                ("ContactStatus", "Unreachable"),
+            ],
+        ] {
+            assert_eq!(client.reading_after(&event(pairs)), None);
+        }
+    }
+
+    #[test]
+    fn test_registrations_dump_parses_like_the_cli() {
+        let event = |pairs: &[(&str, &str)]| -> Message {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+        let dump = registrations_dump(&[
+            event(&[
+                ("Event", "OutboundRegistrationDetail"),
+                ("ObjectName", "voipfone"),
+                ("ServerUri", "sip:sip.voipfone.net"),
+                ("OutboundAuth", ""),
+                ("Status", "Rejected"),
+            ]),
+            event(&[("Event", "AuthDetail"), ("ObjectName", "voipfone")]),
+        ]);
+        let data = crate::registrations::parse_registrations(&dump);
+        assert_eq!(data.registrations.len(), 1);
+        assert_eq!(data.registrations[0].name, "voipfone");
+        assert_eq!(data.registrations[0].status, "Rejected");
+    }
+
+    #[test]
+    fn test_endpoint_detail_dump_parses_like_the_cli() {
+        let event = |pairs: &[(&str, &str)]| -> Message {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+        let dump = endpoint_detail_dump(&[
+            event(&[
+                ("Event", "EndpointDetail"),
+                ("ObjectName", "500"),
+                ("Context", "from-internal"),
+                ("Auth", "500"),
+                ("OutboundAuth", ""),
+            ]),
+            event(&[
+                ("Event", "ContactStatusDetail"),
+                ("AOR", "500"),
+                ("URI", "sip:500@192.168.1.20:5060"),
+                ("Status", "Reachable"),
+                ("RoundtripUsec", "12345"),
+            ]),
+            event(&[
+                ("Event", "ContactStatusDetail"),
+                ("AOR", "500"),
+                ("URI", "sip:500@10.0.0.7:5060"),
+                ("Status", "Unreachable"),
+                ("RoundtripUsec", "0"),
+            ]),
+        ]);
+        let detail = crate::asterisk::parse_endpoint_detail(&dump);
+        assert_eq!(detail.context.as_deref(), Some("from-internal"));
+        assert_eq!(detail.auth.as_deref(), Some("500"));
+        assert_eq!(detail.outbound_auth, None);
+        assert_eq!(detail.contacts.len(), 2);
+        assert_eq!(detail.contacts[0].uri, "sip:500@192.168.1.20:5060");
+        assert_eq!(detail.contacts[0].status, "Reachable");
+        assert_eq!(detail.contacts[0].rtt_ms, Some(12.345));
+        assert_eq!(detail.contacts[1].rtt_ms, None);
+    }
+}
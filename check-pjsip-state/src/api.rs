@@ -0,0 +1,370 @@
+use crate::diff::EndpointChange;
+use crate::metrics::{self, SharedMetrics};
+use crate::Endpoint;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// Optional [api] config for the read-only HTTP server
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiConfig {
+    pub listen_addr: String,
+    // Number of recent change events kept in memory
+    #[serde(default = "default_history_size")]
+    pub history_size: usize,
+    // Label /metrics series by endpoint, one series per endpoint
+    #[serde(default = "default_per_endpoint_metrics")]
+    pub per_endpoint_metrics: bool,
+    // GET /healthz fails once any source hasn't read its endpoints for
+    // this long, three poll intervals if unset
+    pub max_poll_age_seconds: Option<u64>,
+}
+
+fn default_history_size() -> usize {
+    100
+}
+
+fn default_per_endpoint_metrics() -> bool {
+    true
+}
+
+// A detected change along with when it was seen
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub change: EndpointChange,
+}
+
+// Bounded buffer of the most recent change events, oldest first
+#[derive(Debug)]
+pub struct ChangeHistory {
+    events: VecDeque<ChangeEvent>,
+    capacity: usize,
+}
+
+impl ChangeHistory {
+    pub fn new(capacity: usize) -> Self {
+        ChangeHistory {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, event: ChangeEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    // The last `limit` events, in the order they happened
+    pub fn recent(&self, limit: usize) -> Vec<ChangeEvent> {
+        let skip = self.events.len().saturating_sub(limit);
+        self.events.iter().skip(skip).cloned().collect()
+    }
+}
+
+pub type SharedHistory = Arc<Mutex<ChangeHistory>>;
+
+#[derive(Deserialize, Debug)]
+pub struct ChangesQuery {
+    limit: Option<usize>,
+}
+
+// GET /changes?limit=N
+async fn get_changes(
+    State(history): State<SharedHistory>,
+    Query(query): Query<ChangesQuery>,
+) -> Json<Vec<ChangeEvent>> {
+    let history = history.lock().unwrap();
+    let limit = query.limit.unwrap_or(history.capacity);
+    Json(history.recent(limit))
+}
+
+// What the probes judge the poll loops by
+#[derive(Clone)]
+pub struct Health {
+    metrics: SharedMetrics,
+    max_poll_age: Duration,
+    // How many sources have to have polled before the monitor is ready
+    sources: usize,
+    // The first poll is given as long as any other from here
+    started: DateTime<Utc>,
+}
+
+impl Health {
+    pub fn new(metrics: SharedMetrics, max_poll_age_seconds: u64, sources: usize) -> Self {
+        Health {
+            metrics,
+            max_poll_age: Duration::seconds(max_poll_age_seconds as i64),
+            sources: sources.max(1),
+            started: Utc::now(),
+        }
+    }
+
+    // Alive while every source read its endpoints recently enough, judged
+    // by the one that polled longest ago
+    fn live(&self, now: DateTime<Utc>) -> Result<String, String> {
+        let metrics = self.metrics.lock().unwrap();
+        let readings = metrics.readings();
+        let oldest = readings
+            .iter()
+            .map(|(source, checked, _)| checked.map(|checked| (checked, *source)))
+            .min()
+            .flatten()
+            .filter(|_| readings.len() >= self.sources);
+        match oldest {
+            Some((checked, _)) if now - checked <= self.max_poll_age => {
+                Ok(format!("ok, last polled {}", checked.to_rfc3339()))
+            }
+            Some((checked, "")) => Err(format!("no poll since {}", checked.to_rfc3339())),
+            Some((checked, source)) => Err(format!(
+                "no poll of {} since {}",
+                source,
+                checked.to_rfc3339()
+            )),
+            None if now - self.started <= self.max_poll_age => {
+                Ok("ok, waiting for the first poll".to_string())
+            }
+            None => Err("no poll yet".to_string()),
+        }
+    }
+
+    // Ready once every source has read its endpoints
+    fn ready(&self) -> Result<String, String> {
+        let polled = self.metrics.lock().unwrap().readings().len();
+        if polled >= self.sources {
+            Ok("ready".to_string())
+        } else {
+            Err(format!(
+                "waiting for the first poll, {} of {} sources polled",
+                polled, self.sources
+            ))
+        }
+    }
+}
+
+fn probe(result: Result<String, String>) -> (StatusCode, String) {
+    match result {
+        Ok(message) => (StatusCode::OK, message),
+        Err(message) => (StatusCode::SERVICE_UNAVAILABLE, message),
+    }
+}
+
+// GET /healthz, for a liveness probe
+async fn get_healthz(State(health): State<Health>) -> (StatusCode, String) {
+    probe(health.live(Utc::now()))
+}
+
+// GET /readyz, for a readiness probe
+async fn get_readyz(State(health): State<Health>) -> (StatusCode, String) {
+    probe(health.ready())
+}
+
+// One source's last reading, as GET /state returns it
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SourceState {
+    pub source: Option<String>,
+    pub checked_at: Option<DateTime<Utc>>,
+    pub endpoints: Vec<Endpoint>,
+}
+
+// GET /state, the endpoints as each source last read them
+async fn get_state(State(health): State<Health>) -> Json<Vec<SourceState>> {
+    let metrics = health.metrics.lock().unwrap();
+    let states = metrics
+        .readings()
+        .into_iter()
+        .map(|(source, checked_at, data)| SourceState {
+            source: Some(source.to_string()).filter(|source| !source.is_empty()),
+            checked_at,
+            endpoints: data.endpoints.clone(),
+        })
+        .collect();
+    Json(states)
+}
+
+pub fn router(history: SharedHistory, health: Health) -> Router {
+    Router::new()
+        .route("/changes", get(get_changes))
+        .with_state(history)
+        .merge(
+            Router::new()
+                .route("/metrics", get(metrics::get_metrics))
+                .with_state(health.metrics.clone()),
+        )
+        .merge(
+            Router::new()
+                .route("/healthz", get(get_healthz))
+                .route("/readyz", get(get_readyz))
+                .route("/state", get(get_state))
+                .with_state(health),
+        )
+}
+
+// Run the API server until the process exits
+pub async fn serve(listen_addr: String, history: SharedHistory, health: Health) {
+    let listener = match tokio::net::TcpListener::bind(&listen_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to start API server on {}: {}", listen_addr, e);
+            return;
+        }
+    };
+
+    info!("API server listening on {}", listen_addr);
+    if let Err(e) = axum::serve(listener, router(history, health)).await {
+        error!("API server failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::severity::StateClassifier;
+    use crate::EndpointsData;
+
+    fn event(name: &str) -> ChangeEvent {
+        ChangeEvent {
+            timestamp: Utc::now(),
+            change: EndpointChange::Added(Endpoint {
+                endpoint: name.to_string(),
+                state: "Not in use".into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }),
+        }
+    }
+
+    fn names(events: &[ChangeEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match &event.change {
+                EndpointChange::Added(endpoint) => endpoint.endpoint.clone(),
+                other => panic!("unexpected change {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = ChangeHistory::new(2);
+        history.push(event("500/500"));
+        history.push(event("501/501"));
+        history.push(event("502/502"));
+
+        assert_eq!(names(&history.recent(10)), vec!["501/501", "502/502"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_changes_returns_recent_in_order() {
+        let history = Arc::new(Mutex::new(ChangeHistory::new(10)));
+        for name in ["500/500", "501/501", "502/502"] {
+            history.lock().unwrap().push(event(name));
+        }
+
+        let Json(events) = get_changes(
+            State(history.clone()),
+            Query(ChangesQuery { limit: Some(2) }),
+        )
+        .await;
+
+        assert_eq!(names(&events), vec!["501/501", "502/502"]);
+
+        // Check the shape of the JSON returned to clients
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["change"], "added");
+        assert_eq!(json["endpoint"], "501/501");
+        assert!(json["timestamp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_probes_and_state_follow_the_polls() {
+        let metrics = Arc::new(Mutex::new(Metrics::new(false)));
+        let health = Health::new(metrics.clone(), 180, 2);
+        let now = health.started;
+        assert!(health.live(now).is_ok());
+        assert_eq!(
+            health.live(now + Duration::seconds(181)),
+            Err("no poll yet".to_string())
+        );
+        assert!(health.ready().is_err());
+
+        let data = |name: &str| EndpointsData {
+            endpoints: vec![match event(name).change {
+                EndpointChange::Added(endpoint) => endpoint,
+                _ => unreachable!(),
+            }],
+        };
+        let classifier = StateClassifier::default();
+        for source in ["pbx1", "pbx2"] {
+            metrics
+                .lock()
+                .unwrap()
+                .record_poll(source, &data("500/500"), &classifier, 0, now);
+        }
+        assert_eq!(health.ready(), Ok("ready".to_string()));
+        assert!(health.live(now + Duration::seconds(180)).is_ok());
+        assert_eq!(
+            probe(health.live(now + Duration::seconds(181))).0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let Json(states) = get_state(State(health)).await;
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[1].source.as_deref(), Some("pbx2"));
+        let json = serde_json::to_value(&states[0]).unwrap();
+        assert_eq!(json["endpoints"][0]["endpoint"], "500/500");
+        assert_eq!(json["endpoints"][0]["state"], "Not in use");
+    }
+
+    #[test]
+    fn test_one_stale_source_fails_liveness() {
+        let metrics = Arc::new(Mutex::new(Metrics::new(false)));
+        let health = Health::new(metrics.clone(), 180, 2);
+        let now = health.started;
+        let data = EndpointsData {
+            endpoints: Vec::new(),
+        };
+        let classifier = StateClassifier::default();
+
+        // One of two sources polling isn't enough once the first poll is due
+        metrics
+            .lock()
+            .unwrap()
+            .record_poll("pbx1", &data, &classifier, 0, now);
+        assert_eq!(
+            health.live(now + Duration::seconds(181)),
+            Err("no poll yet".to_string())
+        );
+
+        metrics
+            .lock()
+            .unwrap()
+            .record_poll("pbx2", &data, &classifier, 0, now);
+        for minute in 1..=4 {
+            let later = now + Duration::minutes(minute);
+            metrics
+                .lock()
+                .unwrap()
+                .record_poll("pbx1", &data, &classifier, 0, later);
+        }
+        assert_eq!(
+            health.live(now + Duration::minutes(4)),
+            Err(format!("no poll of pbx2 since {}", now.to_rfc3339()))
+        );
+    }
+}
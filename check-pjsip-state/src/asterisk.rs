@@ -0,0 +1,418 @@
+use log::warn;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io;
+use std::process::Command;
+
+pub fn default_asterisk_binary() -> String {
+    "asterisk".to_string()
+}
+
+pub fn default_asterisk_command() -> Vec<String> {
+    vec!["-rx".to_string()]
+}
+
+// How to reach the asterisk CLI: the binary, and the arguments that come
+// before each CLI command, e.g. `sudo asterisk -rx` or `docker exec pbx asterisk -rx`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asterisk {
+    binary: String,
+    args: Vec<String>,
+    strip_control_sequences: bool,
+}
+
+impl Default for Asterisk {
+    fn default() -> Self {
+        Asterisk::new(&default_asterisk_binary(), &default_asterisk_command())
+    }
+}
+
+impl Asterisk {
+    pub fn new(binary: &str, args: &[String]) -> Self {
+        Asterisk {
+            binary: binary.to_string(),
+            args: args.to_vec(),
+            strip_control_sequences: false,
+        }
+    }
+
+    pub fn with_strip_control_sequences(mut self, strip: bool) -> Self {
+        self.strip_control_sequences = strip;
+        self
+    }
+
+    // Run a CLI command and return what it printed. Exiting unsuccessfully,
+    // e.g. when asterisk isn't running to connect to, is an error. It runs
+    // on the blocking pool so a slow asterisk doesn't hold up other sources.
+    pub async fn run_command(&self, command: &str) -> io::Result<String> {
+        let asterisk = self.clone();
+        let command = command.to_string();
+        tokio::task::spawn_blocking(move || asterisk.run_blocking(&command))
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)))
+    }
+
+    fn run_blocking(&self, command: &str) -> io::Result<String> {
+        let output = Command::new(&self.binary)
+            .args(&self.args)
+            .arg(command)
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let message = [stderr.trim(), stdout.trim()]
+                .into_iter()
+                .find(|output| !output.is_empty())
+                .unwrap_or("no output");
+            return Err(io::Error::other(format!(
+                "{} exited with {}: {}",
+                self.binary, output.status, message
+            )));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if self.strip_control_sequences {
+            return Ok(strip_control_sequences(&stdout));
+        }
+        Ok(stdout.into_owned())
+    }
+
+    // Ask asterisk for its version, or None if it couldn't be found
+    pub async fn version(&self) -> Option<String> {
+        match self.run_command("core show version").await {
+            Ok(output) => {
+                let version = parse_version(&output);
+                if version.is_none() {
+                    warn!("No version found in `core show version` output");
+                }
+                version
+            }
+            Err(e) => {
+                warn!("Failed to fetch the asterisk version: {}", e);
+                None
+            }
+        }
+    }
+
+    // Ask asterisk how long it's been up, or None if it couldn't be found
+    pub async fn uptime(&self) -> Option<String> {
+        match self.run_command("core show uptime").await {
+            Ok(output) => {
+                let uptime = parse_uptime(&output);
+                if uptime.is_none() {
+                    warn!("No uptime found in `core show uptime` output");
+                }
+                uptime
+            }
+            Err(e) => {
+                warn!("Failed to fetch the asterisk uptime: {}", e);
+                None
+            }
+        }
+    }
+}
+
+// A contact bound to an endpoint's AOR, as its last qualify left it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactDetail {
+    pub uri: String,
+    // "Avail", "Unavail", "NonQual" and so on
+    pub status: String,
+    // None when it isn't qualified
+    pub rtt_ms: Option<f64>,
+}
+
+// The parts of `pjsip show endpoint <name>` worth including in notifications
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EndpointDetail {
+    pub context: Option<String>,
+    pub callerid: Option<String>,
+    pub contacts: Vec<ContactDetail>,
+    pub auth: Option<String>,
+    pub outbound_auth: Option<String>,
+    // The status of the outbound registration of the same name, which
+    // `pjsip show endpoint` doesn't give but `pjsip list registrations` does
+    pub registration: Option<String>,
+}
+
+impl EndpointDetail {
+    pub fn to_details(&self) -> Vec<(String, String)> {
+        let mut details = Vec::new();
+        if let Some(context) = &self.context {
+            details.push(("context".to_string(), context.clone()));
+        }
+        if let Some(callerid) = &self.callerid {
+            details.push(("callerid".to_string(), callerid.clone()));
+        }
+        for contact in &self.contacts {
+            let qualify = match contact.rtt_ms {
+                Some(rtt_ms) => format!("{}, {:.3} ms", contact.status, rtt_ms),
+                None => contact.status.clone(),
+            };
+            details.push((
+                "contact".to_string(),
+                format!("{} ({})", contact.uri, qualify),
+            ));
+        }
+        if let Some(auth) = &self.auth {
+            details.push(("auth".to_string(), auth.clone()));
+        }
+        if let Some(outbound_auth) = &self.outbound_auth {
+            details.push(("outbound_auth".to_string(), outbound_auth.clone()));
+        }
+        if let Some(registration) = &self.registration {
+            details.push(("registration".to_string(), registration.clone()));
+        }
+        details
+    }
+}
+
+// Escape sequences a terminal would act on rather than show: CSI ("\x1b[1;32m"),
+// OSC ("\x1b]0;title\x07") and the two-byte forms
+const ESCAPE_PATTERN: &str = r"\x1b(\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(\x07|\x1b\\)?|[@-Z\\-_])";
+
+// Whether a line is asterisk talking to a terminal rather than command
+// output: its "Connected to Asterisk" banner or a "pbx*CLI>" prompt
+fn is_prompt_line(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("Connected to Asterisk") || line.contains("*CLI>")
+}
+
+// Clean up what `asterisk -rx` prints when it thinks it has a terminal:
+// escape sequences, carriage-return redraws (where the last text written
+// after a \r is what was left showing), other control characters and
+// prompt lines
+pub fn strip_control_sequences(output: &str) -> String {
+    let escapes = Regex::new(ESCAPE_PATTERN).unwrap();
+    let output = escapes.replace_all(output, "");
+    output
+        .split('\n')
+        .map(|line| {
+            line.rsplit('\r')
+                .find(|part| !part.trim().is_empty())
+                .unwrap_or("")
+        })
+        .map(|line| {
+            line.chars()
+                .filter(|c| *c == '\t' || !c.is_control())
+                .collect::<String>()
+        })
+        .filter(|line| !is_prompt_line(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Pull the contacts and auths out of the object lines at the top, and
+// context, callerid and the auths out of the `ParameterName : ParameterValue`
+// table below them
+pub fn parse_endpoint_detail(output: &str) -> EndpointDetail {
+    let parameter = Regex::new(r"^\s*(\S+)\s+:\s*(.*?)\s*$").unwrap();
+    // "Contact:  500/sip:500@192.168.1.20:5060  5e8d3b47af Avail  12.345"
+    let contact = Regex::new(r"^\s*Contact:\s+(\S+)\s+\S+\s+(\S+)\s+(\S+)\s*$").unwrap();
+    let auth = Regex::new(r"^\s*(InAuth|OutAuth):\s+(\S+)").unwrap();
+    let mut detail = EndpointDetail::default();
+
+    for line in output.lines() {
+        if let Some(captures) = contact.captures(line) {
+            // The AOR comes first, and AOR names may have slashes too
+            let uri = &captures[1];
+            if uri.starts_with('<') {
+                continue;
+            }
+            let uri = uri.find("/sip").map_or(uri, |index| &uri[index + 1..]);
+            detail.contacts.push(ContactDetail {
+                uri: uri.to_string(),
+                status: captures[2].to_string(),
+                rtt_ms: captures[3].parse().ok().filter(|rtt: &f64| rtt.is_finite()),
+            });
+        } else if let Some(captures) = auth.captures(line) {
+            // Skipping the "<AuthId/UserName....>" heading
+            let value = Some(captures[2].to_string()).filter(|value| !value.starts_with('<'));
+            match &captures[1] {
+                "InAuth" => detail.auth = detail.auth.or(value),
+                _ => detail.outbound_auth = detail.outbound_auth.or(value),
+            }
+        } else if let Some(captures) = parameter.captures(line) {
+            let value = captures[2].to_string();
+            if value.is_empty() {
+                continue;
+            }
+            match &captures[1] {
+                "context" => detail.context = Some(value),
+                "callerid" => detail.callerid = Some(value),
+                "auth" => detail.auth = Some(value),
+                "outbound_auth" => detail.outbound_auth = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    detail
+}
+
+// The details last read for each endpoint, to fall back on when asterisk
+// can't be asked. They're read afresh for each change, since contacts and
+// their round trip times move.
+#[derive(Default)]
+pub struct DetailCache {
+    cache: HashMap<String, EndpointDetail>,
+}
+
+impl DetailCache {
+    // What `pjsip show endpoint` gave this time, or the last good reading
+    pub fn update(&mut self, endpoint: &str, output: io::Result<String>) -> EndpointDetail {
+        match output {
+            Ok(output) => {
+                let detail = parse_endpoint_detail(&output);
+                self.cache.insert(endpoint.to_string(), detail.clone());
+                detail
+            }
+            Err(e) => {
+                warn!("Failed to fetch details for {}: {}", endpoint, e);
+                self.cache.get(endpoint).cloned().unwrap_or_default()
+            }
+        }
+    }
+}
+
+// The version from `core show version`, e.g. "18.10.0"
+pub fn parse_version(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("Asterisk"), Some(version)) => Some(version.to_string()),
+            _ => None,
+        }
+    })
+}
+
+// How long asterisk has been running, from the "System uptime:" line of
+// `core show uptime`, e.g. "2 days, 3 hours, 4 minutes, 5 seconds"
+pub fn parse_uptime(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let uptime = line.trim().strip_prefix("System uptime:")?.trim();
+        (!uptime.is_empty()).then(|| uptime.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_configured_invocation() {
+        let echo = Asterisk::new("echo", &["-n".to_string()]);
+        assert_eq!(
+            echo.run_command("pjsip list endpoints").await.unwrap(),
+            "pjsip list endpoints"
+        );
+
+        let failing = Asterisk::new("false", &[]);
+        assert!(failing.run_command("pjsip list endpoints").await.is_err());
+        let missing = Asterisk::new("/nonexistent/asterisk", &[]);
+        assert!(missing.run_command("pjsip list endpoints").await.is_err());
+    }
+
+    #[test]
+    fn test_parse_version() {
+        let output = "Asterisk 18.10.0~dfsg+~cs6.10.40431411-2 built by pbuilder @ localhost on a x86_64 running Linux on 2022-03-18 08:40:12 UTC\n";
+        assert_eq!(
+            parse_version(output),
+            Some("18.10.0~dfsg+~cs6.10.40431411-2".to_string())
+        );
+        assert_eq!(parse_version("No such command 'core show version'"), None);
+    }
+
+    #[test]
+    fn test_parse_uptime() {
+        let output = "System uptime: 2 weeks, 3 days, 4 hours, 5 minutes, 6 seconds\n\
+                      Last reload: 1 hour, 2 minutes, 3 seconds\n";
+        assert_eq!(
+            parse_uptime(output),
+            Some("2 weeks, 3 days, 4 hours, 5 minutes, 6 seconds".to_string())
+        );
+        assert_eq!(parse_uptime("No such command 'core show uptime'"), None);
+    }
+
+    #[test]
+    fn test_parse_endpoint_detail() {
+        let output = r#"
+ Endpoint:  <Endpoint/CID.....................................>  <State.....>  <Channels.>
+    I/OAuth:  <AuthId/UserName..............................................................>
+        Aor:  <Aor............................................>  <MaxContact>
+      Contact:  <Aor/ContactUri..........................> <Hash....> <Status> <RTT(ms)..>
+==========================================================================================
+
+ Endpoint:  500/500                                              Not in use    0 of inf
+     InAuth:  500/500
+        Aor:  500/500                                            1
+      Contact:  500/sip:500@192.168.1.20:5060;ob                 5e8d3b47af Avail        12.345
+      Contact:  500/sip:500@10.0.0.7:5060                        a1b2c3d4e5 NonQual         nan
+
+
+ ParameterName                      : ParameterValue
+ ==============================================================================================
+ accountcode                        :
+ callerid                           : "Front Desk" <500>
+ callerid_privacy                   : allowed_not_screened
+ context                            : from-internal
+ direct_media                       : true
+"#;
+
+        assert_eq!(
+            parse_endpoint_detail(output),
+            EndpointDetail {
+                context: Some("from-internal".to_string()),
+                callerid: Some("\"Front Desk\" <500>".to_string()),
+                contacts: vec![
+                    ContactDetail {
+                        uri: "sip:500@192.168.1.20:5060;ob".to_string(),
+                        status: "Avail".to_string(),
+                        rtt_ms: Some(12.345),
+                    },
+                    ContactDetail {
+                        uri: "sip:500@10.0.0.7:5060".to_string(),
+                        status: "NonQual".to_string(),
+                        rtt_ms: None,
+                    },
+                ],
+                auth: Some("500/500".to_string()),
+                outbound_auth: None,
+                registration: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_details_fall_back_to_the_last_reading() {
+        let output = " Endpoint:  Voipfone  Not in use    0 of inf
+                      \x20   OutAuth:  voipfone-auth/12345
+                      \x20 Contact:  Voipfone/sip:sip.voipfone.net   4a5b6c7d8e Avail       31.2
+";
+        let mut cache = DetailCache::default();
+        let detail = cache.update("Voipfone", Ok(output.to_string()));
+        assert_eq!(
+            detail.to_details(),
+            vec![
+                (
+                    "contact".to_string(),
+                    "sip:sip.voipfone.net (Avail, 31.200 ms)".to_string()
+                ),
+                (
+                    "outbound_auth".to_string(),
+                    "voipfone-auth/12345".to_string()
+                ),
+            ]
+        );
+        let failed = || Err(io::Error::other("asterisk is not running"));
+        assert_eq!(cache.update("Voipfone", failed()), detail);
+        assert_eq!(cache.update("500/500", failed()), EndpointDetail::default());
+    }
+
+    #[test]
+    fn test_parse_endpoint_detail_missing_fields() {
+        assert_eq!(
+            parse_endpoint_detail("Unable to find object 999.\n"),
+            EndpointDetail::default()
+        );
+    }
+}
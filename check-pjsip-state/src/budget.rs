@@ -0,0 +1,119 @@
+use crate::clock::Clock;
+use crate::event::AlertEvent;
+use crate::locale::{Locale, Phrase};
+use crate::severity::Severity;
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+// Optional [byte_budget] config capping how much notification text is sent
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ByteBudgetConfig {
+    pub max_bytes: usize,
+    #[serde(default = "default_window_seconds")]
+    pub window_seconds: u64,
+}
+
+fn default_window_seconds() -> u64 {
+    60
+}
+
+// Bytes sent over a sliding window. Once a message would go over budget,
+// only terse summaries are sent until a full window has passed.
+pub struct ByteBudget {
+    clock: Arc<dyn Clock>,
+    max_bytes: usize,
+    window: Duration,
+    sent: VecDeque<(DateTime<Utc>, usize)>,
+    summary_until: Option<DateTime<Utc>>,
+}
+
+impl ByteBudget {
+    pub fn new(config: &ByteBudgetConfig, clock: Arc<dyn Clock>) -> Self {
+        ByteBudget {
+            clock,
+            max_bytes: config.max_bytes,
+            window: Duration::seconds(config.window_seconds as i64),
+            sent: VecDeque::new(),
+            summary_until: None,
+        }
+    }
+
+    // Whether a message of this size may be sent in full, recording it if so
+    pub fn allow(&mut self, bytes: usize) -> bool {
+        let now = self.clock.now();
+        while self
+            .sent
+            .front()
+            .is_some_and(|(at, _)| *at + self.window <= now)
+        {
+            self.sent.pop_front();
+        }
+
+        match self.summary_until {
+            Some(until) if now < until => return false,
+            Some(_) => {
+                info!("Notification byte budget window has passed, sending in full again");
+                self.summary_until = None;
+            }
+            None => {}
+        }
+
+        let used: usize = self.sent.iter().map(|(_, bytes)| bytes).sum();
+        if used + bytes > self.max_bytes {
+            warn!(
+                "Notification byte budget of {} reached, sending summaries for {}s",
+                self.max_bytes,
+                self.window.num_seconds()
+            );
+            self.summary_until = Some(now + self.window);
+            return false;
+        }
+
+        self.sent.push_back((now, bytes));
+        true
+    }
+}
+
+// Stand-ins sent while over budget
+pub fn text_summary(message: &str, locale: Locale) -> String {
+    locale.fill(Phrase::BudgetText, &[&message.len()])
+}
+
+pub fn events_summary(events: &[AlertEvent], locale: Locale) -> String {
+    let warnings = events
+        .iter()
+        .filter(|event| event.severity >= Severity::Warning)
+        .count();
+    locale.fill(Phrase::BudgetEvents, &[&events.len(), &warnings])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_budget_switches_to_summaries_and_resets() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let config = ByteBudgetConfig {
+            max_bytes: 100,
+            window_seconds: 60,
+        };
+        let mut budget = ByteBudget::new(&config, clock.clone());
+
+        assert!(budget.allow(60));
+        assert!(!budget.allow(60));
+
+        // Still summarising for the rest of the window, even if it would fit
+        clock.advance(Duration::seconds(30));
+        assert!(!budget.allow(10));
+
+        clock.advance(Duration::seconds(31));
+        assert!(budget.allow(60));
+        assert!(budget.allow(40));
+        assert!(!budget.allow(1));
+    }
+}
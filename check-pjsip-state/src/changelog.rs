@@ -0,0 +1,314 @@
+use crate::diff::EndpointChange;
+use crate::severity::{Severity, StateClassifier};
+use crate::storage;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Optional [changelog] config for an NDJSON file aimed at log shippers
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangelogConfig {
+    pub path: PathBuf,
+    // Rotate once the file would grow past this size
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+    // Number of rotated files kept, as path.1, path.2, ...
+    #[serde(default = "default_max_files")]
+    pub max_files: u32,
+    // Host name recorded in each line, the machine's own by default
+    pub host: Option<String>,
+}
+
+fn default_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_files() -> u32 {
+    5
+}
+
+// One line of the change log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangeRecord {
+    pub ts: DateTime<Utc>,
+    pub endpoint: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    pub severity: Severity,
+    pub host: String,
+}
+
+impl ChangeRecord {
+    pub fn new(ts: DateTime<Utc>, change: &EndpointChange, severity: Severity, host: &str) -> Self {
+        let (endpoint, old, new) = match change {
+            EndpointChange::Added(endpoint) => (
+                endpoint.endpoint.clone(),
+                None,
+                Some(endpoint.state.to_string()),
+            ),
+            EndpointChange::Removed(endpoint) => (
+                endpoint.endpoint.clone(),
+                Some(endpoint.state.to_string()),
+                None,
+            ),
+            EndpointChange::Changed { old, new } => (
+                new.endpoint.clone(),
+                Some(old.state.to_string()),
+                Some(new.state.to_string()),
+            ),
+        };
+
+        ChangeRecord {
+            ts,
+            endpoint,
+            old,
+            new,
+            severity,
+            host: host.to_string(),
+        }
+    }
+}
+
+// The name of this machine, as the kernel reports it
+pub fn local_hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|_| "localhost".to_string())
+}
+
+// Appends change records as NDJSON, rotating by size. The file being
+// written stays plain for log shippers to follow; with compress, the
+// rotated files are gzipped.
+pub struct Changelog {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: u32,
+    host: String,
+    compress: bool,
+}
+
+impl Changelog {
+    pub fn new(config: &ChangelogConfig) -> Self {
+        Changelog {
+            path: config.path.clone(),
+            max_bytes: config.max_bytes,
+            max_files: config.max_files,
+            host: config.host.clone().unwrap_or_else(local_hostname),
+            compress: false,
+        }
+    }
+
+    pub fn with_compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    pub fn write(
+        &self,
+        ts: DateTime<Utc>,
+        changes: &[EndpointChange],
+        classifier: &StateClassifier,
+    ) -> io::Result<()> {
+        for change in changes {
+            let record = ChangeRecord::new(ts, change, classifier.classify(change), &self.host);
+            let mut line = serde_json::to_string(&record)?;
+            line.push('\n');
+
+            self.rotate_if_needed(line.len() as u64)?;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            file.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Every record still kept, oldest first, skipping lines that can't be
+    // read back. Gzipped files are read whether or not compress is set now.
+    pub fn read_all(&self) -> io::Result<Vec<ChangeRecord>> {
+        let mut paths: Vec<PathBuf> = (1..=self.max_files)
+            .rev()
+            .map(|index| self.rotated_path(index))
+            .collect();
+        paths.push(self.path.clone());
+
+        let mut records = Vec::new();
+        for path in paths {
+            let content = match storage::read_file(&path) {
+                Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            records.extend(
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<ChangeRecord>(line).ok()),
+            );
+        }
+        Ok(records)
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    // Shift path -> path.1 -> path.2 ... when the next line won't fit
+    fn rotate_if_needed(&self, incoming: u64) -> io::Result<()> {
+        let size = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if size == 0 || size + incoming <= self.max_bytes {
+            return Ok(());
+        }
+
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if Path::new(&from).exists() {
+                fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        if self.compress {
+            storage::write_file(&self.rotated_path(1), &fs::read(&self.path)?, true)?;
+            return fs::remove_file(&self.path);
+        }
+        fs::rename(&self.path, self.rotated_path(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn endpoint(name: &str, state: &str) -> Endpoint {
+        Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    fn changelog(name: &str, max_bytes: u64) -> Changelog {
+        let dir =
+            std::env::temp_dir().join(format!("check-pjsip-state-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        Changelog::new(&ChangelogConfig {
+            path: dir.join("changes.ndjson"),
+            max_bytes,
+            max_files: 2,
+            host: Some("pbx1".to_string()),
+        })
+    }
+
+    #[test]
+    fn test_lines_are_independent_json_objects() {
+        let changelog = changelog("changelog", default_max_bytes());
+        let changes = vec![
+            EndpointChange::Changed {
+                old: endpoint("500/500", "Not in use"),
+                new: endpoint("500/500", "Unavailable"),
+            },
+            EndpointChange::Added(endpoint("502/502", "Not in use")),
+        ];
+        changelog
+            .write(Utc::now(), &changes, &StateClassifier::default())
+            .unwrap();
+
+        let content = fs::read_to_string(&changelog.path).unwrap();
+        let records: Vec<ChangeRecord> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].endpoint, "500/500");
+        assert_eq!(records[0].old.as_deref(), Some("Not in use"));
+        assert_eq!(records[0].new.as_deref(), Some("Unavailable"));
+        assert_eq!(records[0].severity, Severity::Warning);
+        assert_eq!(records[0].host, "pbx1");
+        assert_eq!(records[1].old, None);
+    }
+
+    #[test]
+    fn test_rotates_by_size() {
+        let changelog = changelog("changelog-rotate", 200);
+        let changes = vec![EndpointChange::Added(endpoint("502/502", "Not in use"))];
+        let started = Utc::now();
+        for minute in 0..5 {
+            changelog
+                .write(
+                    started + chrono::Duration::minutes(minute),
+                    &changes,
+                    &StateClassifier::default(),
+                )
+                .unwrap();
+        }
+
+        assert!(fs::metadata(&changelog.path).unwrap().len() <= 200);
+        assert!(changelog.rotated_path(1).exists());
+        assert!(changelog.rotated_path(2).exists());
+        assert!(!changelog.rotated_path(3).exists());
+
+        // Read back across the rotated files, oldest first
+        let times: Vec<_> = changelog
+            .read_all()
+            .unwrap()
+            .iter()
+            .map(|record| record.ts)
+            .collect();
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            times.last(),
+            Some(&(started + chrono::Duration::minutes(4)))
+        );
+    }
+
+    #[test]
+    fn test_rotated_files_can_be_compressed() {
+        let changelog = changelog("changelog-compress", 200).with_compress(true);
+        let changes = vec![EndpointChange::Added(endpoint("502/502", "Not in use"))];
+        let started = Utc::now();
+        for minute in 0..4 {
+            changelog
+                .write(
+                    started + chrono::Duration::minutes(minute),
+                    &changes,
+                    &StateClassifier::default(),
+                )
+                .unwrap();
+        }
+
+        let rotated = fs::read(changelog.rotated_path(1)).unwrap();
+        assert_eq!(rotated[..2], [0x1f, 0x8b]);
+        // The live file is still plain NDJSON
+        let live = fs::read_to_string(&changelog.path).unwrap();
+        assert!(serde_json::from_str::<ChangeRecord>(live.lines().next().unwrap()).is_ok());
+
+        let times: Vec<_> = changelog
+            .read_all()
+            .unwrap()
+            .iter()
+            .map(|record| record.ts)
+            .collect();
+        assert_eq!(times.len(), 3);
+        assert_eq!(
+            times.last(),
+            Some(&(started + chrono::Duration::minutes(3)))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::event::AlertEvent;
+use crate::severity::Severity;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// How changes of a given severity are delivered
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryPolicy {
+    Immediate,
+    // Held and sent together once the interval has passed
+    Batched { interval_seconds: u64 },
+}
+
+// A severity's queued events and when its current batch started
+struct Batch {
+    interval: Duration,
+    started: Option<DateTime<Utc>>,
+    events: Vec<AlertEvent>,
+}
+
+// Routes each event to an immediate send or to its severity's batch.
+// Severities without a policy are sent immediately.
+pub struct Coalescer {
+    batches: BTreeMap<Severity, Batch>,
+}
+
+impl Coalescer {
+    pub fn new(policies: &BTreeMap<Severity, DeliveryPolicy>) -> Self {
+        let batches = policies
+            .iter()
+            .filter_map(|(severity, policy)| match policy {
+                DeliveryPolicy::Immediate => None,
+                DeliveryPolicy::Batched { interval_seconds } => Some((
+                    *severity,
+                    Batch {
+                        interval: Duration::seconds(*interval_seconds as i64),
+                        started: None,
+                        events: Vec::new(),
+                    },
+                )),
+            })
+            .collect();
+        Coalescer { batches }
+    }
+
+    // Queue the batched events and return the ones to send now
+    pub fn route(&mut self, events: Vec<AlertEvent>, now: DateTime<Utc>) -> Vec<AlertEvent> {
+        let mut immediate = Vec::new();
+        for event in events {
+            match self.batches.get_mut(&event.severity) {
+                Some(batch) => {
+                    batch.started.get_or_insert(now);
+                    batch.events.push(event);
+                }
+                None => immediate.push(event),
+            }
+        }
+        immediate
+    }
+
+    // Every batch whose interval has passed, most severe first
+    pub fn flush_due(&mut self, now: DateTime<Utc>) -> Vec<AlertEvent> {
+        let mut due = Vec::new();
+        for batch in self.batches.values_mut().rev() {
+            if batch
+                .started
+                .is_some_and(|started| now - started >= batch.interval)
+            {
+                batch.started = None;
+                due.append(&mut batch.events);
+            }
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::EndpointChange;
+    use crate::Endpoint;
+
+    fn event(name: &str, severity: Severity) -> AlertEvent {
+        AlertEvent::new(
+            EndpointChange::Added(Endpoint {
+                endpoint: name.to_string(),
+                state: "Not in use".into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }),
+            severity,
+        )
+    }
+
+    fn names(events: &[AlertEvent]) -> Vec<&str> {
+        events.iter().map(|event| event.change.endpoint()).collect()
+    }
+
+    #[test]
+    fn test_policies_parse_by_severity() {
+        let policies: BTreeMap<Severity, DeliveryPolicy> = toml::from_str(
+            r#"
+            critical = "immediate"
+            info = { batched = { interval_seconds = 300 } }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policies[&Severity::Critical], DeliveryPolicy::Immediate);
+        assert_eq!(
+            policies[&Severity::Info],
+            DeliveryPolicy::Batched {
+                interval_seconds: 300
+            }
+        );
+    }
+
+    #[test]
+    fn test_critical_is_immediate_and_info_is_batched() {
+        let policies = BTreeMap::from([
+            (Severity::Critical, DeliveryPolicy::Immediate),
+            (
+                Severity::Info,
+                DeliveryPolicy::Batched {
+                    interval_seconds: 300,
+                },
+            ),
+        ]);
+        let mut coalescer = Coalescer::new(&policies);
+        let start = Utc::now();
+
+        let sent = coalescer.route(
+            vec![
+                event("500", Severity::Info),
+                event("501", Severity::Critical),
+            ],
+            start,
+        );
+        assert_eq!(names(&sent), vec!["501"]);
+
+        let sent = coalescer.route(
+            vec![
+                event("502", Severity::Info),
+                event("503", Severity::Warning),
+            ],
+            start + Duration::seconds(60),
+        );
+        // Warnings have no policy, so they go straight out
+        assert_eq!(names(&sent), vec!["503"]);
+        assert!(coalescer
+            .flush_due(start + Duration::seconds(299))
+            .is_empty());
+
+        let flushed = coalescer.flush_due(start + Duration::seconds(300));
+        assert_eq!(names(&flushed), vec!["500", "502"]);
+        assert!(coalescer
+            .flush_due(start + Duration::seconds(900))
+            .is_empty());
+    }
+}
@@ -0,0 +1,475 @@
+use crate::ami::AmiClient;
+use crate::asterisk::Asterisk;
+use crate::endpoint_state::EndpointState;
+use crate::parse_log;
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io;
+
+// Where the `pjsip list endpoints` output for each poll comes from: the
+// asterisk CLI, AMI, or anything else that can produce it, such as a
+// stand-in for asterisk in tests
+#[async_trait]
+pub trait Collector: Send + Sync {
+    async fn collect(&self) -> io::Result<String>;
+}
+
+#[async_trait]
+impl Collector for Asterisk {
+    async fn collect(&self) -> io::Result<String> {
+        self.run_command("pjsip list endpoints").await
+    }
+}
+
+#[async_trait]
+impl Collector for AmiClient {
+    async fn collect(&self) -> io::Result<String> {
+        self.list_endpoints().await
+    }
+}
+
+// One `Endpoint:` line of `pjsip list endpoints`, as it's stored, hashed
+// and diffed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    pub endpoint: String,
+    pub state: EndpointState,
+    // The raw column, e.g. "0 of inf", kept for display
+    pub channels: String,
+    // The same column as numbers, with no limit for "inf". State files
+    // written before these existed are filled in when loaded.
+    #[serde(default)]
+    pub active_channels: u32,
+    #[serde(default)]
+    pub channel_limit: Option<u32>,
+    // Any columns after channels, e.g. auth or identify on some versions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<String>,
+}
+
+// One reading of the endpoint list, in the order asterisk gave it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EndpointsData {
+    pub endpoints: Vec<Endpoint>,
+}
+
+// Recognise lines asterisk always prints around the endpoint list: blank lines,
+// the `<Endpoint/CID.....>` style legend, `=====` separators and the footer,
+// along with the "No objects found." an asterisk without endpoints prints
+fn is_noise_line(line: &str) -> bool {
+    let line = line.trim();
+
+    line.is_empty()
+        || line.chars().all(|c| c == '=' || c == '-')
+        || line.starts_with("Objects found:")
+        || line == "No objects found."
+        || line
+            .split_once(':')
+            .is_some_and(|(_, rest)| rest.trim_start().starts_with('<'))
+}
+
+// The channels column, which ends an endpoint line unless the asterisk
+// version adds columns after it
+const CHANNELS_PATTERN: &str = r"\s\d+\s+of\s+(inf|\d+)(\s|$)";
+
+// The active channels and the limit from a channels column such as
+// "2 of 10", with no limit for "inf"
+pub fn parse_channels(channels: &str) -> Option<(u32, Option<u32>)> {
+    let mut words = channels.split_whitespace();
+    let active = words.next()?.parse().ok()?;
+    if words.next()? != "of" {
+        return None;
+    }
+    let limit = match words.next()? {
+        "inf" => None,
+        limit => Some(limit.parse().ok()?),
+    };
+    Some((active, limit))
+}
+
+// The endpoint's id from the name column, without the "/CID" shown after
+// it, e.g. "500" from "500/500", for asking asterisk about it
+pub fn endpoint_id(name: &str) -> &str {
+    name.split('/').next().unwrap_or(name)
+}
+
+// Split an `Endpoint:` line on its channels token; whatever sits between the
+// name and that token is the state, however many words it has, and anything
+// after it is kept as extra columns
+fn parse_endpoint_line(line: &str, channels_re: &Regex) -> Option<Endpoint> {
+    let rest = line.strip_prefix("Endpoint:")?.trim();
+    let channels = channels_re.find(rest)?;
+    let (endpoint, state) = rest[..channels.start()]
+        .trim()
+        .split_once(char::is_whitespace)?;
+
+    let state = state.trim();
+    if state.is_empty() {
+        return None;
+    }
+    let (active_channels, channel_limit) = parse_channels(channels.as_str())?;
+    Some(Endpoint {
+        endpoint: endpoint.to_string(),
+        state: state.into(),
+        channels: channels.as_str().trim().to_string(),
+        active_channels,
+        channel_limit,
+        extra: rest[channels.end()..]
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+// How many lines of output were neither endpoints nor known noise, with the
+// first few of them and how many there were of each pattern
+#[derive(Debug, Default, PartialEq)]
+pub struct UnparsedLines {
+    pub count: usize,
+    pub samples: Vec<String>,
+    pub patterns: BTreeMap<String, usize>,
+}
+
+const UNPARSED_SAMPLES: usize = 3;
+
+// Parse the asterisk output, keeping track of the lines that couldn't be
+pub fn parse_pjsip_endpoints(output: &str) -> (EndpointsData, UnparsedLines) {
+    let mut endpoints = Vec::new();
+    let mut unparsed = UnparsedLines::default();
+
+    let channels_re = Regex::new(CHANNELS_PATTERN).unwrap();
+    let numbers_re = Regex::new(parse_log::NUMBERS_PATTERN).unwrap();
+
+    // Iterate over each line and pick out the endpoints
+    for mut line in output.lines() {
+        line = line.trim();
+        if let Some(endpoint) = parse_endpoint_line(line, &channels_re) {
+            endpoints.push(endpoint);
+        } else if !is_noise_line(line) {
+            unparsed.count += 1;
+            if unparsed.samples.len() < UNPARSED_SAMPLES {
+                unparsed.samples.push(line.to_string());
+            }
+            *unparsed
+                .patterns
+                .entry(parse_log::pattern(line, &numbers_re))
+                .or_default() += 1;
+        }
+    }
+
+    (EndpointsData { endpoints }, unparsed)
+}
+
+// The endpoints alone, for callers with no use for the unparsed lines
+pub fn get_pjsip_endpoints(output: &str) -> EndpointsData {
+    parse_pjsip_endpoints(output).0
+}
+
+// The count from the "Objects found: N" footer, if there is one
+pub fn footer_count(output: &str) -> Option<usize> {
+    output.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("Objects found:")
+            .and_then(|count| count.trim().parse().ok())
+    })
+}
+
+// Whether asterisk listed endpoints but none of them could be parsed. An
+// empty reading whose footer says 0 is a legitimately empty asterisk.
+pub fn is_parse_failure(output: &str, data: &EndpointsData) -> bool {
+    data.endpoints.is_empty() && footer_count(output).is_some_and(|count| count > 0)
+}
+
+// A hash of the reading, to tell cheaply whether anything changed
+pub fn calculate_hash(data: &EndpointsData) -> String {
+    let serialized = serde_json::to_string(data).unwrap();
+    let mut hasher = Sha256::new();
+    hasher.update(serialized);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asterisk;
+
+    #[test]
+    fn test_parse_pjsip_output() {
+        let output = r#"
+            Endpoint:  500/500                                              Unavailable   0 of inf
+            Endpoint:  502/502                                              Not in use    0 of inf
+            Endpoint:  Voipfone                                             Not in use    0 of inf
+        "#;
+
+        let expected_data = EndpointsData {
+            endpoints: vec![
+                Endpoint {
+                    endpoint: "500/500".to_string(),
+                    state: "Unavailable".into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+                Endpoint {
+                    endpoint: "502/502".to_string(),
+                    state: "Not in use".into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+                Endpoint {
+                    endpoint: "Voipfone".to_string(),
+                    state: "Not in use".into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+            ],
+        };
+
+        let parsed_data = get_pjsip_endpoints(output);
+        assert_eq!(parsed_data, expected_data);
+    }
+
+    #[test]
+    fn test_calculate_hash() {
+        let data = EndpointsData {
+            endpoints: vec![
+                Endpoint {
+                    endpoint: "500/500".to_string(),
+                    state: "Unavailable".into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+                Endpoint {
+                    endpoint: "502/502".to_string(),
+                    state: "Not in use".into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+            ],
+        };
+
+        // Calculate the hash for the initial data
+        let initial_hash = calculate_hash(&data);
+
+        // Modify the data and check that the hash changes
+        let modified_data = EndpointsData {
+            endpoints: vec![
+                Endpoint {
+                    endpoint: "500/500".to_string(),
+                    state: "Unavailable".into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+                Endpoint {
+                    endpoint: "502/502".to_string(),
+                    state: "Unavailable".into(), // Changed from "Not in use"
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+            ],
+        };
+
+        let modified_hash = calculate_hash(&modified_data);
+
+        // Ensure that the hash is different after modification
+        assert_ne!(initial_hash, modified_hash);
+    }
+
+    #[test]
+    fn test_trailing_columns_are_kept_as_extra() {
+        let output = "Endpoint:  500/500    Not in use    0 of inf    auth500    identify500";
+
+        let parsed = get_pjsip_endpoints(output);
+        assert_eq!(
+            parsed.endpoints,
+            vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: "Not in use".into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: vec!["auth500".to_string(), "identify500".to_string()],
+            }]
+        );
+        let json = serde_json::to_value(&parsed.endpoints[0]).unwrap();
+        assert_eq!(json["extra"], serde_json::json!(["auth500", "identify500"]));
+    }
+
+    #[test]
+    fn test_parse_multi_word_states() {
+        let output = r#"
+            Endpoint:  500/500                                              In use & busy 1 of inf
+            Endpoint:  501/501                                              Busy    2 of 4
+            Endpoint:  502/502                                              Not in use    0 of inf
+        "#;
+
+        let states: Vec<(String, String, String)> = get_pjsip_endpoints(output)
+            .endpoints
+            .into_iter()
+            .map(|e| (e.endpoint, e.state.to_string(), e.channels))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                (
+                    "500/500".to_string(),
+                    "In use & busy".to_string(),
+                    "1 of inf".to_string()
+                ),
+                (
+                    "501/501".to_string(),
+                    "Busy".to_string(),
+                    "2 of 4".to_string()
+                ),
+                (
+                    "502/502".to_string(),
+                    "Not in use".to_string(),
+                    "0 of inf".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_channel_counts() {
+        let output = r#"
+            Endpoint:  500/500                                              Not in use    0 of inf
+            Endpoint:  501/501                                              In use        3 of inf
+            Endpoint:  502/502                                              Busy          2 of 10
+        "#;
+
+        let counts: Vec<(u32, Option<u32>)> = get_pjsip_endpoints(output)
+            .endpoints
+            .into_iter()
+            .map(|e| (e.active_channels, e.channel_limit))
+            .collect();
+        assert_eq!(counts, vec![(0, None), (3, None), (2, Some(10))]);
+        assert_eq!(parse_channels("2 of many"), None);
+    }
+
+    #[test]
+    fn test_hash_changes_with_active_channels() {
+        let idle = get_pjsip_endpoints("Endpoint:  502/502   Busy   1 of 10");
+        let busier = get_pjsip_endpoints("Endpoint:  502/502   Busy   2 of 10");
+        assert_ne!(calculate_hash(&idle), calculate_hash(&busier));
+    }
+
+    #[test]
+    fn test_line_without_state_is_rejected() {
+        let channels_re = Regex::new(CHANNELS_PATTERN).unwrap();
+        assert_eq!(
+            parse_endpoint_line("Endpoint:  500/500   0 of inf", &channels_re),
+            None
+        );
+    }
+
+    #[test]
+    fn test_noise_lines() {
+        assert!(is_noise_line(""));
+        assert!(is_noise_line("   "));
+        assert!(is_noise_line(
+            " Endpoint:  <Endpoint/CID.....................................>  <State.....>  <Channels.>"
+        ));
+        assert!(is_noise_line(
+            "    I/OAuth:  <AuthId/UserName...........................................................>"
+        ));
+        assert!(is_noise_line(
+            "=========================================================================================="
+        ));
+        assert!(is_noise_line("----------"));
+        assert!(is_noise_line("Objects found: 3"));
+        assert!(is_noise_line("No objects found."));
+    }
+
+    #[test]
+    fn test_unparsed_lines_are_counted_without_noise() {
+        let output = r#"
+ Endpoint:  <Endpoint/CID.....................................>  <State.....>  <Channels.>
+==========================================================================================
+
+ Endpoint:  500/500                                              Not in use    0 of inf
+ Endpoint:  501/501                                              Not in use    0/inf
+ Endpoint:  502/502                                              Not in use    0/inf
+
+Objects found: 3
+"#;
+        let (data, unparsed) = parse_pjsip_endpoints(output);
+        assert_eq!(data.endpoints.len(), 1);
+        assert_eq!(unparsed.count, 2);
+        assert_eq!(
+            unparsed.samples[0],
+            "Endpoint:  501/501                                              Not in use    0/inf"
+        );
+
+        let (_, unparsed) = parse_pjsip_endpoints("Objects found: 0\n");
+        assert_eq!(unparsed, UnparsedLines::default());
+    }
+
+    #[test]
+    fn test_control_sequences_are_stripped_before_parsing() {
+        let output = "Connected to Asterisk 18.10.0 currently running on pbx (pid = 812)\r\n\
+            \x1b]0;Asterisk (pbx)\x07\x1b[0m\r\n\
+            \x1b[1;37m Endpoint:  <Endpoint/CID.....>  <State.....>  <Channels.>\x1b[0m\r\n\
+            \x1b[32m Endpoint:  500/500    Not in use    0 of inf\x1b[0m\r\n\
+            \r\x1b[K Endpoint:  Voipfone   Unavailable   1 of 2\x07\r\n\
+            \r\n\
+            Objects found: 2\r\n\
+            pbx*CLI> \x1b[0m";
+
+        let (_, unparsed) = parse_pjsip_endpoints(output);
+        assert!(unparsed.count > 0);
+
+        let (data, unparsed) = parse_pjsip_endpoints(&asterisk::strip_control_sequences(output));
+        assert_eq!(unparsed, UnparsedLines::default());
+        assert_eq!(data.endpoints.len(), 2);
+        assert_eq!(data.endpoints[0].endpoint, "500/500");
+        assert_eq!(data.endpoints[0].state, "Not in use");
+        assert_eq!(data.endpoints[1].endpoint, "Voipfone");
+        assert_eq!(data.endpoints[1].channels, "1 of 2");
+        assert_eq!(data.endpoints[1].extra, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_legitimately_empty_output() {
+        let output = "\n Endpoint:  <Endpoint/CID.....>  <State.....>  <Channels.>\n\nNo objects found.\n\nObjects found: 0\n";
+        let data = get_pjsip_endpoints(output);
+        assert!(data.endpoints.is_empty());
+        assert_eq!(footer_count(output), Some(0));
+        assert!(!is_parse_failure(output, &data));
+    }
+
+    #[test]
+    fn test_unparsed_endpoints_are_a_parse_failure() {
+        let output = " Endpoint:  500/500 ??? garbled\n\nObjects found: 2\n";
+        let data = get_pjsip_endpoints(output);
+        assert!(data.endpoints.is_empty());
+        assert_eq!(footer_count(output), Some(2));
+        assert!(is_parse_failure(output, &data));
+    }
+
+    #[test]
+    fn test_bad_line_is_not_noise() {
+        assert!(!is_noise_line("Endpoint:  500/500  Unavailable"));
+        assert!(!is_noise_line("No such command 'pjsip list endpoints'"));
+    }
+}
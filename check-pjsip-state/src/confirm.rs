@@ -0,0 +1,87 @@
+use crate::{Endpoint, EndpointsData};
+
+// Work out which parts of a changed reading to believe, given the readings
+// taken to confirm it. A change only stands if every confirmation agrees;
+// anything else keeps its previous value.
+pub fn confirmed(
+    previous: &EndpointsData,
+    reading: &EndpointsData,
+    confirmations: &[EndpointsData],
+) -> EndpointsData {
+    let find = |data: &EndpointsData, name: &str| -> Option<Endpoint> {
+        data.endpoints
+            .iter()
+            .find(|endpoint| endpoint.endpoint == name)
+            .cloned()
+    };
+    let persists = |name: &str, value: Option<&Endpoint>| {
+        confirmations
+            .iter()
+            .all(|confirmation| find(confirmation, name).as_ref() == value)
+    };
+
+    let mut endpoints = Vec::with_capacity(reading.endpoints.len());
+    for endpoint in &reading.endpoints {
+        if persists(&endpoint.endpoint, Some(endpoint)) {
+            endpoints.push(endpoint.clone());
+        } else if let Some(old) = find(previous, &endpoint.endpoint) {
+            endpoints.push(old);
+        }
+    }
+
+    // Endpoints that went missing only count as removed if they stay missing
+    for old in &previous.endpoints {
+        let removed = find(reading, &old.endpoint).is_none();
+        if removed && !persists(&old.endpoint, None) {
+            endpoints.push(old.clone());
+        }
+    }
+
+    EndpointsData { endpoints }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::diff_endpoints;
+
+    fn data(endpoints: &[(&str, &str)]) -> EndpointsData {
+        EndpointsData {
+            endpoints: endpoints
+                .iter()
+                .map(|(name, state)| Endpoint {
+                    endpoint: name.to_string(),
+                    state: (*state).into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_transient_change_is_suppressed() {
+        let previous = data(&[("500/500", "Not in use"), ("Voipfone", "Not in use")]);
+        let glitch = data(&[("500/500", "Unavailable")]);
+
+        let confirmed = confirmed(&previous, &glitch, std::slice::from_ref(&previous));
+        assert!(diff_endpoints(&previous, &confirmed).is_empty());
+    }
+
+    #[test]
+    fn test_persistent_change_is_kept() {
+        let previous = data(&[("500/500", "Not in use"), ("Voipfone", "Not in use")]);
+        let reading = data(&[("500/500", "Unavailable"), ("Voipfone", "Not in use")]);
+        // The first confirmation agrees, the second has Voipfone flickering
+        let confirmations = [
+            reading.clone(),
+            data(&[("500/500", "Unavailable"), ("Voipfone", "Unavailable")]),
+        ];
+
+        let confirmed = confirmed(&previous, &reading, &confirmations);
+        assert_eq!(confirmed, reading);
+        assert_eq!(diff_endpoints(&previous, &confirmed).len(), 1);
+    }
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+// Holds back further notifications for an endpoint for a while after one
+// has been sent about it
+pub struct Cooldown {
+    period: Duration,
+    last_sent: HashMap<String, DateTime<Utc>>,
+}
+
+impl Cooldown {
+    pub fn new(seconds: u64) -> Self {
+        Cooldown {
+            period: Duration::seconds(seconds as i64),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    // How much longer the endpoint is held back for, if at all
+    pub fn remaining(&self, endpoint: &str, now: DateTime<Utc>) -> Option<Duration> {
+        let last_sent = self.last_sent.get(endpoint)?;
+        let remaining = *last_sent + self.period - now;
+        (remaining > Duration::zero()).then_some(remaining)
+    }
+
+    pub fn record(&mut self, endpoint: &str, now: DateTime<Utc>) {
+        self.last_sent.insert(endpoint.to_string(), now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_expires() {
+        let start = Utc::now();
+        let mut cooldown = Cooldown::new(300);
+        assert_eq!(cooldown.remaining("500/500", start), None);
+
+        cooldown.record("500/500", start);
+        assert_eq!(
+            cooldown.remaining("500/500", start + Duration::seconds(60)),
+            Some(Duration::seconds(240))
+        );
+        assert_eq!(cooldown.remaining("502/502", start), None);
+        assert_eq!(
+            cooldown.remaining("500/500", start + Duration::seconds(300)),
+            None
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use crate::diff::EndpointChange;
+use crate::event::AlertEvent;
+use crate::Endpoint;
+use chrono::{DateTime, Duration, Utc};
+
+// An endpoint's changes while held back: how it was before the first of
+// them and the latest event, which says how it is now
+struct Pending {
+    before: Option<Endpoint>,
+    latest: AlertEvent,
+}
+
+impl Pending {
+    // The one change the held events add up to, if they don't cancel out
+    fn merged(self) -> Option<AlertEvent> {
+        let after = match &self.latest.change {
+            EndpointChange::Added(endpoint) | EndpointChange::Changed { new: endpoint, .. } => {
+                Some(endpoint.clone())
+            }
+            EndpointChange::Removed(_) => None,
+        };
+        let change = match (self.before, after) {
+            (Some(old), Some(new)) if old == new => return None,
+            (Some(old), Some(new)) => EndpointChange::Changed { old, new },
+            (None, Some(new)) => EndpointChange::Added(new),
+            (Some(old), None) => EndpointChange::Removed(old),
+            (None, None) => return None,
+        };
+        Some(AlertEvent {
+            change,
+            ..self.latest
+        })
+    }
+}
+
+// Sends no more than one notification per interval. The first change is
+// sent straight away; anything within the interval after it is held, and
+// sent together once it's over, less any endpoint that ended up back where
+// it started.
+pub struct Debouncer {
+    interval: Duration,
+    last_sent: Option<DateTime<Utc>>,
+    // In the order endpoints first changed
+    pending: Vec<(String, Pending)>,
+}
+
+impl Debouncer {
+    pub fn new(seconds: u64) -> Self {
+        Debouncer {
+            interval: Duration::seconds(seconds as i64),
+            last_sent: None,
+            pending: Vec::new(),
+        }
+    }
+
+    fn within_interval(&self, now: DateTime<Utc>) -> bool {
+        self.last_sent
+            .is_some_and(|last_sent| now - last_sent < self.interval)
+    }
+
+    // The events to send now, holding the rest back
+    pub fn route(&mut self, events: Vec<AlertEvent>, now: DateTime<Utc>) -> Vec<AlertEvent> {
+        if events.is_empty() {
+            return events;
+        }
+        if !self.within_interval(now) && self.pending.is_empty() {
+            self.last_sent = Some(now);
+            return events;
+        }
+        for event in events {
+            let name = event.change.endpoint().to_string();
+            match self
+                .pending
+                .iter_mut()
+                .find(|(pending, _)| *pending == name)
+            {
+                Some((_, pending)) => pending.latest = event,
+                None => {
+                    let before = match &event.change {
+                        EndpointChange::Added(_) => None,
+                        EndpointChange::Removed(old) | EndpointChange::Changed { old, .. } => {
+                            Some(old.clone())
+                        }
+                    };
+                    self.pending.push((
+                        name,
+                        Pending {
+                            before,
+                            latest: event,
+                        },
+                    ));
+                }
+            }
+        }
+        Vec::new()
+    }
+
+    // What the held events add up to, once the interval they came in is over
+    pub fn flush_due(&mut self, now: DateTime<Utc>) -> Vec<AlertEvent> {
+        if self.pending.is_empty() || self.within_interval(now) {
+            return Vec::new();
+        }
+        let merged: Vec<AlertEvent> = self
+            .pending
+            .drain(..)
+            .filter_map(|(_, pending)| pending.merged())
+            .collect();
+        if !merged.is_empty() {
+            self.last_sent = Some(now);
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::Severity;
+
+    fn endpoint(state: &str) -> Endpoint {
+        Endpoint {
+            endpoint: "Voipfone".to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    fn changed(old: &str, new: &str) -> AlertEvent {
+        AlertEvent::new(
+            EndpointChange::Changed {
+                old: endpoint(old),
+                new: endpoint(new),
+            },
+            Severity::Warning,
+        )
+    }
+
+    #[test]
+    fn test_first_change_is_sent_promptly() {
+        let start = Utc::now();
+        let mut debouncer = Debouncer::new(60);
+
+        let sent = debouncer.route(vec![changed("Not in use", "Unavailable")], start);
+        assert_eq!(sent.len(), 1);
+        assert!(debouncer.flush_due(start).is_empty());
+    }
+
+    #[test]
+    fn test_changes_within_the_interval_are_merged() {
+        let start = Utc::now();
+        let mut debouncer = Debouncer::new(60);
+        debouncer.route(vec![changed("Not in use", "Unavailable")], start);
+
+        let later = start + Duration::seconds(10);
+        assert!(debouncer
+            .route(vec![changed("Unavailable", "Not in use")], later)
+            .is_empty());
+        assert!(debouncer
+            .route(vec![changed("Not in use", "Invalid")], later)
+            .is_empty());
+        assert!(debouncer.flush_due(later).is_empty());
+
+        let end = start + Duration::seconds(60);
+        assert_eq!(
+            debouncer.flush_due(end)[0].change,
+            EndpointChange::Changed {
+                old: endpoint("Unavailable"),
+                new: endpoint("Invalid"),
+            }
+        );
+        // And the summary starts another interval
+        assert!(debouncer
+            .route(vec![changed("Invalid", "Not in use")], end)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_reverted_change_is_not_sent() {
+        let start = Utc::now();
+        let mut debouncer = Debouncer::new(60);
+        debouncer.route(vec![changed("Not in use", "Unavailable")], start);
+
+        debouncer.route(
+            vec![changed("Unavailable", "Not in use")],
+            start + Duration::seconds(5),
+        );
+        debouncer.route(
+            vec![changed("Not in use", "Unavailable")],
+            start + Duration::seconds(10),
+        );
+        assert!(debouncer
+            .flush_due(start + Duration::seconds(60))
+            .is_empty());
+
+        // Nothing went out, so the next change is sent straight away
+        let next = start + Duration::seconds(70);
+        assert_eq!(
+            debouncer
+                .route(vec![changed("Unavailable", "Not in use")], next)
+                .len(),
+            1
+        );
+    }
+}
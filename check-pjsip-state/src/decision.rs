@@ -0,0 +1,543 @@
+use crate::cooldown::Cooldown;
+use crate::dedup::{AlertTracker, DedupField};
+use crate::diff::EndpointChange;
+use crate::event::AlertEvent;
+use crate::filter::{EndpointFilter, FilterMatch};
+use crate::format::Formatter;
+use crate::ignore::TemporaryIgnores;
+use crate::locale::Locale;
+use crate::maintenance::Maintenance;
+use crate::names::NameCanonicalizer;
+use crate::rules::Rules;
+use crate::schedule::{MaintenanceWindows, WindowAction};
+use crate::severity::{Severity, StateClassifier};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+// Whether a change ends up in a notification, and if not, why not
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Send,
+    Suppressed(String),
+}
+
+// A change together with every step taken in deciding what to do with it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub event: AlertEvent,
+    pub trace: Vec<String>,
+    pub outcome: Outcome,
+}
+
+impl Decision {
+    // A change held back before the alert rules were consulted at all
+    pub fn suppressed(event: AlertEvent, reason: &str) -> Self {
+        Decision {
+            event,
+            trace: Vec::new(),
+            outcome: Outcome::Suppressed(reason.to_string()),
+        }
+    }
+
+    pub fn is_send(&self) -> bool {
+        self.outcome == Outcome::Send
+    }
+
+    // The decision trace printed by --explain
+    pub fn explain(&self) -> String {
+        let mut lines = vec![Formatter::Plain.format_change(&self.event.change, Locale::En)];
+        lines.extend(self.trace.iter().map(|step| format!("  {}", step)));
+        lines.push(match &self.outcome {
+            Outcome::Send => "  outcome: send".to_string(),
+            Outcome::Suppressed(reason) => format!("  outcome: suppressed by {}", reason),
+        });
+        lines.join("\n")
+    }
+}
+
+// The rules deciding which changes are notified
+pub struct AlertPolicy {
+    alerts: AlertTracker,
+    cooldown: Option<Cooldown>,
+    rules: Rules,
+    maintenance: Arc<Maintenance>,
+    ignores: Arc<TemporaryIgnores>,
+    windows: Arc<MaintenanceWindows>,
+    // Only changes into or out of these states are notified, if any are set
+    alert_on: Vec<String>,
+}
+
+impl AlertPolicy {
+    pub fn new(dedup_key_fields: &[DedupField], cooldown_seconds: Option<u64>) -> Self {
+        AlertPolicy {
+            alerts: AlertTracker::new(dedup_key_fields),
+            cooldown: cooldown_seconds.map(Cooldown::new),
+            rules: Rules::new(&[]),
+            maintenance: Arc::default(),
+            ignores: Arc::default(),
+            windows: Arc::default(),
+            alert_on: Vec::new(),
+        }
+    }
+
+    // States worth alerting about, as canonical states or "Removed"
+    pub fn with_alert_on(mut self, states: &[String]) -> Self {
+        self.alert_on = states.to_vec();
+        self
+    }
+
+    pub fn with_maintenance(mut self, maintenance: Arc<Maintenance>) -> Self {
+        self.maintenance = maintenance;
+        self
+    }
+
+    pub fn with_ignores(mut self, ignores: Arc<TemporaryIgnores>) -> Self {
+        self.ignores = ignores;
+        self
+    }
+
+    pub fn with_windows(mut self, windows: Arc<MaintenanceWindows>) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    // Alerts for names with the same key dedupe as one endpoint's
+    pub fn with_names(mut self, names: NameCanonicalizer) -> Self {
+        self.alerts = self.alerts.with_names(names);
+        self
+    }
+
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn decide(
+        &mut self,
+        changes: &[EndpointChange],
+        filter: &EndpointFilter,
+        classifier: &StateClassifier,
+        now: DateTime<Utc>,
+    ) -> Vec<Decision> {
+        changes
+            .iter()
+            .map(|change| self.decide_one(change, filter, classifier, now))
+            .collect()
+    }
+
+    fn decide_one(
+        &mut self,
+        change: &EndpointChange,
+        filter: &EndpointFilter,
+        classifier: &StateClassifier,
+        now: DateTime<Utc>,
+    ) -> Decision {
+        let endpoint = change.endpoint();
+        let mut event = AlertEvent::new(change.clone(), classifier.classify(change));
+        let mut trace = Vec::new();
+        let rule = self.rules.first_match(change);
+        if let Some((_, rule)) = rule {
+            event.severity = rule.severity.unwrap_or(event.severity);
+            event.notifier = rule.notify.clone().filter(|_| !rule.suppresses());
+        }
+
+        // Ignored endpoints are filtered out before diffing, so never get here
+        trace.push(match filter.classify(endpoint) {
+            FilterMatch::Included => "filter: matched include_endpoints".to_string(),
+            FilterMatch::Unfiltered => "filter: monitored, no include_endpoints set".to_string(),
+            FilterMatch::Ignored => "filter: matched ignore_endpoints".to_string(),
+        });
+        if let Some(pattern) = self.maintenance.matching(endpoint) {
+            trace.push(format!("maintenance: matched {}", pattern));
+            return Decision {
+                event,
+                trace,
+                outcome: Outcome::Suppressed("maintenance".to_string()),
+            };
+        }
+        if let Some(until) = self.ignores.until(endpoint, now) {
+            trace.push(format!("ignore: ignored until {}", until.to_rfc3339()));
+            return Decision {
+                event,
+                trace,
+                outcome: Outcome::Suppressed("ignore".to_string()),
+            };
+        }
+        match self.windows.active(endpoint, now) {
+            Some((window, WindowAction::Suppress)) => {
+                trace.push(format!("window: {} is on", window));
+                return Decision {
+                    event,
+                    trace,
+                    outcome: Outcome::Suppressed(format!("window {}", window)),
+                };
+            }
+            Some((window, WindowAction::Downgrade)) => {
+                trace.push(format!("window: {} is on, so sent as info", window));
+                event.severity = Severity::Info;
+            }
+            None => {}
+        }
+        if let Some((position, rule)) = rule {
+            trace.push(format!(
+                "rule: matched rule {} ({})",
+                position, rule.endpoint
+            ));
+            if rule.suppresses() {
+                return Decision {
+                    event,
+                    trace,
+                    outcome: Outcome::Suppressed(format!("rule {}", position)),
+                };
+            }
+        }
+        if !self.alert_on.is_empty() {
+            let (old, new) = match change {
+                EndpointChange::Added(endpoint) => (None, endpoint.state.as_str()),
+                EndpointChange::Removed(endpoint) => (Some(endpoint.state.as_str()), "Removed"),
+                EndpointChange::Changed { old, new } => {
+                    (Some(old.state.as_str()), new.state.as_str())
+                }
+            };
+            let listed = |state: &str| self.alert_on.iter().any(|wanted| wanted == state);
+            match (listed(new), old.filter(|old| listed(old))) {
+                (true, _) => trace.push(format!("alert_on: into {}", new)),
+                (false, Some(old)) => trace.push(format!("alert_on: out of {}", old)),
+                (false, None) => {
+                    trace.push(format!("alert_on: {} not listed", new));
+                    return Decision {
+                        event,
+                        trace,
+                        outcome: Outcome::Suppressed("alert_on".to_string()),
+                    };
+                }
+            }
+        }
+        trace.push(format!("severity: {}", severity_name(event.severity)));
+
+        if let Some(cooldown) = self.cooldown.as_ref() {
+            if let Some(remaining) = cooldown.remaining(endpoint, now) {
+                trace.push(format!(
+                    "cooldown: {}s remaining since the last notification",
+                    remaining.num_seconds()
+                ));
+                trace.push("dedup: not checked".to_string());
+                return Decision {
+                    event,
+                    trace,
+                    outcome: Outcome::Suppressed("cooldown".to_string()),
+                };
+            }
+            trace.push("cooldown: not active".to_string());
+        }
+
+        let key = self.alerts.dedup_key(&event);
+        let admitted = self.alerts.admit(&event);
+        if event.severity < Severity::Warning {
+            trace.push("dedup: not a problem, clears active alerts".to_string());
+        } else if admitted {
+            trace.push(format!("dedup: new alert for key {}", key));
+        } else {
+            trace.push(format!("dedup: already alerted for key {}", key));
+            return Decision {
+                event,
+                trace,
+                outcome: Outcome::Suppressed("dedup".to_string()),
+            };
+        }
+
+        if let Some(cooldown) = self.cooldown.as_mut() {
+            cooldown.record(endpoint, now);
+        }
+        Decision {
+            event,
+            trace,
+            outcome: Outcome::Send,
+        }
+    }
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+    use crate::dedup::default_dedup_key_fields;
+    use crate::filter::FilterConfig;
+    use crate::rules::RuleConfig;
+    use crate::Endpoint;
+
+    fn change(old: &str, new: &str) -> EndpointChange {
+        let endpoint = |state: &str| Endpoint {
+            endpoint: "500/500".to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        };
+        EndpointChange::Changed {
+            old: endpoint(old),
+            new: endpoint(new),
+        }
+    }
+
+    #[test]
+    fn test_matching_rule_routes_and_classifies() {
+        let filter = EndpointFilter::new(&FilterConfig::default());
+        let classifier = StateClassifier::default();
+        let rules = Rules::new(&[RuleConfig {
+            endpoint: "500/*".to_string(),
+            state: Some("Unavailable".to_string()),
+            notify: Some("pagerduty".to_string()),
+            severity: Some(Severity::Critical),
+        }]);
+        let mut policy = AlertPolicy::new(&default_dedup_key_fields(), None).with_rules(rules);
+
+        let decisions = policy.decide(
+            &[
+                change("Not in use", "Unavailable"),
+                change("Unavailable", "Not in use"),
+            ],
+            &filter,
+            &classifier,
+            Utc::now(),
+        );
+        assert!(decisions[0].is_send());
+        assert_eq!(decisions[0].event.severity, Severity::Critical);
+        assert_eq!(decisions[0].event.notifier.as_deref(), Some("pagerduty"));
+        assert!(decisions[0]
+            .explain()
+            .contains("rule: matched rule 1 (500/*)"));
+
+        // The recovery matches no rule, so it's classified and sent as usual
+        assert!(decisions[1].is_send());
+        assert_eq!(decisions[1].event.severity, Severity::Info);
+        assert_eq!(decisions[1].event.notifier, None);
+    }
+
+    #[test]
+    fn test_only_alert_on_states_are_notified() {
+        let filter = EndpointFilter::new(&FilterConfig::default());
+        let classifier = StateClassifier::default();
+        let mut policy = AlertPolicy::new(&default_dedup_key_fields(), None)
+            .with_alert_on(&["Unavailable".to_string()]);
+
+        let decisions = policy.decide(
+            &[change("Not in use", "In use")],
+            &filter,
+            &classifier,
+            Utc::now(),
+        );
+        assert_eq!(
+            decisions[0].outcome,
+            Outcome::Suppressed("alert_on".to_string())
+        );
+        let decisions = policy.decide(
+            &[change("In use", "Unavailable")],
+            &filter,
+            &classifier,
+            Utc::now(),
+        );
+        assert!(decisions[0].is_send());
+        // And the recovery from it
+        let decisions = policy.decide(
+            &[change("Unavailable", "Not in use")],
+            &filter,
+            &classifier,
+            Utc::now(),
+        );
+        assert!(decisions[0].is_send());
+        assert!(decisions[0]
+            .explain()
+            .contains("alert_on: out of Unavailable"));
+    }
+
+    #[test]
+    fn test_maintenance_endpoints_are_suppressed() {
+        let filter = EndpointFilter::new(&FilterConfig::default());
+        let classifier = StateClassifier::default();
+        let maintenance = Arc::new(Maintenance::new(&["500/.*".to_string()]));
+        let mut policy = AlertPolicy::new(&default_dedup_key_fields(), None)
+            .with_maintenance(maintenance.clone());
+        let other = EndpointChange::Added(Endpoint {
+            endpoint: "502/502".to_string(),
+            state: "Unavailable".into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        });
+
+        let decisions = policy.decide(
+            &[change("Not in use", "Unavailable"), other],
+            &filter,
+            &classifier,
+            Utc::now(),
+        );
+        assert_eq!(
+            decisions[0].outcome,
+            Outcome::Suppressed("maintenance".to_string())
+        );
+        assert!(decisions[1].is_send());
+
+        // Taken out of maintenance, it alerts again
+        maintenance.remove("500/.*");
+        let decisions = policy.decide(
+            &[change("Unavailable", "Invalid")],
+            &filter,
+            &classifier,
+            Utc::now(),
+        );
+        assert!(decisions[0].is_send());
+    }
+
+    #[test]
+    fn test_windows_hold_back_or_downgrade_alerts() {
+        use crate::schedule::{WindowConfig, WindowTimezone};
+        use chrono::{NaiveTime, TimeZone};
+
+        let filter = EndpointFilter::new(&FilterConfig::default());
+        let classifier = StateClassifier::default();
+        let window = |name: &str, action| WindowConfig {
+            name: name.to_string(),
+            days: Vec::new(),
+            start: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(4, 0, 0).unwrap(),
+            timezone: WindowTimezone::Utc,
+            action,
+            endpoints: vec!["500/*".to_string()],
+        };
+        let at = |hour| Utc.with_ymd_and_hms(2024, 5, 5, hour, 0, 0).unwrap();
+
+        let windows = MaintenanceWindows::new(&[window("nightly", WindowAction::Suppress)]);
+        let mut policy =
+            AlertPolicy::new(&default_dedup_key_fields(), None).with_windows(Arc::new(windows));
+        let decisions = policy.decide(
+            &[change("Not in use", "Unavailable")],
+            &filter,
+            &classifier,
+            at(3),
+        );
+        assert_eq!(
+            decisions[0].outcome,
+            Outcome::Suppressed("window nightly".to_string())
+        );
+        let decisions = policy.decide(
+            &[change("Unavailable", "Not in use")],
+            &filter,
+            &classifier,
+            at(4),
+        );
+        assert!(decisions[0].is_send());
+
+        let windows = MaintenanceWindows::new(&[window("nightly", WindowAction::Downgrade)]);
+        let mut policy =
+            AlertPolicy::new(&default_dedup_key_fields(), None).with_windows(Arc::new(windows));
+        let decisions = policy.decide(
+            &[change("Not in use", "Unavailable")],
+            &filter,
+            &classifier,
+            at(3),
+        );
+        assert!(decisions[0].is_send());
+        assert_eq!(decisions[0].event.severity, Severity::Info);
+    }
+
+    #[test]
+    fn test_ignored_endpoint_is_monitored_again_after_its_ttl() {
+        let filter = EndpointFilter::new(&FilterConfig::default());
+        let classifier = StateClassifier::default();
+        let clock = ManualClock::new(Utc::now());
+        let ignores = Arc::new(TemporaryIgnores::default());
+        ignores.ignore("500/500", clock.now() + chrono::Duration::hours(2));
+        let mut policy =
+            AlertPolicy::new(&default_dedup_key_fields(), None).with_ignores(ignores.clone());
+
+        let decisions = policy.decide(
+            &[change("Not in use", "Unavailable")],
+            &filter,
+            &classifier,
+            clock.now(),
+        );
+        assert_eq!(
+            decisions[0].outcome,
+            Outcome::Suppressed("ignore".to_string())
+        );
+
+        clock.advance(chrono::Duration::hours(2));
+        let decisions = policy.decide(
+            &[change("Unavailable", "Invalid")],
+            &filter,
+            &classifier,
+            clock.now(),
+        );
+        assert!(decisions[0].is_send());
+        assert!(ignores.active(clock.now()).is_empty());
+    }
+
+    #[test]
+    fn test_explain_cooldown_suppression() {
+        let filter = EndpointFilter::new(&FilterConfig::default());
+        let classifier = StateClassifier::default();
+        let mut policy = AlertPolicy::new(&default_dedup_key_fields(), Some(300));
+        let start = Utc::now();
+
+        let first = policy.decide(
+            &[change("Not in use", "Unavailable")],
+            &filter,
+            &classifier,
+            start,
+        );
+        assert!(first[0].is_send());
+
+        let second = policy.decide(
+            &[change("Unavailable", "Not in use")],
+            &filter,
+            &classifier,
+            start + chrono::Duration::seconds(60),
+        );
+        assert!(!second[0].is_send());
+        assert_eq!(
+            second[0].explain(),
+            "500/500: Unavailable -> Not in use\n  \
+             filter: monitored, no include_endpoints set\n  \
+             severity: info\n  \
+             cooldown: 240s remaining since the last notification\n  \
+             dedup: not checked\n  \
+             outcome: suppressed by cooldown"
+        );
+    }
+
+    #[test]
+    fn test_explain_dedup_suppression() {
+        let filter = EndpointFilter::new(&FilterConfig::default());
+        let classifier = StateClassifier::default();
+        let mut policy = AlertPolicy::new(&default_dedup_key_fields(), None);
+        let now = Utc::now();
+
+        policy.decide(
+            &[change("Not in use", "Unavailable")],
+            &filter,
+            &classifier,
+            now,
+        );
+        let repeat = policy.decide(
+            &[change("Invalid", "Unavailable")],
+            &filter,
+            &classifier,
+            now,
+        );
+        assert_eq!(repeat[0].outcome, Outcome::Suppressed("dedup".to_string()));
+        assert!(repeat[0]
+            .trace
+            .contains(&"dedup: already alerted for key 500/500|Unavailable".to_string()));
+    }
+}
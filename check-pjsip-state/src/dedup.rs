@@ -0,0 +1,156 @@
+use crate::diff::EndpointChange;
+use crate::event::AlertEvent;
+use crate::names::NameCanonicalizer;
+use crate::severity::Severity;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Fields that can make up an alert's dedup key
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupField {
+    Endpoint,
+    State,
+    Channels,
+}
+
+pub fn default_dedup_key_fields() -> Vec<DedupField> {
+    vec![DedupField::Endpoint, DedupField::State]
+}
+
+// Tracks which alerts are currently active so repeats of the same logical
+// problem are only sent once, until the endpoint recovers
+pub struct AlertTracker {
+    fields: Vec<DedupField>,
+    names: NameCanonicalizer,
+    // Active dedup key -> the key of the endpoint it belongs to
+    active: HashMap<String, String>,
+}
+
+impl AlertTracker {
+    pub fn new(fields: &[DedupField]) -> Self {
+        AlertTracker {
+            fields: fields.to_vec(),
+            names: NameCanonicalizer::default(),
+            active: HashMap::new(),
+        }
+    }
+
+    pub fn with_names(mut self, names: NameCanonicalizer) -> Self {
+        self.names = names;
+        self
+    }
+
+    pub fn dedup_key(&self, event: &AlertEvent) -> String {
+        let endpoint = match &event.change {
+            EndpointChange::Added(endpoint) | EndpointChange::Removed(endpoint) => endpoint,
+            EndpointChange::Changed { new, .. } => new,
+        };
+        let state = match &event.change {
+            EndpointChange::Removed(_) => "Removed",
+            _ => endpoint.state.as_str(),
+        };
+
+        let name = self.names.key(&endpoint.endpoint);
+        self.fields
+            .iter()
+            .map(|field| match field {
+                DedupField::Endpoint => name.as_str(),
+                DedupField::State => state,
+                DedupField::Channels => endpoint.channels.as_str(),
+            })
+            .collect::<Vec<&str>>()
+            .join("|")
+    }
+
+    // Whether an event should be sent. Problems are only sent the first time
+    // their key is seen; anything healthy clears the endpoint's active alerts.
+    pub fn admit(&mut self, event: &AlertEvent) -> bool {
+        let endpoint = self.names.key(event.change.endpoint());
+
+        if event.severity < Severity::Warning {
+            self.active
+                .retain(|_, active_endpoint| *active_endpoint != endpoint);
+            return true;
+        }
+
+        self.active
+            .insert(self.dedup_key(event), endpoint)
+            .is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::names::NamesConfig;
+    use crate::Endpoint;
+
+    fn event(state: &str, severity: Severity) -> AlertEvent {
+        named_event("Voipfone", state, severity)
+    }
+
+    fn named_event(name: &str, state: &str, severity: Severity) -> AlertEvent {
+        let endpoint = |state: &str| Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        };
+        AlertEvent::new(
+            EndpointChange::Changed {
+                old: endpoint("Not in use"),
+                new: endpoint(state),
+            },
+            severity,
+        )
+    }
+
+    #[test]
+    fn test_same_key_dedupes() {
+        let mut tracker = AlertTracker::new(&[DedupField::Endpoint]);
+
+        assert!(tracker.admit(&event("Unavailable", Severity::Warning)));
+        // Same endpoint, so with an endpoint-only key this is the same problem
+        assert!(!tracker.admit(&event("Invalid", Severity::Warning)));
+        assert_eq!(
+            tracker.dedup_key(&event("Invalid", Severity::Warning)),
+            "Voipfone"
+        );
+    }
+
+    #[test]
+    fn test_different_keys_do_not_dedupe() {
+        let mut tracker = AlertTracker::new(&default_dedup_key_fields());
+
+        assert!(tracker.admit(&event("Unavailable", Severity::Warning)));
+        assert!(tracker.admit(&event("Invalid", Severity::Warning)));
+        assert!(!tracker.admit(&event("Invalid", Severity::Warning)));
+    }
+
+    #[test]
+    fn test_names_with_the_same_key_dedupe() {
+        let names = NameCanonicalizer::new(&NamesConfig {
+            aliases: HashMap::from([("500/500".to_string(), "500".to_string())]),
+            pattern: None,
+        });
+        let mut tracker = AlertTracker::new(&default_dedup_key_fields()).with_names(names);
+
+        assert!(tracker.admit(&named_event("500", "Unavailable", Severity::Warning)));
+        assert!(!tracker.admit(&named_event("500/500", "Unavailable", Severity::Warning)));
+        // Recovering under either name clears the alert
+        assert!(tracker.admit(&named_event("500/500", "Not in use", Severity::Info)));
+        assert!(tracker.admit(&named_event("500", "Unavailable", Severity::Warning)));
+    }
+
+    #[test]
+    fn test_recovery_clears_active_alerts() {
+        let mut tracker = AlertTracker::new(&[DedupField::Endpoint]);
+
+        assert!(tracker.admit(&event("Unavailable", Severity::Warning)));
+        assert!(tracker.admit(&event("Not in use", Severity::Info)));
+        assert!(tracker.admit(&event("Unavailable", Severity::Warning)));
+    }
+}
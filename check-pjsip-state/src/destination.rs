@@ -0,0 +1,33 @@
+use crate::config::SlackConfig;
+use crate::notify::SlackApiNotifier;
+use serde::Deserialize;
+
+// An [[endpoint_destinations]] entry: a notifier of its own for a set of
+// endpoints, e.g. one customer's channel. Changes on those endpoints go
+// there and nowhere else, and it gets nothing but those changes.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DestinationConfig {
+    // How the destination appears in logs and the outbox
+    pub name: String,
+    // Glob patterns (`*` and `?`) for the endpoints it's for
+    pub endpoints: Vec<String>,
+    pub slack_channel: String,
+    // Defaults to the [slack] token, and is needed when that's a webhook
+    pub api_token: Option<String>,
+    // Log what would be sent to the channel instead of sending it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+pub fn notifier(config: &DestinationConfig, slack: &SlackConfig) -> SlackApiNotifier {
+    SlackApiNotifier::for_destination(
+        &config.name,
+        config
+            .api_token
+            .as_deref()
+            .or(slack.api_token.as_deref())
+            .unwrap_or_default(),
+        &config.slack_channel,
+        slack.format,
+    )
+}
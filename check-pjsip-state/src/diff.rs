@@ -0,0 +1,190 @@
+use crate::{Endpoint, EndpointsData};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// A single difference between two readings of the endpoints
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum EndpointChange {
+    Added(Endpoint),
+    Removed(Endpoint),
+    Changed { old: Endpoint, new: Endpoint },
+}
+
+impl EndpointChange {
+    // Name of the endpoint this change concerns
+    pub fn endpoint(&self) -> &str {
+        match self {
+            EndpointChange::Added(endpoint) | EndpointChange::Removed(endpoint) => {
+                &endpoint.endpoint
+            }
+            EndpointChange::Changed { new, .. } => &new.endpoint,
+        }
+    }
+}
+
+// Compare two readings, matching endpoints by name so that a reordered
+// listing doesn't produce spurious changes
+pub fn diff_endpoints(old: &EndpointsData, new: &EndpointsData) -> Vec<EndpointChange> {
+    diff_endpoints_by(old, new, |a, b| a == b)
+}
+
+// As diff_endpoints, with `same` deciding whether an endpoint has changed
+pub fn diff_endpoints_by(
+    old: &EndpointsData,
+    new: &EndpointsData,
+    same: impl Fn(&Endpoint, &Endpoint) -> bool,
+) -> Vec<EndpointChange> {
+    diff_endpoints_keyed(old, new, str::to_string, same)
+}
+
+// As diff_endpoints_by, matching endpoints by `key` of their names rather
+// than the names themselves
+pub fn diff_endpoints_keyed(
+    old: &EndpointsData,
+    new: &EndpointsData,
+    key: impl Fn(&str) -> String,
+    same: impl Fn(&Endpoint, &Endpoint) -> bool,
+) -> Vec<EndpointChange> {
+    let mut changes = Vec::new();
+
+    let old_by_key: HashMap<String, &Endpoint> = old
+        .endpoints
+        .iter()
+        .map(|endpoint| (key(&endpoint.endpoint), endpoint))
+        .collect();
+    let new_by_key: HashMap<String, &Endpoint> = new
+        .endpoints
+        .iter()
+        .map(|endpoint| (key(&endpoint.endpoint), endpoint))
+        .collect();
+
+    // Walk the new reading in order for additions and transitions
+    for endpoint in &new.endpoints {
+        match old_by_key.get(&key(&endpoint.endpoint)) {
+            None => changes.push(EndpointChange::Added(endpoint.clone())),
+            Some(previous) if !same(previous, endpoint) => changes.push(EndpointChange::Changed {
+                old: (*previous).clone(),
+                new: endpoint.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    // Then the old reading for anything that has gone away
+    for endpoint in &old.endpoints {
+        if !new_by_key.contains_key(&key(&endpoint.endpoint)) {
+            changes.push(EndpointChange::Removed(endpoint.clone()));
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::names::{NameCanonicalizer, NamesConfig};
+
+    fn endpoint(name: &str, state: &str) -> Endpoint {
+        Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        let old = EndpointsData {
+            endpoints: vec![endpoint("500/500", "Not in use")],
+        };
+        let new = EndpointsData {
+            endpoints: vec![endpoint("502/502", "Not in use")],
+        };
+
+        assert_eq!(
+            diff_endpoints(&old, &new),
+            vec![
+                EndpointChange::Added(endpoint("502/502", "Not in use")),
+                EndpointChange::Removed(endpoint("500/500", "Not in use")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_state_change() {
+        let old = EndpointsData {
+            endpoints: vec![
+                endpoint("500/500", "Not in use"),
+                endpoint("502/502", "Not in use"),
+            ],
+        };
+        let new = EndpointsData {
+            endpoints: vec![
+                endpoint("500/500", "Unavailable"),
+                endpoint("502/502", "Not in use"),
+            ],
+        };
+
+        assert_eq!(
+            diff_endpoints(&old, &new),
+            vec![EndpointChange::Changed {
+                old: endpoint("500/500", "Not in use"),
+                new: endpoint("500/500", "Unavailable"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_matches_names_by_key() {
+        let names = NameCanonicalizer::new(&NamesConfig {
+            aliases: HashMap::new(),
+            pattern: Some(r"^(\d+)(/\d+)?$".to_string()),
+        });
+        let key = |name: &str| names.key(name);
+        let same = |a: &Endpoint, b: &Endpoint| a.state == b.state;
+        let old = EndpointsData {
+            endpoints: vec![endpoint("500", "Not in use")],
+        };
+
+        // The same line under its other name is no change at all
+        let renamed = EndpointsData {
+            endpoints: vec![endpoint("500/500", "Not in use")],
+        };
+        assert!(diff_endpoints_keyed(&old, &renamed, key, same).is_empty());
+
+        // And a change of state is one change, shown under the new raw name
+        let changed = EndpointsData {
+            endpoints: vec![endpoint("500/500", "Unavailable")],
+        };
+        assert_eq!(
+            diff_endpoints_keyed(&old, &changed, key, same),
+            vec![EndpointChange::Changed {
+                old: endpoint("500", "Not in use"),
+                new: endpoint("500/500", "Unavailable"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_ignores_reordering() {
+        let old = EndpointsData {
+            endpoints: vec![
+                endpoint("500/500", "Not in use"),
+                endpoint("502/502", "Unavailable"),
+            ],
+        };
+        let new = EndpointsData {
+            endpoints: vec![
+                endpoint("502/502", "Unavailable"),
+                endpoint("500/500", "Not in use"),
+            ],
+        };
+
+        assert!(diff_endpoints(&old, &new).is_empty());
+    }
+}
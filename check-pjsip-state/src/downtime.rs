@@ -0,0 +1,187 @@
+use crate::locale::{Locale, Phrase};
+use crate::severity::StateClassifier;
+use crate::EndpointsData;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// How durations are written in messages
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationFormat {
+    // "1h 23m"
+    #[default]
+    Humanized,
+    // "PT1H23M"
+    Iso8601,
+}
+
+impl DurationFormat {
+    pub fn format(&self, duration: Duration) -> String {
+        let total = duration.num_seconds().max(0);
+        let (days, hours, minutes, seconds) = (
+            total / 86400,
+            total % 86400 / 3600,
+            total % 3600 / 60,
+            total % 60,
+        );
+
+        match self {
+            // The two most significant units are plenty to read at a glance
+            DurationFormat::Humanized => {
+                let units = [(days, "d"), (hours, "h"), (minutes, "m"), (seconds, "s")];
+                let Some(first) = units.iter().position(|(value, _)| *value > 0) else {
+                    return "0s".to_string();
+                };
+                units[first..]
+                    .iter()
+                    .take(2)
+                    .filter(|(value, _)| *value > 0)
+                    .map(|(value, unit)| format!("{}{}", value, unit))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            }
+            DurationFormat::Iso8601 => {
+                let mut iso = "P".to_string();
+                if days > 0 {
+                    iso.push_str(&format!("{}D", days));
+                }
+                if hours > 0 || minutes > 0 || seconds > 0 || days == 0 {
+                    iso.push('T');
+                    if hours > 0 {
+                        iso.push_str(&format!("{}H", hours));
+                    }
+                    if minutes > 0 {
+                        iso.push_str(&format!("{}M", minutes));
+                    }
+                    if seconds > 0 || total == 0 {
+                        iso.push_str(&format!("{}S", seconds));
+                    }
+                }
+                iso
+            }
+        }
+    }
+}
+
+// When each currently unhealthy endpoint was first seen down
+#[derive(Default)]
+pub struct DownSince {
+    since: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl DownSince {
+    pub fn observe(
+        &mut self,
+        data: &EndpointsData,
+        classifier: &StateClassifier,
+        now: DateTime<Utc>,
+    ) {
+        self.since.retain(|name, _| {
+            data.endpoints.iter().any(|endpoint| {
+                endpoint.endpoint == *name && !classifier.is_healthy(endpoint.state.as_str())
+            })
+        });
+        for endpoint in &data.endpoints {
+            if !classifier.is_healthy(endpoint.state.as_str()) {
+                self.since.entry(endpoint.endpoint.clone()).or_insert(now);
+            }
+        }
+    }
+
+    pub fn down_for(&self, endpoint: &str, now: DateTime<Utc>) -> Option<Duration> {
+        self.since.get(endpoint).map(|since| now - *since)
+    }
+
+    // e.g. "Currently down: 500/500 for 1h 23m, Voipfone for 5m"
+    pub fn summary(
+        &self,
+        now: DateTime<Utc>,
+        format: DurationFormat,
+        max_listed: Option<usize>,
+        locale: Locale,
+    ) -> Option<String> {
+        if self.since.is_empty() {
+            return None;
+        }
+        let down: Vec<String> = self
+            .since
+            .iter()
+            .map(|(endpoint, since)| {
+                locale.fill(
+                    Phrase::DownForEntry,
+                    &[endpoint, &format.format(now - *since)],
+                )
+            })
+            .collect();
+        Some(locale.fill(Phrase::CurrentlyDown, &[&locale.list(&down, max_listed)]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    #[test]
+    fn test_duration_formats() {
+        let cases = [
+            (0, "0s", "PT0S"),
+            (45, "45s", "PT45S"),
+            (65, "1m 5s", "PT1M5S"),
+            (3600, "1h", "PT1H"),
+            (5025, "1h 23m", "PT1H23M45S"),
+            (90061, "1d 1h", "P1DT1H1M1S"),
+            (172800, "2d", "P2D"),
+        ];
+        for (seconds, humanized, iso) in cases {
+            let duration = Duration::seconds(seconds);
+            assert_eq!(DurationFormat::Humanized.format(duration), humanized);
+            assert_eq!(DurationFormat::Iso8601.format(duration), iso);
+        }
+    }
+
+    #[test]
+    fn test_down_since_tracks_unhealthy_endpoints() {
+        let data = |state: &str| EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: state.into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        };
+        let classifier = StateClassifier::default();
+        let start = Utc::now();
+        let mut down = DownSince::default();
+
+        down.observe(&data("Unavailable"), &classifier, start);
+        down.observe(
+            &data("Unavailable"),
+            &classifier,
+            start + Duration::seconds(60),
+        );
+        assert_eq!(
+            down.summary(
+                start + Duration::seconds(5025),
+                DurationFormat::Humanized,
+                None,
+                Locale::En
+            ),
+            Some("Currently down: 500/500 for 1h 23m".to_string())
+        );
+
+        down.observe(
+            &data("Not in use"),
+            &classifier,
+            start + Duration::seconds(120),
+        );
+        assert_eq!(down.down_for("500/500", start), None);
+        assert_eq!(
+            down.summary(start, DurationFormat::Humanized, None, Locale::En),
+            None
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use crate::locale::{Locale, Phrase};
+use crate::notify::{Notifier, NotifyError};
+use crate::severity::StateClassifier;
+use crate::EndpointsData;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+// Optional [email] config for a scheduled HTML digest of every endpoint
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct EmailConfig {
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default = "default_subject")]
+    pub subject: String,
+    // Anything that reads a message with its headers on stdin, like sendmail -t
+    #[serde(default = "default_sendmail")]
+    pub sendmail: PathBuf,
+    #[serde(default = "default_digest_interval_seconds")]
+    pub digest_interval_seconds: u64,
+}
+
+fn default_subject() -> String {
+    "PJSIP endpoint digest".to_string()
+}
+
+fn default_sendmail() -> PathBuf {
+    PathBuf::from("/usr/sbin/sendmail")
+}
+
+fn default_digest_interval_seconds() -> u64 {
+    86400
+}
+
+const DIGEST_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<style>
+  body { font-family: sans-serif; }
+  table { border-collapse: collapse; }
+  th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+  th { background: #eee; }
+  tr.unhealthy td { background: #fdd; }
+</style>
+</head>
+<body>
+<h2>{{heading}}</h2>
+{{uptime}}
+<table>
+<tr>{{columns}}</tr>
+{{rows}}
+</table>
+</body>
+</html>
+"#;
+
+// Every endpoint and its state as a styled HTML table
+pub fn html_digest(
+    data: &EndpointsData,
+    classifier: &StateClassifier,
+    generated: DateTime<Utc>,
+    uptime: Option<&str>,
+    locale: Locale,
+) -> String {
+    let columns: String = [
+        Phrase::ColumnEndpoint,
+        Phrase::ColumnState,
+        Phrase::ColumnChannels,
+        Phrase::ColumnHealth,
+    ]
+    .iter()
+    .map(|&column| format!("<th>{}</th>", escape(locale.text(column))))
+    .collect();
+
+    let rows: Vec<String> = data
+        .endpoints
+        .iter()
+        .map(|endpoint| {
+            let (class, health) = if classifier.is_healthy(endpoint.state.as_str()) {
+                ("healthy", Phrase::Healthy)
+            } else {
+                ("unhealthy", Phrase::Unhealthy)
+            };
+            format!(
+                r#"<tr class="{}"><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+                class,
+                escape(&endpoint.endpoint),
+                escape(endpoint.state.as_str()),
+                escape(&endpoint.channels),
+                escape(locale.text(health))
+            )
+        })
+        .collect();
+
+    let heading = locale.fill(
+        Phrase::DigestHeading,
+        &[&generated.format("%Y-%m-%d %H:%M UTC")],
+    );
+    let uptime = uptime
+        .map(|uptime| {
+            let uptime = locale.fill(Phrase::AsteriskUptime, &[&uptime]);
+            format!("<p>{}</p>", escape(&uptime))
+        })
+        .unwrap_or_default();
+    DIGEST_TEMPLATE
+        .replace("{{heading}}", &escape(&heading))
+        .replace("{{uptime}}", &uptime)
+        .replace("{{columns}}", &columns)
+        .replace("{{rows}}", &rows.join("\n"))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// A [[notifiers]] mailbox, sent each notification as plain text
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct MailConfig {
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default = "default_mail_subject")]
+    pub subject: String,
+    #[serde(default = "default_sendmail")]
+    pub sendmail: PathBuf,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_mail_subject() -> String {
+    "PJSIP endpoint changes".to_string()
+}
+
+// Sends mail by handing it to sendmail, which relays it over SMTP
+pub struct EmailNotifier {
+    name: String,
+    from: String,
+    to: Vec<String>,
+    subject: String,
+    sendmail: PathBuf,
+}
+
+impl EmailNotifier {
+    // For the [email] digest
+    pub fn new(config: &EmailConfig) -> Self {
+        EmailNotifier {
+            name: "email".to_string(),
+            from: config.from.clone(),
+            to: config.to.clone(),
+            subject: config.subject.clone(),
+            sendmail: config.sendmail.clone(),
+        }
+    }
+
+    // For a [[notifiers]] mailbox
+    pub fn named(name: &str, config: &MailConfig) -> Self {
+        EmailNotifier {
+            name: name.to_string(),
+            from: config.from.clone(),
+            to: config.to.clone(),
+            subject: config.subject.clone(),
+            sendmail: config.sendmail.clone(),
+        }
+    }
+
+    // The full message, headers included, as sendmail -t expects it
+    fn message(&self, content_type: &str, body: &str) -> String {
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\n\
+             Content-Type: {}; charset=utf-8\r\n\r\n{}",
+            self.from,
+            self.to.join(", "),
+            self.subject,
+            content_type,
+            body
+        )
+    }
+
+    pub async fn send_html(&self, html: &str) -> Result<(), NotifyError> {
+        self.deliver("text/html", html).await
+    }
+
+    async fn deliver(&self, content_type: &str, body: &str) -> Result<(), NotifyError> {
+        let mut child = Command::new(&self.sendmail)
+            .arg("-t")
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| NotifyError::Send(e.to_string()))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(self.message(content_type, body).as_bytes())
+                .await
+                .map_err(|e| NotifyError::Send(e.to_string()))?;
+        }
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| NotifyError::Send(e.to_string()))?;
+        if !status.success() {
+            return Err(NotifyError::Send(format!(
+                "{} exited with {}",
+                self.sendmail.display(),
+                status
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, message: &str) -> Result<(), NotifyError> {
+        self.deliver("text/plain", message).await
+    }
+
+    // Nothing is mailed, but sendmail should at least be there
+    async fn check(&self) -> Option<Result<(), NotifyError>> {
+        Some(if self.sendmail.is_file() {
+            Ok(())
+        } else {
+            Err(NotifyError::Send(format!(
+                "{} not found",
+                self.sendmail.display()
+            )))
+        })
+    }
+}
+
+// When the next digest is due, the first one a full interval after startup
+pub struct DigestSchedule {
+    interval: Duration,
+    next: DateTime<Utc>,
+}
+
+impl DigestSchedule {
+    pub fn new(seconds: u64, now: DateTime<Utc>) -> Self {
+        let interval = Duration::seconds(seconds as i64);
+        DigestSchedule {
+            interval,
+            next: now + interval,
+        }
+    }
+
+    pub fn due(&mut self, now: DateTime<Utc>) -> bool {
+        if now < self.next {
+            return false;
+        }
+        self.next = now + self.interval;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn endpoint(name: &str, state: &str) -> Endpoint {
+        Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_digest_has_an_escaped_row_per_endpoint() {
+        let data = EndpointsData {
+            endpoints: vec![
+                endpoint("500/500", "Not in use"),
+                endpoint("<Front & Back>", "Unavailable"),
+            ],
+        };
+        let html = html_digest(
+            &data,
+            &StateClassifier::default(),
+            Utc::now(),
+            Some("3 days, 4 hours"),
+            Locale::En,
+        );
+
+        assert_eq!(html.matches("<tr class=").count(), 2);
+        assert!(html.contains(
+            r#"<tr class="healthy"><td>500/500</td><td>Not in use</td><td>0 of inf</td><td>healthy</td></tr>"#
+        ));
+        assert!(html.contains(
+            r#"<tr class="unhealthy"><td>&lt;Front &amp; Back&gt;</td><td>Unavailable</td>"#
+        ));
+        assert!(!html.contains("<Front"));
+        assert!(html.contains("<p>Asterisk uptime: 3 days, 4 hours</p>"));
+    }
+
+    #[test]
+    fn test_digest_schedule() {
+        let start = Utc::now();
+        let mut schedule = DigestSchedule::new(3600, start);
+
+        assert!(!schedule.due(start + Duration::seconds(60)));
+        assert!(schedule.due(start + Duration::seconds(3600)));
+        assert!(!schedule.due(start + Duration::seconds(3660)));
+        assert!(schedule.due(start + Duration::seconds(7200)));
+    }
+}
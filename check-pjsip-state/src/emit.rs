@@ -0,0 +1,160 @@
+use crate::diff::EndpointChange;
+use crate::severity::{Severity, StateClassifier};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+// The schema version of each line, for pipelines to match on
+const SCHEMA: u32 = 1;
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "error",
+        Severity::Warning => "warn",
+        Severity::Info => "info",
+    }
+}
+
+// One change as a line of JSON:
+// {"schema":1,"level":"warn","timestamp":"...","event":"endpoint_changed","fields":{...}}
+fn event_line(
+    ts: DateTime<Utc>,
+    change: &EndpointChange,
+    severity: Severity,
+    source: Option<&str>,
+) -> String {
+    let (event, endpoint, old, new) = match change {
+        EndpointChange::Added(endpoint) => (
+            "endpoint_added",
+            &endpoint.endpoint,
+            None,
+            Some(endpoint.state.as_str()),
+        ),
+        EndpointChange::Removed(endpoint) => (
+            "endpoint_removed",
+            &endpoint.endpoint,
+            Some(endpoint.state.as_str()),
+            None,
+        ),
+        EndpointChange::Changed { old, new } => (
+            "endpoint_changed",
+            &new.endpoint,
+            Some(old.state.as_str()),
+            Some(new.state.as_str()),
+        ),
+    };
+    serde_json::json!({
+        "schema": SCHEMA,
+        "level": level(severity),
+        "timestamp": ts.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "event": event,
+        "fields": {
+            "endpoint": endpoint,
+            "old_state": old,
+            "new_state": new,
+            "severity": severity,
+            "source": source,
+        },
+    })
+    .to_string()
+}
+
+// Writes each change as NDJSON for --emit-events-stdout, keeping stdout for
+// events alone while diagnostics go to stderr
+pub struct EventEmitter {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl EventEmitter {
+    pub fn stdout() -> Self {
+        EventEmitter::new(Box::new(io::stdout()))
+    }
+
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        EventEmitter {
+            out: Mutex::new(out),
+        }
+    }
+
+    pub fn emit(
+        &self,
+        ts: DateTime<Utc>,
+        changes: &[EndpointChange],
+        classifier: &StateClassifier,
+        source: Option<&str>,
+    ) -> io::Result<()> {
+        let mut out = self.out.lock().unwrap();
+        for change in changes {
+            let line = event_line(ts, change, classifier.classify(change), source);
+            writeln!(out, "{}", line)?;
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+    use std::sync::Arc;
+
+    // A writer the test can read back from
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_each_change_is_one_json_line() {
+        let endpoint = |state: &str| Endpoint {
+            endpoint: "500/500".to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        };
+        let captured = Captured::default();
+        let emitter = EventEmitter::new(Box::new(captured.clone()));
+        let ts = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        emitter
+            .emit(
+                ts,
+                &[
+                    EndpointChange::Changed {
+                        old: endpoint("Not in use"),
+                        new: endpoint("Unavailable"),
+                    },
+                    EndpointChange::Added(endpoint("Not in use")),
+                ],
+                &StateClassifier::default(),
+                Some("voip1"),
+            )
+            .unwrap();
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "warn");
+        assert_eq!(lines[0]["timestamp"], "2024-05-01T12:00:00.000Z");
+        assert_eq!(lines[0]["event"], "endpoint_changed");
+        assert_eq!(lines[0]["fields"]["old_state"], "Not in use");
+        assert_eq!(lines[0]["fields"]["new_state"], "Unavailable");
+        assert_eq!(lines[0]["fields"]["source"], "voip1");
+        assert_eq!(lines[1]["event"], "endpoint_added");
+        assert_eq!(lines[1]["fields"]["old_state"], serde_json::Value::Null);
+    }
+}
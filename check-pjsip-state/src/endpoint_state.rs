@@ -0,0 +1,114 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// An endpoint's device state. Asterisk's text is kept exactly, so state
+// files, hashes and messages read as before, and anything it reports that
+// isn't listed here, e.g. "Invalid", is kept as it was given.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EndpointState {
+    NotInUse,
+    InUse,
+    Unavailable,
+    Busy,
+    Ringing,
+    OnHold,
+    Unknown(String),
+}
+
+impl EndpointState {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EndpointState::NotInUse => "Not in use",
+            EndpointState::InUse => "In use",
+            EndpointState::Unavailable => "Unavailable",
+            EndpointState::Busy => "Busy",
+            EndpointState::Ringing => "Ringing",
+            EndpointState::OnHold => "On Hold",
+            EndpointState::Unknown(state) => state,
+        }
+    }
+}
+
+impl From<&str> for EndpointState {
+    fn from(state: &str) -> Self {
+        match state {
+            "Not in use" => EndpointState::NotInUse,
+            "In use" => EndpointState::InUse,
+            "Unavailable" => EndpointState::Unavailable,
+            "Busy" => EndpointState::Busy,
+            "Ringing" => EndpointState::Ringing,
+            "On Hold" => EndpointState::OnHold,
+            state => EndpointState::Unknown(state.to_string()),
+        }
+    }
+}
+
+impl From<String> for EndpointState {
+    fn from(state: String) -> Self {
+        EndpointState::from(state.as_str())
+    }
+}
+
+impl fmt::Display for EndpointState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<str> for EndpointState {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for EndpointState {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Serialize for EndpointState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for EndpointState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = String::deserialize(deserializer)?;
+        Ok(EndpointState::from(state.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_states_round_trip_as_asterisk_wrote_them() {
+        for state in [
+            "Not in use",
+            "In use",
+            "Unavailable",
+            "Busy",
+            "Ringing",
+            "On Hold",
+            "Invalid",
+            "In use & busy",
+        ] {
+            let parsed = EndpointState::from(state);
+            assert_eq!(parsed.as_str(), state);
+            let json = serde_json::to_string(&parsed).unwrap();
+            assert_eq!(json, format!("\"{}\"", state));
+            assert_eq!(
+                serde_json::from_str::<EndpointState>(&json).unwrap(),
+                parsed
+            );
+        }
+        assert_eq!(EndpointState::from("On Hold"), EndpointState::OnHold);
+        assert_eq!(
+            EndpointState::from("Invalid"),
+            EndpointState::Unknown("Invalid".to_string())
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::diff::EndpointChange;
+use crate::severity::Severity;
+use crate::Endpoint;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// A change on its way to the notifiers, with everything learned about it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertEvent {
+    pub change: EndpointChange,
+    pub severity: Severity,
+    // Extra context shown alongside the change, e.g. ("context", "from-internal")
+    pub details: Vec<(String, String)>,
+    // Set by a matching rule: send only to the notifier with this name
+    pub notifier: Option<String>,
+    // The [[sources]] entry it came from, tagging each line it's shown on
+    pub source: Option<String>,
+}
+
+// Whether a change made things worse or better
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Degradation,
+    Recovery,
+}
+
+impl AlertEvent {
+    pub fn new(change: EndpointChange, severity: Severity) -> Self {
+        AlertEvent {
+            change,
+            severity,
+            details: Vec::new(),
+            notifier: None,
+            source: None,
+        }
+    }
+
+    // The run-book for this change, from runbook_urls. The endpoint's own
+    // entry wins over one for the state it went into.
+    pub fn runbook_url<'a>(&self, urls: &'a HashMap<String, String>) -> Option<&'a str> {
+        let state = match &self.change {
+            EndpointChange::Removed(_) => "Removed",
+            EndpointChange::Added(endpoint) | EndpointChange::Changed { new: endpoint, .. } => {
+                endpoint.state.as_str()
+            }
+        };
+        urls.get(self.change.endpoint())
+            .or_else(|| urls.get(state))
+            .map(String::as_str)
+    }
+
+    // The endpoint's dashboard, from dashboard_url_template, with the name
+    // encoded so "500/500" stays one path segment or query value
+    pub fn dashboard_url(&self, template: &str) -> String {
+        let endpoint: String =
+            url::form_urlencoded::byte_serialize(self.change.endpoint().as_bytes()).collect();
+        template.replace("{endpoint}", &endpoint)
+    }
+
+    // One line for this change from a message_template, with {endpoint},
+    // {change} ("added", "removed" or "changed"), {old_state}, {new_state},
+    // {channels}, {severity} and {source} filled in. A state there wasn't
+    // is left empty.
+    pub fn render(&self, template: &str) -> String {
+        let (change, old, new) = match &self.change {
+            EndpointChange::Added(endpoint) => ("added", None, Some(endpoint)),
+            EndpointChange::Removed(endpoint) => ("removed", Some(endpoint), None),
+            EndpointChange::Changed { old, new } => ("changed", Some(old), Some(new)),
+        };
+        let state = |endpoint: Option<&Endpoint>| {
+            endpoint.map_or(String::new(), |endpoint| endpoint.state.to_string())
+        };
+        let channels = new
+            .or(old)
+            .map_or("", |endpoint| endpoint.channels.as_str());
+        template
+            .replace("{endpoint}", self.change.endpoint())
+            .replace("{change}", change)
+            .replace("{old_state}", &state(old))
+            .replace("{new_state}", &state(new))
+            .replace("{channels}", channels)
+            .replace("{severity}", self.severity.name())
+            .replace("{source}", self.source.as_deref().unwrap_or_default())
+    }
+
+    pub fn kind(&self) -> ChangeKind {
+        if self.severity >= Severity::Warning {
+            ChangeKind::Degradation
+        } else {
+            ChangeKind::Recovery
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Formatter;
+    use crate::locale::Locale;
+
+    fn event(name: &str, state: &str) -> AlertEvent {
+        let endpoint = |state: &str| Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        };
+        AlertEvent::new(
+            EndpointChange::Changed {
+                old: endpoint("Not in use"),
+                new: endpoint(state),
+            },
+            Severity::Warning,
+        )
+    }
+
+    #[test]
+    fn test_runbook_url_by_endpoint_then_state() {
+        let urls = HashMap::from([
+            (
+                "Unavailable".to_string(),
+                "https://wiki.example.com/unavailable".to_string(),
+            ),
+            (
+                "Voipfone".to_string(),
+                "https://wiki.example.com/voipfone".to_string(),
+            ),
+        ]);
+
+        assert_eq!(
+            event("Voipfone", "Unavailable").runbook_url(&urls),
+            Some("https://wiki.example.com/voipfone")
+        );
+        let mut matched = event("500/500", "Unavailable");
+        let url = matched.runbook_url(&urls).unwrap().to_string();
+        matched.details.push(("runbook".to_string(), url));
+        assert!(Formatter::Plain
+            .format_events(&[matched], Locale::En)
+            .contains("https://wiki.example.com/unavailable"));
+        assert_eq!(event("500/500", "Invalid").runbook_url(&urls), None);
+    }
+
+    #[test]
+    fn test_dashboard_url_has_the_endpoint() {
+        let mut event = event("500/500", "Unavailable");
+        let url =
+            event.dashboard_url("https://grafana.example.com/d/pjsip?var-endpoint={endpoint}");
+        event.details.push(("dashboard".to_string(), url));
+        assert!(Formatter::Plain
+            .format_events(&[event], Locale::En)
+            .contains("https://grafana.example.com/d/pjsip?var-endpoint=500%2F500"));
+    }
+}
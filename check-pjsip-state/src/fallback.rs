@@ -0,0 +1,125 @@
+use crate::notify::{Notifier, NotifyError};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+
+// Optional [fallback_notifier] config for somewhere local to put messages
+// that no primary notifier managed to deliver
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FallbackConfig {
+    // Append each message to a file
+    File {
+        path: PathBuf,
+    },
+    // Log each message to the local syslog daemon
+    Syslog {
+        #[serde(default = "default_syslog_socket")]
+        socket: PathBuf,
+    },
+}
+
+fn default_syslog_socket() -> PathBuf {
+    PathBuf::from("/dev/log")
+}
+
+pub fn notifier(config: &FallbackConfig) -> Box<dyn Notifier> {
+    match config {
+        FallbackConfig::File { path } => Box::new(FileNotifier { path: path.clone() }),
+        FallbackConfig::Syslog { socket } => Box::new(SyslogNotifier {
+            socket: socket.clone(),
+        }),
+    }
+}
+
+// Appends timestamped messages to a file
+pub struct FileNotifier {
+    path: PathBuf,
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn send(&self, message: &str) -> Result<(), NotifyError> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| NotifyError::Send(e.to_string()))?;
+        writeln!(file, "{} {}", chrono::Utc::now().to_rfc3339(), message)
+            .map_err(|e| NotifyError::Send(e.to_string()))
+    }
+
+    async fn check(&self) -> Option<Result<(), NotifyError>> {
+        let opened = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path);
+        Some(
+            opened
+                .map(|_| ())
+                .map_err(|e| NotifyError::Send(e.to_string())),
+        )
+    }
+}
+
+// Sends messages to syslog over its local datagram socket
+pub struct SyslogNotifier {
+    socket: PathBuf,
+}
+
+// user.warning, in syslog's facility * 8 + severity encoding
+const SYSLOG_PRIORITY: u8 = 8 + 4;
+
+#[async_trait]
+impl Notifier for SyslogNotifier {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+
+    async fn send(&self, message: &str) -> Result<(), NotifyError> {
+        let socket = UnixDatagram::unbound().map_err(|e| NotifyError::Send(e.to_string()))?;
+        // Syslog records are single lines, so send one per line of the message
+        for line in message.lines() {
+            let record = format!("<{}>check-pjsip-state: {}", SYSLOG_PRIORITY, line);
+            socket
+                .send_to(record.as_bytes(), &self.socket)
+                .map_err(|e| NotifyError::Send(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn check(&self) -> Option<Result<(), NotifyError>> {
+        let connected = UnixDatagram::unbound().and_then(|socket| socket.connect(&self.socket));
+        Some(connected.map_err(|e| NotifyError::Network(e.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_notifier_appends() {
+        let dir =
+            std::env::temp_dir().join(format!("check-pjsip-state-fallback-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("undelivered.log");
+
+        let notifier = notifier(&FallbackConfig::File { path: path.clone() });
+        notifier.send("first").await.unwrap();
+        notifier.send("second").await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" first"));
+        assert!(lines[1].ends_with(" second"));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::locale::{Locale, Phrase};
+use crate::EndpointsData;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+// Optional [filters] config selecting which endpoints are monitored
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FilterConfig {
+    // Glob patterns (`*` and `?`), or regexes starting "re:", for endpoints
+    // to monitor, all if empty
+    #[serde(default)]
+    pub include_endpoints: Vec<String>,
+    // Patterns for endpoints that are never monitored
+    #[serde(default)]
+    pub ignore_endpoints: Vec<String>,
+    // Alert once when an endpoint matches neither list
+    #[serde(default)]
+    pub notify_on_unfiltered: bool,
+}
+
+// How an endpoint name relates to the configured filters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterMatch {
+    Included,
+    Ignored,
+    Unfiltered,
+}
+
+pub struct EndpointFilter {
+    include: Vec<Regex>,
+    ignore: Vec<Regex>,
+    notify_on_unfiltered: bool,
+    announced: HashSet<String>,
+}
+
+// A filter pattern: a regex when it starts "re:", e.g. "re:^50[0-9]$", and
+// otherwise a glob
+pub fn endpoint_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    match pattern.strip_prefix("re:") {
+        Some(regex) => Regex::new(regex),
+        None => Ok(glob_to_regex(pattern)),
+    }
+}
+
+// Turn a glob pattern into an anchored regex
+pub fn glob_to_regex(pattern: &str) -> Regex {
+    let escaped = regex::escape(pattern)
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+    Regex::new(&format!("^{}$", escaped)).unwrap()
+}
+
+impl EndpointFilter {
+    pub fn new(config: &FilterConfig) -> Self {
+        EndpointFilter {
+            // Patterns are checked when the config is loaded
+            include: config
+                .include_endpoints
+                .iter()
+                .filter_map(|p| endpoint_pattern(p).ok())
+                .collect(),
+            ignore: config
+                .ignore_endpoints
+                .iter()
+                .filter_map(|p| endpoint_pattern(p).ok())
+                .collect(),
+            notify_on_unfiltered: config.notify_on_unfiltered,
+            announced: HashSet::new(),
+        }
+    }
+
+    pub fn classify(&self, endpoint: &str) -> FilterMatch {
+        if self.ignore.iter().any(|re| re.is_match(endpoint)) {
+            FilterMatch::Ignored
+        } else if self.include.iter().any(|re| re.is_match(endpoint)) {
+            FilterMatch::Included
+        } else {
+            FilterMatch::Unfiltered
+        }
+    }
+
+    pub fn is_monitored(&self, endpoint: &str) -> bool {
+        match self.classify(endpoint) {
+            FilterMatch::Included => true,
+            FilterMatch::Ignored => false,
+            FilterMatch::Unfiltered => self.include.is_empty(),
+        }
+    }
+
+    // Keep only the endpoints that are monitored
+    pub fn apply(&self, data: &EndpointsData) -> EndpointsData {
+        EndpointsData {
+            endpoints: data
+                .endpoints
+                .iter()
+                .filter(|endpoint| self.is_monitored(&endpoint.endpoint))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    // Unfiltered endpoints that haven't been announced yet, when enabled
+    pub fn new_unfiltered(&mut self, data: &EndpointsData) -> Vec<String> {
+        if !self.notify_on_unfiltered {
+            return Vec::new();
+        }
+
+        let mut unfiltered = Vec::new();
+        for endpoint in &data.endpoints {
+            if self.classify(&endpoint.endpoint) == FilterMatch::Unfiltered
+                && self.announced.insert(endpoint.endpoint.clone())
+            {
+                unfiltered.push(endpoint.endpoint.clone());
+            }
+        }
+        unfiltered
+    }
+}
+
+pub fn unfiltered_message(
+    endpoints: &[String],
+    max_listed: Option<usize>,
+    locale: Locale,
+) -> String {
+    locale.fill(Phrase::Unfiltered, &[&locale.list(endpoints, max_listed)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn data(names: &[&str]) -> EndpointsData {
+        EndpointsData {
+            endpoints: names
+                .iter()
+                .map(|name| Endpoint {
+                    endpoint: name.to_string(),
+                    state: "Not in use".into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn filter() -> EndpointFilter {
+        EndpointFilter::new(&FilterConfig {
+            include_endpoints: vec!["50?/*".to_string(), "Voipfone".to_string()],
+            ignore_endpoints: vec!["509/*".to_string()],
+            notify_on_unfiltered: true,
+        })
+    }
+
+    #[test]
+    fn test_classify_and_apply() {
+        let filter = filter();
+        assert_eq!(filter.classify("500/500"), FilterMatch::Included);
+        assert_eq!(filter.classify("509/509"), FilterMatch::Ignored);
+        assert_eq!(filter.classify("600/600"), FilterMatch::Unfiltered);
+
+        let filtered = filter.apply(&data(&["500/500", "509/509", "600/600", "Voipfone"]));
+        assert_eq!(filtered, data(&["500/500", "Voipfone"]));
+    }
+
+    #[test]
+    fn test_new_unfiltered_endpoint_alerts_once() {
+        let mut filter = filter();
+
+        assert!(filter.new_unfiltered(&data(&["500/500"])).is_empty());
+        assert_eq!(
+            filter.new_unfiltered(&data(&["500/500", "600/600"])),
+            vec!["600/600".to_string()]
+        );
+        assert!(filter
+            .new_unfiltered(&data(&["500/500", "600/600"]))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_regex_patterns() {
+        let filter = EndpointFilter::new(&FilterConfig {
+            ignore_endpoints: vec!["re:^5[0-9]{2}/".to_string()],
+            ..FilterConfig::default()
+        });
+        assert!(!filter.is_monitored("500/500"));
+        assert!(filter.is_monitored("5000/5000"));
+        assert!(endpoint_pattern("re:(").is_err());
+    }
+
+    #[test]
+    fn test_everything_monitored_without_include_list() {
+        let filter = EndpointFilter::new(&FilterConfig::default());
+        assert!(filter.is_monitored("600/600"));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::diff::EndpointChange;
+use crate::locale::{Locale, Phrase};
+use crate::EndpointsData;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+// Optional [flapping] config. An endpoint changing state this many times
+// within the window is reported once as flapping rather than once per
+// change, until it holds one state for a whole window.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FlappingConfig {
+    #[serde(default = "default_flap_transitions")]
+    pub transitions: usize,
+    #[serde(default = "default_flap_window_seconds")]
+    pub window_seconds: u64,
+}
+
+fn default_flap_transitions() -> usize {
+    4
+}
+
+fn default_flap_window_seconds() -> u64 {
+    600
+}
+
+// An endpoint starting or stopping flapping
+#[derive(Debug, PartialEq)]
+pub enum FlapStep {
+    Started { endpoint: String, changes: usize },
+    Stopped { endpoint: String, state: String },
+}
+
+impl FlapStep {
+    pub fn message(&self, window_seconds: u64, locale: Locale) -> String {
+        match self {
+            FlapStep::Started { endpoint, changes } => locale.fill(
+                Phrase::Flapping,
+                &[endpoint, changes, &format!("{}s", window_seconds)],
+            ),
+            FlapStep::Stopped { endpoint, state } => {
+                locale.fill(Phrase::FlappingOver, &[endpoint, state])
+            }
+        }
+    }
+}
+
+pub struct FlapDetector {
+    transitions: usize,
+    window: Duration,
+    // Endpoint -> when it changed within the window
+    recent: HashMap<String, VecDeque<DateTime<Utc>>>,
+    flapping: BTreeSet<String>,
+}
+
+impl FlapDetector {
+    pub fn new(config: &FlappingConfig) -> Self {
+        FlapDetector {
+            transitions: config.transitions.max(2),
+            window: Duration::seconds(config.window_seconds as i64),
+            recent: HashMap::new(),
+            flapping: BTreeSet::new(),
+        }
+    }
+
+    pub fn window_seconds(&self) -> u64 {
+        self.window.num_seconds() as u64
+    }
+
+    pub fn is_flapping(&self, endpoint: &str) -> bool {
+        self.flapping.contains(endpoint)
+    }
+
+    // Count this poll's changes, returning the endpoints that started
+    // flapping with them and those that have since settled into a state
+    pub fn observe(
+        &mut self,
+        changes: &[EndpointChange],
+        data: &EndpointsData,
+        now: DateTime<Utc>,
+    ) -> Vec<FlapStep> {
+        for change in changes {
+            self.recent
+                .entry(change.endpoint().to_string())
+                .or_default()
+                .push_back(now);
+        }
+        let window = self.window;
+        self.recent.retain(|_, times| {
+            while times.front().is_some_and(|time| now - *time > window) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+
+        let mut steps = Vec::new();
+        let settled: Vec<String> = self
+            .flapping
+            .iter()
+            .filter(|endpoint| !self.recent.contains_key(*endpoint))
+            .cloned()
+            .collect();
+        for endpoint in settled {
+            self.flapping.remove(&endpoint);
+            let state = data
+                .endpoints
+                .iter()
+                .find(|current| current.endpoint == endpoint)
+                .map_or_else(
+                    || "Removed".to_string(),
+                    |current| current.state.to_string(),
+                );
+            steps.push(FlapStep::Stopped { endpoint, state });
+        }
+        for (endpoint, times) in &self.recent {
+            if times.len() >= self.transitions && self.flapping.insert(endpoint.clone()) {
+                steps.push(FlapStep::Started {
+                    endpoint: endpoint.clone(),
+                    changes: times.len(),
+                });
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn endpoint(state: &str) -> Endpoint {
+        Endpoint {
+            endpoint: "500/500".to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_repeated_changes_are_one_flapping_notice() {
+        let mut detector = FlapDetector::new(&FlappingConfig {
+            transitions: 3,
+            window_seconds: 600,
+        });
+        let started = Utc::now();
+        let data = |state: &str| EndpointsData {
+            endpoints: vec![endpoint(state)],
+        };
+        let bounce = |old: &str, new: &str| {
+            vec![EndpointChange::Changed {
+                old: endpoint(old),
+                new: endpoint(new),
+            }]
+        };
+
+        assert!(detector
+            .observe(
+                &bounce("Not in use", "Unavailable"),
+                &data("Unavailable"),
+                started
+            )
+            .is_empty());
+        assert!(detector
+            .observe(
+                &bounce("Unavailable", "Not in use"),
+                &data("Not in use"),
+                started + Duration::minutes(1)
+            )
+            .is_empty());
+        let steps = detector.observe(
+            &bounce("Not in use", "Unavailable"),
+            &data("Unavailable"),
+            started + Duration::minutes(2),
+        );
+        assert_eq!(
+            steps[0].message(600, Locale::En),
+            "500/500 is flapping: 3 state changes in 600s"
+        );
+        assert!(detector.is_flapping("500/500"));
+        // Still changing, so no more notices
+        assert!(detector
+            .observe(
+                &bounce("Unavailable", "Not in use"),
+                &data("Not in use"),
+                started + Duration::minutes(3)
+            )
+            .is_empty());
+
+        // Settled once a whole window passes without a change
+        assert!(detector
+            .observe(&[], &data("Not in use"), started + Duration::minutes(12))
+            .is_empty());
+        assert_eq!(
+            detector.observe(&[], &data("Not in use"), started + Duration::minutes(14)),
+            vec![FlapStep::Stopped {
+                endpoint: "500/500".to_string(),
+                state: "Not in use".into(),
+            }]
+        );
+        assert!(!detector.is_flapping("500/500"));
+    }
+}
@@ -0,0 +1,213 @@
+use crate::diff::EndpointChange;
+use crate::event::AlertEvent;
+use crate::locale::{Locale, Phrase};
+use crate::{source, EndpointsData};
+use serde::Deserialize;
+
+// How a notifier wants change sets rendered
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Formatter {
+    #[default]
+    Plain,
+    SlackMarkdown,
+}
+
+impl Formatter {
+    pub fn format_events(&self, events: &[AlertEvent], locale: Locale) -> String {
+        let mut lines = Vec::with_capacity(events.len() + 1);
+        let heading = locale.text(Phrase::EndpointsChanged);
+        match self {
+            Formatter::Plain => lines.push(heading.to_string()),
+            Formatter::SlackMarkdown => lines.push(format!("*{}*", heading)),
+        }
+
+        for event in events {
+            let mut line = source::tagged(
+                event.source.as_deref(),
+                &self.format_change(&event.change, locale),
+            );
+            if !event.details.is_empty() {
+                line.push_str(&self.format_details(&event.details));
+            }
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+
+    // The full list of endpoints, one per line
+    pub fn format_inventory(&self, data: &EndpointsData, locale: Locale) -> String {
+        let mut lines = Vec::with_capacity(data.endpoints.len() + 1);
+        let heading = locale.fill(Phrase::CurrentEndpoints, &[&data.endpoints.len()]);
+        match self {
+            Formatter::Plain => lines.push(heading),
+            Formatter::SlackMarkdown => lines.push(format!("*{}*", heading)),
+        }
+
+        for endpoint in &data.endpoints {
+            lines.push(match self {
+                Formatter::Plain => format!(
+                    "{}: {} ({})",
+                    endpoint.endpoint, endpoint.state, endpoint.channels
+                ),
+                Formatter::SlackMarkdown => format!(
+                    "• `{}`: {} ({})",
+                    endpoint.endpoint, endpoint.state, endpoint.channels
+                ),
+            });
+        }
+
+        lines.join("\n")
+    }
+
+    fn format_details(&self, details: &[(String, String)]) -> String {
+        let details: Vec<String> = details
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect();
+        match self {
+            Formatter::Plain => format!(" ({})", details.join(", ")),
+            Formatter::SlackMarkdown => format!(" _({})_", details.join(", ")),
+        }
+    }
+
+    pub fn format_change(&self, change: &EndpointChange, locale: Locale) -> String {
+        let added = locale.text(Phrase::Added);
+        let removed = locale.text(Phrase::Removed);
+        let channels = locale.text(Phrase::Channels);
+        match (self, change) {
+            (Formatter::Plain, EndpointChange::Added(endpoint)) => {
+                format!("{}: {} ({})", endpoint.endpoint, added, endpoint.state)
+            }
+            (Formatter::Plain, EndpointChange::Removed(endpoint)) => {
+                format!("{}: {}", endpoint.endpoint, removed)
+            }
+            (Formatter::Plain, EndpointChange::Changed { old, new }) => {
+                if old.state != new.state {
+                    format!("{}: {} -> {}", new.endpoint, old.state, new.state)
+                } else {
+                    format!(
+                        "{}: {} {} -> {}",
+                        new.endpoint, channels, old.channels, new.channels
+                    )
+                }
+            }
+            (Formatter::SlackMarkdown, EndpointChange::Added(endpoint)) => {
+                format!("• `{}` {} ({})", endpoint.endpoint, added, endpoint.state)
+            }
+            (Formatter::SlackMarkdown, EndpointChange::Removed(endpoint)) => {
+                format!("• `{}` {}", endpoint.endpoint, removed)
+            }
+            (Formatter::SlackMarkdown, EndpointChange::Changed { old, new }) => {
+                if old.state != new.state {
+                    format!("• `{}`: {} → *{}*", new.endpoint, old.state, new.state)
+                } else {
+                    format!(
+                        "• `{}`: {} {} → *{}*",
+                        new.endpoint, channels, old.channels, new.channels
+                    )
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::Severity;
+    use crate::Endpoint;
+
+    fn endpoint(name: &str, state: &str) -> Endpoint {
+        Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    fn events() -> Vec<AlertEvent> {
+        vec![
+            EndpointChange::Changed {
+                old: endpoint("500/500", "Not in use"),
+                new: endpoint("500/500", "Unavailable"),
+            },
+            EndpointChange::Added(endpoint("502/502", "Not in use")),
+            EndpointChange::Removed(endpoint("Voipfone", "Not in use")),
+        ]
+        .into_iter()
+        .map(|change| AlertEvent::new(change, Severity::Info))
+        .collect()
+    }
+
+    #[test]
+    fn test_plain_format() {
+        assert_eq!(
+            Formatter::Plain.format_events(&events(), Locale::En),
+            "Endpoints have changed:\n\
+             500/500: Not in use -> Unavailable\n\
+             502/502: added (Not in use)\n\
+             Voipfone: removed"
+        );
+    }
+
+    #[test]
+    fn test_lines_are_tagged_with_their_source() {
+        let mut events = events();
+        events[0].source = Some("voip1".to_string());
+        events[2].source = Some("voip2".to_string());
+        assert_eq!(
+            Formatter::Plain.format_events(&events, Locale::En),
+            "Endpoints have changed:\n\
+             [voip1] 500/500: Not in use -> Unavailable\n\
+             502/502: added (Not in use)\n\
+             [voip2] Voipfone: removed"
+        );
+    }
+
+    #[test]
+    fn test_details_are_appended() {
+        let mut event = events().remove(0);
+        event.details = vec![
+            ("context".to_string(), "from-internal".to_string()),
+            ("callerid".to_string(), "\"Front Desk\" <500>".to_string()),
+        ];
+
+        assert_eq!(
+            Formatter::Plain.format_events(&[event], Locale::En),
+            "Endpoints have changed:\n\
+             500/500: Not in use -> Unavailable (context: from-internal, callerid: \"Front Desk\" <500>)"
+        );
+    }
+
+    #[test]
+    fn test_inventory_format() {
+        let data = EndpointsData {
+            endpoints: vec![
+                endpoint("500/500", "Unavailable"),
+                endpoint("502/502", "Not in use"),
+            ],
+        };
+        assert_eq!(
+            Formatter::Plain.format_inventory(&data, Locale::En),
+            "Current endpoints (2):\n\
+             500/500: Unavailable (0 of inf)\n\
+             502/502: Not in use (0 of inf)"
+        );
+    }
+
+    #[test]
+    fn test_slack_markdown_format() {
+        assert_eq!(
+            Formatter::SlackMarkdown.format_events(&events(), Locale::En),
+            "*Endpoints have changed:*\n\
+             • `500/500`: Not in use → *Unavailable*\n\
+             • `502/502` added (Not in use)\n\
+             • `Voipfone` removed"
+        );
+    }
+}
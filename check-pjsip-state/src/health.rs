@@ -0,0 +1,191 @@
+use crate::collector::endpoint_id;
+use crate::severity::StateClassifier;
+use crate::EndpointsData;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Which signals decide an endpoint's effective state
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthPolicy {
+    // The device state from `pjsip list endpoints`, as always
+    #[default]
+    DeviceOnly,
+    // The worst status of the endpoint's contacts
+    ContactsOnly,
+    // Whichever of the two is unhealthy, preferring the device state
+    WorstOfBoth,
+}
+
+// Parse `pjsip list contacts` into each AOR's contact statuses. Lines look like
+// `Contact:  500/sip:500@192.168.1.10:5060;ob   5b3f8e8e0f Avail   23.456`
+pub fn parse_contacts(output: &str) -> HashMap<String, Vec<String>> {
+    let mut contacts: HashMap<String, Vec<String>> = HashMap::new();
+    for line in output.lines() {
+        let Some(rest) = line.trim().strip_prefix("Contact:") else {
+            continue;
+        };
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        let (Some(uri), Some(status)) = (fields.first(), fields.get(2)) else {
+            continue;
+        };
+        // Skip the `<Aor/ContactUri...>` legend
+        if uri.starts_with('<') {
+            continue;
+        }
+        let aor = uri.split('/').next().unwrap_or(uri);
+        contacts
+            .entry(aor.to_string())
+            .or_default()
+            .push(status.to_string());
+    }
+    contacts
+}
+
+// Contact statuses from best to worst, with anything unrecognised as Unknown
+fn contact_rank(status: &str) -> u8 {
+    match status {
+        "Avail" => 0,
+        "NonQual" => 1,
+        "Unavail" => 3,
+        _ => 2,
+    }
+}
+
+// The state an endpoint's contacts put it in, in terms the unhealthy states
+// already understand. No contacts at all means nothing can reach it.
+fn contact_state(statuses: Option<&Vec<String>>) -> String {
+    let worst = statuses
+        .and_then(|statuses| statuses.iter().max_by_key(|status| contact_rank(status)))
+        .map(String::as_str);
+    match worst {
+        Some(status @ ("Avail" | "NonQual")) => status.to_string(),
+        None | Some("Unavail") => "Unavailable".to_string(),
+        Some(_) => "Unknown".to_string(),
+    }
+}
+
+// Rewrite each endpoint's state to its effective state under the policy
+pub fn apply(
+    policy: HealthPolicy,
+    data: &mut EndpointsData,
+    contacts: &HashMap<String, Vec<String>>,
+    classifier: &StateClassifier,
+) {
+    for endpoint in &mut data.endpoints {
+        // Contacts are listed by AOR, which is the endpoint id
+        let from_contacts = contact_state(contacts.get(endpoint_id(&endpoint.endpoint)));
+        match policy {
+            HealthPolicy::DeviceOnly => {}
+            HealthPolicy::ContactsOnly => endpoint.state = from_contacts.into(),
+            HealthPolicy::WorstOfBoth => {
+                if classifier.is_healthy(endpoint.state.as_str())
+                    && !classifier.is_healthy(&from_contacts)
+                {
+                    endpoint.state = from_contacts.into();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    const CONTACTS: &str = "
+  Contact:  <Aor/ContactUri..............................> <Hash....> <Status> <RTT(ms)..>
+==========================================================================================
+
+  Contact:  500/sip:500@192.168.1.10:5060;ob                 5b3f8e8e0f Unavail       nan
+  Contact:  501/sip:501@192.168.1.11:5060;ob                 8c1d2e3f4a Avail        23.456
+  Contact:  501/sip:501@192.168.1.12:5060;ob                 9d2e3f4a5b NonQual       nan
+
+Objects found: 3
+";
+
+    // 500 says it's fine but its contact is unreachable, 501 is the opposite
+    fn effective_states(policy: HealthPolicy) -> Vec<String> {
+        let endpoint = |name: &str, state: &str| Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        };
+        let mut data = EndpointsData {
+            endpoints: vec![
+                endpoint("500", "Not in use"),
+                endpoint("501", "Unavailable"),
+            ],
+        };
+        apply(
+            policy,
+            &mut data,
+            &parse_contacts(CONTACTS),
+            &StateClassifier::default(),
+        );
+        data.endpoints
+            .into_iter()
+            .map(|e| e.state.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_contacts() {
+        let contacts = parse_contacts(CONTACTS);
+        assert_eq!(contacts.len(), 2);
+        assert_eq!(contacts["501"], vec!["Avail", "NonQual"]);
+    }
+
+    #[test]
+    fn test_device_only() {
+        assert_eq!(
+            effective_states(HealthPolicy::DeviceOnly),
+            vec!["Not in use", "Unavailable"]
+        );
+    }
+
+    #[test]
+    fn test_contacts_only() {
+        assert_eq!(
+            effective_states(HealthPolicy::ContactsOnly),
+            vec!["Unavailable", "NonQual"]
+        );
+    }
+
+    #[test]
+    fn test_contacts_found_for_endpoints_with_a_caller_id() {
+        let mut data = EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "501/501".to_string(),
+                state: "Not in use".into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        };
+        let contacts = parse_contacts(CONTACTS);
+        let classifier = StateClassifier::default();
+        apply(HealthPolicy::WorstOfBoth, &mut data, &contacts, &classifier);
+        assert_eq!(data.endpoints[0].state.as_str(), "Not in use");
+        apply(
+            HealthPolicy::ContactsOnly,
+            &mut data,
+            &contacts,
+            &classifier,
+        );
+        assert_eq!(data.endpoints[0].state.as_str(), "NonQual");
+    }
+
+    #[test]
+    fn test_worst_of_both() {
+        assert_eq!(
+            effective_states(HealthPolicy::WorstOfBoth),
+            vec!["Unavailable", "Unavailable"]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use crate::changelog::ChangeRecord;
+use crate::downtime::DurationFormat;
+use crate::severity::StateClassifier;
+use chrono::{DateTime, Duration, Utc};
+
+// A --since period: "7d", "12h", "30m", "90s" or a bare number of seconds
+pub fn parse_since(since: &str) -> Option<Duration> {
+    let (number, unit) = match since.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => since.split_at(index),
+        None => (since, "s"),
+    };
+    let number: i64 = number.parse().ok()?;
+    let seconds = match unit {
+        "d" => 86400,
+        "h" => 3600,
+        "m" => 60,
+        "s" => 1,
+        _ => return None,
+    };
+    Some(Duration::seconds(number.checked_mul(seconds)?))
+}
+
+// The endpoint's changes in the change log, oldest first, one per line
+pub fn history(
+    records: &[ChangeRecord],
+    endpoint: &str,
+    since: Option<DateTime<Utc>>,
+) -> Vec<String> {
+    records
+        .iter()
+        .filter(|record| record.endpoint == endpoint)
+        .filter(|record| since.is_none_or(|since| record.ts >= since))
+        .map(|record| {
+            format!(
+                "{} {}: {} -> {}",
+                record.ts.to_rfc3339(),
+                record.endpoint,
+                record.old.as_deref().unwrap_or("added"),
+                record.new.as_deref().unwrap_or("removed")
+            )
+        })
+        .collect()
+}
+
+// How an endpoint fared over a period
+#[derive(Debug, PartialEq)]
+pub struct Uptime {
+    pub up: Duration,
+    pub down: Duration,
+    // Times it went down, counting being down as the period began
+    pub outages: usize,
+}
+
+impl Uptime {
+    pub fn percent(&self) -> f64 {
+        let total = (self.up + self.down).num_seconds();
+        if total == 0 {
+            return 100.0;
+        }
+        self.up.num_seconds() as f64 * 100.0 / total as f64
+    }
+
+    // "500/500 was available 98.52% of the last 7d, down for 2h 29m over 3 outages"
+    pub fn message(&self, endpoint: &str, period: &str, format: DurationFormat) -> String {
+        format!(
+            "{} was available {:.2}% of the last {}, down for {} over {} outages",
+            endpoint,
+            self.percent(),
+            period,
+            format.format(self.down),
+            self.outages
+        )
+    }
+}
+
+// Time spent up and down since a point, from the change log. None when the
+// log doesn't show what state the endpoint was in.
+pub fn uptime(
+    records: &[ChangeRecord],
+    endpoint: &str,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    classifier: &StateClassifier,
+) -> Option<Uptime> {
+    let records: Vec<&ChangeRecord> = records
+        .iter()
+        .filter(|record| record.endpoint == endpoint)
+        .collect();
+    let healthy = |state: Option<&str>| state.is_some_and(|state| classifier.is_healthy(state));
+
+    // Where it stood as the period began: what the last change before it
+    // left, or failing that what the first change in it came from
+    let before = records.iter().rev().find(|record| record.ts <= since);
+    let mut is_up = match before {
+        Some(record) => healthy(record.new.as_deref()),
+        None => healthy(
+            records
+                .iter()
+                .find(|record| record.ts > since)?
+                .old
+                .as_deref(),
+        ),
+    };
+
+    let mut uptime = Uptime {
+        up: Duration::zero(),
+        down: Duration::zero(),
+        outages: usize::from(!is_up),
+    };
+    let mut cursor = since;
+    for record in records
+        .iter()
+        .filter(|record| record.ts > since && record.ts <= now)
+    {
+        let spent = record.ts - cursor;
+        if is_up {
+            uptime.up += spent;
+        } else {
+            uptime.down += spent;
+        }
+        let now_up = healthy(record.new.as_deref());
+        if is_up && !now_up {
+            uptime.outages += 1;
+        }
+        is_up = now_up;
+        cursor = record.ts;
+    }
+    let spent = now - cursor;
+    if is_up {
+        uptime.up += spent;
+    } else {
+        uptime.down += spent;
+    }
+    Some(uptime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::Severity;
+
+    fn record(
+        ts: DateTime<Utc>,
+        endpoint: &str,
+        old: Option<&str>,
+        new: Option<&str>,
+    ) -> ChangeRecord {
+        ChangeRecord {
+            ts,
+            endpoint: endpoint.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+            severity: Severity::Info,
+            host: "pbx1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("7d"), Some(Duration::days(7)));
+        assert_eq!(parse_since("12h"), Some(Duration::hours(12)));
+        assert_eq!(parse_since("90"), Some(Duration::seconds(90)));
+        assert_eq!(parse_since("7w"), None);
+        assert_eq!(parse_since("d"), None);
+    }
+
+    #[test]
+    fn test_uptime_and_history_from_the_change_log() {
+        let now = Utc::now();
+        let ago = |hours: i64| now - Duration::hours(hours);
+        let records = vec![
+            record(ago(48), "Voipfone", None, Some("Not in use")),
+            record(ago(20), "Voipfone", Some("Not in use"), Some("Unavailable")),
+            record(ago(18), "Voipfone", Some("Unavailable"), Some("Not in use")),
+            record(ago(10), "500/500", Some("Not in use"), Some("Unavailable")),
+            record(ago(6), "Voipfone", Some("Not in use"), None),
+            record(ago(4), "Voipfone", None, Some("In use")),
+        ];
+        let classifier = StateClassifier::default();
+
+        let day = uptime(&records, "Voipfone", ago(24), now, &classifier).unwrap();
+        assert_eq!(
+            day,
+            Uptime {
+                up: Duration::hours(20),
+                down: Duration::hours(4),
+                outages: 2,
+            }
+        );
+        assert_eq!(
+            day.message("Voipfone", "1d", DurationFormat::Humanized),
+            "Voipfone was available 83.33% of the last 1d, down for 4h over 2 outages"
+        );
+        // Down since before the period, and never seen to come back
+        let down = uptime(&records, "500/500", ago(5), now, &classifier).unwrap();
+        assert_eq!(down.outages, 1);
+        assert_eq!(down.percent(), 0.0);
+        assert_eq!(uptime(&records, "501/501", ago(24), now, &classifier), None);
+
+        let lines = history(&records, "Voipfone", Some(ago(7)));
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" Voipfone: Not in use -> removed"));
+        assert!(lines[1].ends_with(" Voipfone: added -> In use"));
+    }
+}
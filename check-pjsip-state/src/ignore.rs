@@ -0,0 +1,148 @@
+use crate::storage;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Endpoints ignored for a while, from --ignore or the admin socket, each
+// with the time it's monitored again. Kept in the config's ignore_file, if
+// set, so they last across restarts.
+#[derive(Default)]
+pub struct TemporaryIgnores {
+    until: Mutex<BTreeMap<String, DateTime<Utc>>>,
+    file: Option<PathBuf>,
+}
+
+fn load(path: &Path) -> io::Result<BTreeMap<String, DateTime<Utc>>> {
+    match storage::read_file(path) {
+        Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save(path: &Path, until: &BTreeMap<String, DateTime<Utc>>) -> io::Result<()> {
+    storage::write_file(path, &serde_json::to_vec_pretty(until)?, false)
+}
+
+// Ignore an endpoint until a time, straight in the file, for --ignore
+pub fn save_ignore(path: &Path, endpoint: &str, until: DateTime<Utc>) -> io::Result<()> {
+    let mut ignores = load(path)?;
+    ignores.insert(endpoint.to_string(), until);
+    save(path, &ignores)
+}
+
+// Whether the endpoint was ignored, for --unignore
+pub fn save_unignore(path: &Path, endpoint: &str) -> io::Result<bool> {
+    let mut ignores = load(path)?;
+    let removed = ignores.remove(endpoint).is_some();
+    if removed {
+        save(path, &ignores)?;
+    }
+    Ok(removed)
+}
+
+impl TemporaryIgnores {
+    // Kept in path, starting with whatever is already there
+    pub fn with_file(path: &Path) -> Self {
+        let until = load(path).unwrap_or_else(|e| {
+            warn!("Failed to read the ignore file {}: {}", path.display(), e);
+            BTreeMap::new()
+        });
+        TemporaryIgnores {
+            until: Mutex::new(until),
+            file: Some(path.to_path_buf()),
+        }
+    }
+
+    fn persist(&self, until: &BTreeMap<String, DateTime<Utc>>) {
+        if let Some(path) = self.file.as_deref() {
+            if let Err(e) = save(path, until) {
+                warn!("Failed to update the ignore file {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    pub fn ignore(&self, endpoint: &str, until: DateTime<Utc>) {
+        let mut ignores = self.until.lock().unwrap();
+        ignores.insert(endpoint.to_string(), until);
+        self.persist(&ignores);
+    }
+
+    // Whether the endpoint was ignored
+    pub fn remove(&self, endpoint: &str) -> bool {
+        let mut ignores = self.until.lock().unwrap();
+        let removed = ignores.remove(endpoint).is_some();
+        if removed {
+            self.persist(&ignores);
+        }
+        removed
+    }
+
+    // Pick up ignores added or removed from the command line while running
+    pub fn refresh(&self) {
+        let Some(path) = self.file.as_deref() else {
+            return;
+        };
+        match load(path) {
+            Ok(until) => *self.until.lock().unwrap() = until,
+            Err(e) => warn!("Failed to read the ignore file {}: {}", path.display(), e),
+        }
+    }
+
+    // When an endpoint stops being ignored, if it still is at now. Expired
+    // ignores are dropped as they're found.
+    pub fn until(&self, endpoint: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut ignores = self.until.lock().unwrap();
+        let until = *ignores.get(endpoint)?;
+        if until > now {
+            return Some(until);
+        }
+        ignores.remove(endpoint);
+        self.persist(&ignores);
+        info!("{} is no longer ignored", endpoint);
+        None
+    }
+
+    // Every ignore still in force at now
+    pub fn active(&self, now: DateTime<Utc>) -> BTreeMap<String, DateTime<Utc>> {
+        let ignores = self.until.lock().unwrap();
+        ignores
+            .iter()
+            .filter(|(_, until)| **until > now)
+            .map(|(endpoint, until)| (endpoint.clone(), *until))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_ignores_last_across_restarts() {
+        let path =
+            std::env::temp_dir().join(format!("check-pjsip-state-ignores-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let now = Utc::now();
+
+        TemporaryIgnores::with_file(&path).ignore("Voipfone", now + Duration::hours(2));
+        save_ignore(&path, "500/500", now + Duration::hours(1)).unwrap();
+
+        let ignores = TemporaryIgnores::with_file(&path);
+        assert_eq!(
+            ignores.until("Voipfone", now),
+            Some(now + Duration::hours(2))
+        );
+        assert!(save_unignore(&path, "500/500").unwrap());
+        assert!(!save_unignore(&path, "500/500").unwrap());
+        ignores.refresh();
+        assert_eq!(ignores.until("500/500", now), None);
+        assert_eq!(ignores.active(now).len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+use crate::notify::{Notifier, NotifyError};
+use async_trait::async_trait;
+use futures::StreamExt;
+use irc::client::prelude::{Client, Command, Config, Sender};
+use log::{info, warn};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration};
+
+// Optional [irc] config for posting to an IRC channel
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct IrcConfig {
+    pub server: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub use_tls: bool,
+    #[serde(default = "default_nickname")]
+    pub nickname: String,
+    pub channel: String,
+    #[serde(default = "default_reconnect_seconds")]
+    pub reconnect_seconds: u64,
+    // Log what would be sent to the channel instead of sending it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_port() -> u16 {
+    6667
+}
+
+fn default_nickname() -> String {
+    "check-pjsip-state".to_string()
+}
+
+fn default_reconnect_seconds() -> u64 {
+    30
+}
+
+// A server relays a line as ":nick!user@host PRIVMSG #channel :text\r\n" in
+// at most 512 bytes, so leave generous room for the prefix it adds
+const IRC_LINE_BYTES: usize = 512;
+const PREFIX_ALLOWANCE: usize = 100;
+
+// The sender for the current connection, if there is one
+type Connection = Arc<Mutex<Option<Sender>>>;
+
+// Posts one-line summaries to an IRC channel. Clones share the connection.
+#[derive(Clone)]
+pub struct IrcNotifier {
+    server: String,
+    port: u16,
+    channel: String,
+    connection: Connection,
+}
+
+impl IrcNotifier {
+    pub fn new(config: &IrcConfig) -> Self {
+        IrcNotifier {
+            server: config.server.clone(),
+            port: config.port,
+            channel: config.channel.clone(),
+            connection: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn connection(&self) -> Connection {
+        self.connection.clone()
+    }
+}
+
+#[async_trait]
+impl Notifier for IrcNotifier {
+    fn name(&self) -> &str {
+        "irc"
+    }
+
+    async fn send(&self, message: &str) -> Result<(), NotifyError> {
+        let connection = self.connection.lock().unwrap();
+        let Some(sender) = connection.as_ref() else {
+            return Err(NotifyError::Network("not connected to IRC".to_string()));
+        };
+        for line in privmsg_lines(&self.channel, message) {
+            sender
+                .send_privmsg(&self.channel, line)
+                .map_err(|e| NotifyError::Network(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    // Only that the server accepts connections; registering would show the
+    // nickname in the channel
+    async fn check(&self) -> Option<Result<(), NotifyError>> {
+        let connected = tokio::net::TcpStream::connect((self.server.as_str(), self.port)).await;
+        Some(
+            connected
+                .map(|_| ())
+                .map_err(|e| NotifyError::Network(e.to_string())),
+        )
+    }
+}
+
+// Collapse a multi-line message onto one line: the header, then each entry
+// separated by semicolons
+pub fn summary_line(message: &str) -> String {
+    let mut lines = message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let Some(first) = lines.next() else {
+        return String::new();
+    };
+    let rest: Vec<&str> = lines.collect();
+    if rest.is_empty() {
+        first.to_string()
+    } else {
+        format!("{} {}", first, rest.join("; "))
+    }
+}
+
+// The texts to send as PRIVMSGs so each line fits within the IRC limit
+pub fn privmsg_lines(channel: &str, message: &str) -> Vec<String> {
+    let overhead = format!("PRIVMSG {} :\r\n", channel).len() + PREFIX_ALLOWANCE;
+    split_line(&summary_line(message), IRC_LINE_BYTES - overhead)
+}
+
+// Split text into pieces of at most max_bytes, preferring to break at spaces
+fn split_line(text: &str, max_bytes: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = text.trim();
+    while rest.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let split = rest[..end].rfind(' ').filter(|&at| at > 0).unwrap_or(end);
+        pieces.push(rest[..split].trim_end().to_string());
+        rest = rest[split..].trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest.to_string());
+    }
+    pieces
+}
+
+// Keep a connection to the server, reconnecting whenever it drops
+pub async fn run(config: IrcConfig, connection: Connection) {
+    loop {
+        if let Err(e) = connect(&config, &connection).await {
+            warn!("IRC connection to {} failed: {}", config.server, e);
+        }
+        *connection.lock().unwrap() = None;
+        sleep(Duration::from_secs(config.reconnect_seconds)).await;
+    }
+}
+
+async fn connect(config: &IrcConfig, connection: &Connection) -> Result<(), irc::error::Error> {
+    let mut client = Client::from_config(Config {
+        nickname: Some(config.nickname.clone()),
+        server: Some(config.server.clone()),
+        port: Some(config.port),
+        use_tls: Some(config.use_tls),
+        channels: vec![config.channel.clone()],
+        ..Config::default()
+    })
+    .await?;
+    client.identify()?;
+
+    let mut stream = client.stream()?;
+    while let Some(message) = stream.next().await.transpose()? {
+        // Messages can only be posted once the channel has been joined
+        if let Command::JOIN(channel, _, _) = &message.command {
+            if channel.eq_ignore_ascii_case(&config.channel)
+                && message.source_nickname() == Some(client.current_nickname())
+            {
+                info!("Joined {} on {}", config.channel, config.server);
+                *connection.lock().unwrap() = Some(client.sender());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_line() {
+        assert_eq!(
+            summary_line(
+                "Endpoints have changed:\n500/500: Not in use -> Unavailable\n502/502: removed"
+            ),
+            "Endpoints have changed: 500/500: Not in use -> Unavailable; 502/502: removed"
+        );
+        assert_eq!(summary_line("check-pjsip-started"), "check-pjsip-started");
+    }
+
+    #[test]
+    fn test_long_summary_is_split() {
+        let mut message = "Endpoints have changed:".to_string();
+        for i in 0..60 {
+            message.push_str(&format!(
+                "\n{}/{}: Not in use -> Unavailable",
+                500 + i,
+                500 + i
+            ));
+        }
+
+        let lines = privmsg_lines("#noc", &message);
+        let max = IRC_LINE_BYTES - "PRIVMSG #noc :\r\n".len() - PREFIX_ALLOWANCE;
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= max));
+        assert!(lines[0].starts_with("Endpoints have changed: 500/500"));
+        // Nothing is lost in the split
+        assert_eq!(lines.join(" "), summary_line(&message));
+    }
+
+    #[test]
+    fn test_split_without_spaces() {
+        assert_eq!(split_line("abcdefgh", 3), vec!["abc", "def", "gh"]);
+    }
+}
@@ -0,0 +1,79 @@
+// Watches the PJSIP endpoints of an asterisk server and reports when their
+// states change.
+//
+// The binary is a thin layer over this crate, which can be used on its
+// own: collector reads and parses `pjsip list endpoints`, diff finds what
+// changed between two readings, state keeps the last reading across
+// restarts and notify sends AlertEvents wherever they're wanted.
+
+pub mod admin;
+pub mod all_clear;
+pub mod ami;
+pub mod api;
+pub mod asterisk;
+pub mod budget;
+pub mod changelog;
+pub mod clock;
+pub mod coalesce;
+pub mod collector;
+pub mod config;
+pub mod confirm;
+pub mod cooldown;
+pub mod debounce;
+pub mod decision;
+pub mod dedup;
+pub mod destination;
+pub mod diff;
+pub mod downtime;
+pub mod email;
+pub mod emit;
+pub mod endpoint_state;
+pub mod event;
+pub mod fallback;
+pub mod filter;
+pub mod flap;
+pub mod format;
+pub mod health;
+pub mod history;
+pub mod ignore;
+pub mod irc_notifier;
+pub mod locale;
+pub mod logging;
+pub mod maintenance;
+pub mod metrics;
+pub mod monitor;
+pub mod names;
+pub mod normalize;
+pub mod notifiers;
+pub mod notify;
+pub mod outage;
+pub mod outbox;
+pub mod parse_log;
+pub mod peer;
+pub mod plugin;
+pub mod preflight;
+pub mod ratelimit;
+pub mod registrations;
+pub mod reload;
+pub mod replay;
+pub mod report;
+pub mod rules;
+pub mod schedule;
+pub mod severity;
+pub mod signals;
+pub mod slo;
+pub mod source;
+pub mod state;
+pub mod storage;
+pub mod sustain;
+pub mod systemd;
+pub mod telemetry;
+pub mod transitions;
+pub mod utilization;
+pub mod watchdog;
+pub mod webhook;
+
+pub use collector::{
+    calculate_hash, endpoint_id, footer_count, get_pjsip_endpoints, is_parse_failure,
+    parse_channels, parse_pjsip_endpoints, Collector, Endpoint, EndpointsData, UnparsedLines,
+};
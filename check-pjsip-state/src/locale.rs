@@ -0,0 +1,294 @@
+use serde::Deserialize;
+use std::fmt::Display;
+
+// Language for the fixed parts of messages. Endpoint names and the states
+// asterisk reports are always passed through as they are.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+// The built-in message text, each a template whose `{}`s are filled in order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phrase {
+    EndpointsChanged,
+    CurrentEndpoints,
+    Added,
+    Removed,
+    Channels,
+    DownFor,
+    CurrentlyDown,
+    DownForEntry,
+    AllClear,
+    Reloaded,
+    CommandFailed,
+    CommandRecovered,
+    Unfiltered,
+    StillDown,
+    TransitionBudget,
+    TransitionCounts,
+    NoTransitions,
+    BudgetText,
+    BudgetEvents,
+    SloBreached,
+    SloRecovered,
+    AndMore,
+    DigestHeading,
+    ColumnEndpoint,
+    ColumnState,
+    ColumnChannels,
+    ColumnHealth,
+    Healthy,
+    Unhealthy,
+    ParseFailed,
+    UnparsedLines,
+    Up,
+    AsteriskUptime,
+    OutageStarted,
+    OutageOver,
+    Flapping,
+    FlappingOver,
+    Runbook,
+    Dashboard,
+    CountChanged,
+    PeerDiverged,
+    PeerAgreed,
+    ChannelsHigh,
+    ChannelsHighCount,
+    ChannelsNormal,
+    ChannelsNormalCount,
+    RegistrationChanged,
+    WindowOver,
+    RateLimited,
+}
+
+impl Locale {
+    pub fn text(self, phrase: Phrase) -> &'static str {
+        match self {
+            Locale::En => match phrase {
+                Phrase::EndpointsChanged => "Endpoints have changed:",
+                Phrase::CurrentEndpoints => "Current endpoints ({}):",
+                Phrase::Added => "added",
+                Phrase::Removed => "removed",
+                Phrase::Channels => "channels",
+                Phrase::DownFor => "down for",
+                Phrase::CurrentlyDown => "Currently down: {}",
+                Phrase::DownForEntry => "{} for {}",
+                Phrase::AllClear => "All clear: all {} endpoints healthy",
+                Phrase::Reloaded => "Configuration reloaded, {} endpoints",
+                Phrase::CommandFailed => "Failed to run the command",
+                Phrase::CommandRecovered => "The command is working again after {} failures",
+                Phrase::Unfiltered => "Endpoints not covered by any filter: {}",
+                Phrase::StillDown => "Still down since before restart: {}",
+                Phrase::TransitionBudget => {
+                    "{} has changed state {} times this period, exceeding the budget of {}"
+                }
+                Phrase::TransitionCounts => "Transition counts: {}",
+                Phrase::NoTransitions => "Transition counts: no endpoint changed state",
+                Phrase::BudgetText => "Notification budget reached, withheld a {} byte message",
+                Phrase::BudgetEvents => {
+                    "Endpoints have changed: {} changes ({} warnings), details withheld as the notification budget is reached"
+                }
+                Phrase::SloBreached => {
+                    "{} is below its SLO: {} available over {} (target {})"
+                }
+                Phrase::SloRecovered => {
+                    "{} is back within its SLO: {} available over {} (target {})"
+                }
+                Phrase::AndMore => "…and {} more",
+                Phrase::DigestHeading => "Endpoint states at {}",
+                Phrase::ColumnEndpoint => "Endpoint",
+                Phrase::ColumnState => "State",
+                Phrase::ColumnChannels => "Channels",
+                Phrase::ColumnHealth => "Health",
+                Phrase::Healthy => "healthy",
+                Phrase::Unhealthy => "unhealthy",
+                Phrase::ParseFailed => "Asterisk reported {} endpoints but none could be parsed",
+                Phrase::UnparsedLines => {
+                    "{} lines of asterisk output could not be parsed, so its format may have changed: {}"
+                }
+                Phrase::Up => "up {}",
+                Phrase::AsteriskUptime => "Asterisk uptime: {}",
+                Phrase::OutageStarted => "Systemic outage: {} of {} endpoints are {}",
+                Phrase::OutageOver => "Systemic outage over: {} of {} endpoints are {}",
+                Phrase::Flapping => "{} is flapping: {} state changes in {}",
+                Phrase::FlappingOver => "{} has stopped flapping and is {}",
+                Phrase::Runbook => "runbook",
+                Phrase::Dashboard => "dashboard",
+                Phrase::CountChanged => "Endpoint count {} -> {}",
+                Phrase::PeerDiverged => {
+                    "This monitor and its peer at {} see different endpoints, so one of them may be misreading asterisk"
+                }
+                Phrase::PeerAgreed => "This monitor and its peer at {} agree again",
+                Phrase::ChannelsHigh => "{}: {} of {} channels in use ({}%)",
+                Phrase::ChannelsNormal => "{}: channels in use back under {}%",
+                Phrase::ChannelsHighCount => "{}: {} channels in use",
+                Phrase::ChannelsNormalCount => "{}: channels in use back under {}",
+                Phrase::RegistrationChanged => "{} registration: {} -> {}",
+                Phrase::WindowOver => {
+                    "Maintenance window {} is over, {} changes were held back during it:"
+                }
+                Phrase::RateLimited => {
+                    "{} further changes were suppressed while {} was at its limit of {} messages per {}, see /state for how things stand"
+                }
+            },
+            Locale::Fr => match phrase {
+                Phrase::EndpointsChanged => "Les endpoints ont changé :",
+                Phrase::CurrentEndpoints => "Endpoints actuels ({}) :",
+                Phrase::Added => "ajouté",
+                Phrase::Removed => "supprimé",
+                Phrase::Channels => "canaux",
+                Phrase::DownFor => "hors service depuis",
+                Phrase::CurrentlyDown => "Actuellement hors service : {}",
+                Phrase::DownForEntry => "{} depuis {}",
+                Phrase::AllClear => "Tout va bien : les {} endpoints sont opérationnels",
+                Phrase::Reloaded => "Configuration rechargée, {} endpoints",
+                Phrase::CommandFailed => "Échec de l'exécution de la commande",
+                Phrase::CommandRecovered => "La commande fonctionne de nouveau après {} échecs",
+                Phrase::Unfiltered => "Endpoints couverts par aucun filtre : {}",
+                Phrase::StillDown => "Toujours hors service depuis avant le redémarrage : {}",
+                Phrase::TransitionBudget => {
+                    "{} a changé d'état {} fois sur cette période, au-delà du budget de {}"
+                }
+                Phrase::TransitionCounts => "Nombre de transitions : {}",
+                Phrase::NoTransitions => "Nombre de transitions : aucun endpoint n'a changé d'état",
+                Phrase::BudgetText => {
+                    "Budget de notifications atteint, message de {} octets retenu"
+                }
+                Phrase::BudgetEvents => {
+                    "Les endpoints ont changé : {} changements ({} alertes), détails retenus car le budget de notifications est atteint"
+                }
+                Phrase::SloBreached => {
+                    "{} est sous son SLO : {} de disponibilité sur {} (objectif {})"
+                }
+                Phrase::SloRecovered => {
+                    "{} respecte à nouveau son SLO : {} de disponibilité sur {} (objectif {})"
+                }
+                Phrase::AndMore => "…et {} de plus",
+                Phrase::DigestHeading => "État des endpoints au {}",
+                Phrase::ColumnEndpoint => "Endpoint",
+                Phrase::ColumnState => "État",
+                Phrase::ColumnChannels => "Canaux",
+                Phrase::ColumnHealth => "Santé",
+                Phrase::Healthy => "opérationnel",
+                Phrase::Unhealthy => "hors service",
+                Phrase::ParseFailed => {
+                    "Asterisk a signalé {} endpoints mais aucun n'a pu être analysé"
+                }
+                Phrase::UnparsedLines => {
+                    "{} lignes de la sortie d'asterisk n'ont pas pu être analysées, son format a peut-être changé : {}"
+                }
+                Phrase::Up => "démarré depuis {}",
+                Phrase::AsteriskUptime => "Asterisk démarré depuis : {}",
+                Phrase::OutageStarted => "Panne générale : {} endpoints sur {} sont {}",
+                Phrase::OutageOver => "Fin de la panne générale : {} endpoints sur {} sont {}",
+                Phrase::Flapping => "{} oscille : {} changements d'état en {}",
+                Phrase::FlappingOver => "{} n'oscille plus et est {}",
+                Phrase::Runbook => "procédure",
+                Phrase::Dashboard => "tableau de bord",
+                Phrase::CountChanged => "Nombre d'endpoints {} -> {}",
+                Phrase::PeerDiverged => {
+                    "Ce moniteur et son pair à {} voient des endpoints différents, l'un d'eux lit peut-être mal asterisk"
+                }
+                Phrase::PeerAgreed => "Ce moniteur et son pair à {} sont de nouveau d'accord",
+                Phrase::ChannelsHigh => "{} : {} canaux utilisés sur {} ({} %)",
+                Phrase::ChannelsNormal => "{} : canaux utilisés de nouveau sous {} %",
+                Phrase::ChannelsHighCount => "{} : {} canaux utilisés",
+                Phrase::ChannelsNormalCount => "{} : canaux utilisés de nouveau sous {}",
+                Phrase::RegistrationChanged => "Enregistrement de {} : {} -> {}",
+                Phrase::WindowOver => {
+                    "La fenêtre de maintenance {} est terminée, {} changements y ont été retenus :"
+                }
+                Phrase::RateLimited => {
+                    "{} autres changements ont été supprimés pendant que {} était à sa limite de {} messages par {}, voir /state pour l'état actuel"
+                }
+            },
+        }
+    }
+
+    // The phrase with its placeholders filled from `args`, in order
+    pub fn fill(self, phrase: Phrase, args: &[&dyn Display]) -> String {
+        let mut parts = self.text(phrase).split("{}");
+        let mut filled = parts.next().unwrap_or_default().to_string();
+        for (i, part) in parts.enumerate() {
+            if let Some(arg) = args.get(i) {
+                filled.push_str(&arg.to_string());
+            }
+            filled.push_str(part);
+        }
+        filled
+    }
+
+    // A comma-separated list, cut off after `max` items with "…and N more"
+    pub fn list(self, items: &[String], max: Option<usize>) -> String {
+        match max {
+            Some(max) if items.len() > max => format!(
+                "{} {}",
+                items[..max].join(", "),
+                self.fill(Phrase::AndMore, &[&(items.len() - max)])
+            ),
+            _ => items.join(", "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::EndpointChange;
+    use crate::event::AlertEvent;
+    use crate::format::Formatter;
+    use crate::severity::Severity;
+    use crate::Endpoint;
+
+    #[test]
+    fn test_fill_placeholders_in_order() {
+        assert_eq!(
+            Locale::En.fill(Phrase::TransitionBudget, &[&"500/500", &7, &5]),
+            "500/500 has changed state 7 times this period, exceeding the budget of 5"
+        );
+    }
+
+    #[test]
+    fn test_long_lists_are_truncated() {
+        let endpoints: Vec<String> = (500..515).map(|n| format!("{}/{}", n, n)).collect();
+        assert_eq!(
+            Locale::En.list(&endpoints, Some(3)),
+            "500/500, 501/501, 502/502 …and 12 more"
+        );
+        assert_eq!(
+            Locale::En.list(&endpoints[..3], Some(3)),
+            "500/500, 501/501, 502/502"
+        );
+        assert_eq!(Locale::En.list(&endpoints, None).matches(", ").count(), 14);
+    }
+
+    #[test]
+    fn test_locale_changes_fixed_phrases() {
+        let endpoint = Endpoint {
+            endpoint: "500/500".to_string(),
+            state: "Not in use".into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        };
+        let events = vec![
+            AlertEvent::new(EndpointChange::Added(endpoint.clone()), Severity::Info),
+            AlertEvent::new(EndpointChange::Removed(endpoint), Severity::Warning),
+        ];
+
+        assert_eq!(
+            Formatter::Plain.format_events(&events, Locale::En),
+            "Endpoints have changed:\n500/500: added (Not in use)\n500/500: removed"
+        );
+        // The state stays as asterisk reported it
+        assert_eq!(
+            Formatter::Plain.format_events(&events, Locale::Fr),
+            "Les endpoints ont changé :\n500/500: ajouté (Not in use)\n500/500: supprimé"
+        );
+    }
+}
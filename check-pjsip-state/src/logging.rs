@@ -0,0 +1,215 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::Level;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::future::Future;
+use std::io::Write;
+
+// How log lines are written: "text" for people, "json" for Loki or ELK
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+pub fn default_log_level() -> String {
+    "info".to_string()
+}
+
+// What a log line was written during, so a failed send can be matched
+// with the poll and the reading behind it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogContext {
+    pub source: Option<String>,
+    pub poll: Option<u64>,
+    // The start of the reading's hash, once the poll has one
+    pub snapshot: Option<String>,
+    pub notifier: Option<String>,
+}
+
+impl LogContext {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = Vec::new();
+        if let Some(source) = self.source.as_ref() {
+            fields.push(("source", source.clone()));
+        }
+        if let Some(poll) = self.poll {
+            fields.push(("poll", poll.to_string()));
+        }
+        if let Some(snapshot) = self.snapshot.as_ref() {
+            fields.push(("snapshot", snapshot.clone()));
+        }
+        if let Some(notifier) = self.notifier.as_ref() {
+            fields.push(("notifier", notifier.clone()));
+        }
+        fields
+    }
+}
+
+// Kept in a task-local rather than tracing spans: without a subscriber to
+// put span fields on the lines env_logger writes, spans would be dropped
+tokio::task_local! {
+    static CONTEXT: RefCell<LogContext>;
+}
+
+fn current() -> LogContext {
+    CONTEXT
+        .try_with(|context| context.borrow().clone())
+        .unwrap_or_default()
+}
+
+// Tag everything logged while f runs with the poll
+pub async fn in_poll<F: Future>(source: Option<String>, poll: u64, f: F) -> F::Output {
+    let context = LogContext {
+        source,
+        poll: Some(poll),
+        ..LogContext::default()
+    };
+    CONTEXT.scope(RefCell::new(context), f).await
+}
+
+// Tag the rest of the poll's log lines with the reading it got
+pub fn set_snapshot(hash: &str) {
+    let _ = CONTEXT.try_with(|context| {
+        context.borrow_mut().snapshot = Some(hash.chars().take(12).collect());
+    });
+}
+
+// Tag everything logged while f runs with the notifier, and the poll if
+// it's sending for one
+pub async fn for_notifier<F: Future>(name: &str, f: F) -> F::Output {
+    let context = LogContext {
+        notifier: Some(name.to_string()),
+        ..current()
+    };
+    CONTEXT.scope(RefCell::new(context), f).await
+}
+
+// One log record as a line of text, as env_logger writes them, with the
+// context after the message, e.g. "... Failed to send source=pbx1 poll=3"
+fn text_line(
+    timestamp: DateTime<Utc>,
+    level: Level,
+    target: &str,
+    message: &str,
+    context: &LogContext,
+) -> String {
+    let mut line = format!(
+        "[{} {:<5} {}] {}",
+        timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+        level,
+        target,
+        message
+    );
+    for (name, value) in context.fields() {
+        line.push_str(&format!(" {}={}", name, value));
+    }
+    line
+}
+
+// One log record as a line of JSON
+fn json_line(
+    timestamp: DateTime<Utc>,
+    level: Level,
+    target: &str,
+    message: &str,
+    context: &LogContext,
+) -> String {
+    let mut line = serde_json::json!({
+        "timestamp": timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "level": level.as_str(),
+        "target": target,
+        "message": message,
+    });
+    for (name, value) in context.fields() {
+        line[name] = match name {
+            "poll" => serde_json::json!(context.poll),
+            _ => serde_json::Value::String(value),
+        };
+    }
+    line.to_string()
+}
+
+// Start logging to stderr at the configured level or filter, e.g. "debug"
+// or "check_pjsip_state=debug". RUST_LOG, when set, takes precedence.
+pub fn init(level: &str, format: LogFormat) {
+    let mut builder = env_logger::Builder::new();
+    builder
+        .target(env_logger::Target::Stderr)
+        .parse_filters(level)
+        .parse_default_env();
+    builder.format(move |buf, record| {
+        let line = match format {
+            LogFormat::Text => text_line,
+            LogFormat::Json => json_line,
+        };
+        let line = line(
+            Utc::now(),
+            record.level(),
+            record.target(),
+            &record.args().to_string(),
+            &current(),
+        );
+        writeln!(buf, "{}", line)
+    });
+    builder.init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_json_line() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let line = json_line(
+            timestamp,
+            Level::Warn,
+            "check_pjsip_state::monitor",
+            "Failed to parse \"line\"",
+            &LogContext::default(),
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["timestamp"], "2024-05-01T12:00:00.000Z");
+        assert_eq!(parsed["level"], "WARN");
+        assert_eq!(parsed["target"], "check_pjsip_state::monitor");
+        assert_eq!(parsed["message"], "Failed to parse \"line\"");
+        assert!(parsed.get("poll").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lines_carry_the_poll_and_notifier() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let context = in_poll(Some("pbx1".to_string()), 3, async {
+            set_snapshot("0123456789abcdef");
+            for_notifier("slack", async { current() }).await
+        })
+        .await;
+        assert_eq!(
+            text_line(
+                timestamp,
+                Level::Warn,
+                "check_pjsip_state::notify",
+                "Failed to send",
+                &context
+            ),
+            "[2024-05-01T12:00:00Z WARN  check_pjsip_state::notify] Failed to send source=pbx1 poll=3 snapshot=0123456789ab notifier=slack"
+        );
+        let line = json_line(
+            timestamp,
+            Level::Warn,
+            "check_pjsip_state::notify",
+            "Failed to send",
+            &context,
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["poll"], 3);
+        assert_eq!(parsed["notifier"], "slack");
+
+        // Outside a poll, lines are as they were
+        assert_eq!(current(), LogContext::default());
+    }
+}
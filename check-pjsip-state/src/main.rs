@@ -1,177 +1,687 @@
-use regex::Regex;
-use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::fs;
-use std::process::Command;
-use tokio::time::{sleep, Duration};
-use slack_morphism::prelude::*;
-
-// Struct for deserializing TOML config
-#[derive(Deserialize, Debug, PartialEq)]
-struct Config {
-    slack: SlackConfig,
-    sleep_time_seconds: u64,
-}
-
-#[derive(Deserialize, Debug, PartialEq)]
-struct SlackConfig {
-    api_token: String,
+use check_pjsip_state::admin::Admin;
+use check_pjsip_state::ami::AmiClient;
+use check_pjsip_state::api::ChangeHistory;
+use check_pjsip_state::budget::ByteBudget;
+use check_pjsip_state::changelog::Changelog;
+use check_pjsip_state::clock::SystemClock;
+use check_pjsip_state::config::Config;
+use check_pjsip_state::emit::EventEmitter;
+use check_pjsip_state::health::HealthPolicy;
+use check_pjsip_state::ignore::TemporaryIgnores;
+use check_pjsip_state::irc_notifier::IrcNotifier;
+use check_pjsip_state::locale::Phrase;
+use check_pjsip_state::maintenance::Maintenance;
+use check_pjsip_state::metrics::Metrics;
+use check_pjsip_state::monitor::{Monitor, PollSource};
+use check_pjsip_state::notify::{ConsoleNotifier, Dispatcher, Notifier, SlackApiNotifier};
+use check_pjsip_state::outbox::Outbox;
+use check_pjsip_state::peer::{PeerSync, SharedHash};
+use check_pjsip_state::ratelimit::RateLimiter;
+use check_pjsip_state::severity::StateClassifier;
+use check_pjsip_state::signals::{SignalRequest, Signals};
+use check_pjsip_state::telemetry::Telemetry;
+use check_pjsip_state::watchdog::Watchdog;
+use check_pjsip_state::webhook::WebhookNotifier;
+use check_pjsip_state::{
+    admin, api, config, destination, fallback, history, ignore, irc_notifier, logging, metrics,
+    notifiers, notify, peer, plugin, preflight, replay, signals, source, systemd, watchdog,
+};
+use chrono::Utc;
+use log::{error, info, warn};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Command line options
+#[derive(Debug, PartialEq)]
+struct Args {
+    config_file: String,
+    // Work through a captured file of `pjsip list endpoints` output instead
+    // of asking asterisk, printing notifications rather than sending them
+    replay: Option<PathBuf>,
+    // Print the decision trace for every detected change
+    explain: bool,
+    // Check the config, asterisk and notifiers, then exit
+    preflight: bool,
+    // Check once, then exit with a status saying what was found
+    once: bool,
+    // Check once without notifying, printing a Nagios/Icinga plugin line
+    // and exiting with its status
+    check: bool,
+    // Set (--mute) or clear (--unmute) the persisted mute, then exit
+    mute: Option<bool>,
+    // Ignore an endpoint for a while or stop ignoring it, then exit
+    ignore: Option<IgnoreChange>,
+    // Look an endpoint up in the change log, then exit
+    query: Option<HistoryQuery>,
+    // Write each change to stdout as NDJSON, for Vector or Fluent Bit,
+    // keeping everything else on stderr
+    emit_events_stdout: bool,
 }
 
-// Define an endpoint structure for deserialization
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-struct Endpoint {
-    endpoint: String,
-    state: String,
-    channels: String,
+#[derive(Debug, PartialEq)]
+enum HistoryQuery {
+    // --history <endpoint> [--since <period>]
+    History(String, Option<String>),
+    // --uptime <endpoint> [--since <period>], over the last week by default
+    Uptime(String, String),
 }
 
-// Struct to hold the parsed data
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-struct EndpointsData {
-    endpoints: Vec<Endpoint>,
+#[derive(Debug, PartialEq)]
+enum IgnoreChange {
+    // --ignore <endpoint> --ttl <seconds>
+    Ignore(String, u64),
+    // --unignore <endpoint>
+    Unignore(String),
 }
 
-// Function to run the asterisk command and parse the output
-fn get_pjsip_endpoints(output: &str) -> EndpointsData {
-    let mut endpoints = Vec::new();
-
-    // Define the regex pattern for extracting the data
-    let re = Regex::new(r"^Endpoint:\s+(\S+)\s+(.+?)\s+(\d+\s+of\s+inf)$").unwrap();
-
-    // Iterate over each line and apply the regex
-    for mut line in output.lines() {
-        line = line.trim();
-        if let Some(captures) = re.captures(line) {
-            let endpoint = captures.get(1).unwrap().as_str().to_string();
-            let state = captures.get(2).unwrap().as_str().to_string();
-            let channels = captures.get(3).unwrap().as_str().to_string();
-
-            // Add the parsed data to the endpoints vector
-            endpoints.push(Endpoint {
-                endpoint,
-                state,
-                channels,
-            });
-        } else {
-            println!("Failed to parse line: {}", line);
+fn parse_args(args: &[String]) -> Option<Args> {
+    let mut config_file = None;
+    let mut replay = None;
+    let mut explain = false;
+    let mut preflight = false;
+    let mut once = false;
+    let mut check = false;
+    let mut mute = None;
+    let mut ignore = None;
+    let mut unignore = None;
+    let mut ttl = None;
+    let mut history = None;
+    let mut uptime = None;
+    let mut since = None;
+    let mut emit_events_stdout = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--replay" => replay = Some(PathBuf::from(args.next()?)),
+            "--explain" => explain = true,
+            "--preflight" => preflight = true,
+            "--once" => once = true,
+            "--check" => check = true,
+            "--mute" => mute = Some(true),
+            "--unmute" => mute = Some(false),
+            "--ignore" => ignore = Some(args.next()?.clone()),
+            "--unignore" => unignore = Some(args.next()?.clone()),
+            "--ttl" => ttl = Some(args.next()?.parse().ok()?),
+            "--history" => history = Some(args.next()?.clone()),
+            "--uptime" => uptime = Some(args.next()?.clone()),
+            "--since" => {
+                let period = args.next()?;
+                history::parse_since(period)?;
+                since = Some(period.clone());
+            }
+            "--emit-events-stdout" => emit_events_stdout = true,
+            _ if config_file.is_none() && !arg.starts_with("--") => config_file = Some(arg.clone()),
+            _ => return None,
         }
     }
 
-    EndpointsData { endpoints }
-}
+    let ignore = match (ignore, unignore, ttl) {
+        (Some(endpoint), None, Some(ttl)) => Some(IgnoreChange::Ignore(endpoint, ttl)),
+        (None, Some(endpoint), None) => Some(IgnoreChange::Unignore(endpoint)),
+        (None, None, None) => None,
+        _ => return None,
+    };
+    let query = match (history, uptime, since) {
+        (Some(endpoint), None, since) => Some(HistoryQuery::History(endpoint, since)),
+        (None, Some(endpoint), since) => Some(HistoryQuery::Uptime(
+            endpoint,
+            since.unwrap_or_else(|| "7d".to_string()),
+        )),
+        (None, None, None) => None,
+        _ => return None,
+    };
 
-// Function to calculate a hash for the endpoints data
-fn calculate_hash(data: &EndpointsData) -> String {
-    let serialized = serde_json::to_string(data).unwrap();
-    let mut hasher = Sha256::new();
-    hasher.update(serialized);
-    format!("{:x}", hasher.finalize())
+    Some(Args {
+        config_file: config_file?,
+        replay,
+        explain,
+        preflight,
+        once,
+        check,
+        mute,
+        ignore,
+        query,
+        emit_events_stdout,
+    })
 }
 
-// Function to read the config file
-fn read_config(file_content: &str) -> Config {
-    toml::from_str(file_content).expect("Failed to parse config.toml")
+// Turn off what needs the asterisk CLI when the output comes from elsewhere
+fn without_asterisk_cli(config: &mut Config) {
+    if config.source != source::Source::Asterisk {
+        // The output is fed in or comes over AMI, so there may be no
+        // asterisk CLI to ask for more
+        config.confirm_polls = 0;
+        // AMI has its own PJSIPShowEndpoint
+        if config.source != source::Source::Ami {
+            config.enrich_endpoint_details = false;
+        }
+        config.include_asterisk_version = false;
+        config.include_asterisk_uptime = false;
+        config.health_policy = HealthPolicy::DeviceOnly;
+    }
 }
 
-async fn slack_send_message(app_token: &str, the_message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
-{
-    let client = SlackClient::new(SlackClientHyperConnector::new()?);
-
-    // Create our Slack API token
-    let token_value: SlackApiTokenValue = app_token.into();
-    let token: SlackApiToken = SlackApiToken::new(token_value);
-    
-    // Create a Slack session with this token
-    // A session is just a lightweight wrapper around your token
-    // not to specify it all the time for series of calls.
-    let session = client.open_session(&token);
-    
-    // Make your first API call (which is `api.test` here)
-    let _: SlackApiTestResponse = session
-            .api_test(&SlackApiTestRequest::new().with_foo("Test".into()))
-            .await?;
-
-    // Send a simple text message
-    let post_chat_req =
-        SlackApiChatPostMessageRequest::new("#general".into(),
-               SlackMessageContent::new().with_text(the_message.into())
-        );
-
-    let _ = session.chat_post_message(&post_chat_req).await?;
-
-    Ok(())
+// Everything configured to be notified, sharing the one IRC connection
+fn build_notifiers(config: &Config, irc: Option<&IrcNotifier>) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = vec![notify::dry_run_if(
+        config.slack.dry_run,
+        Box::new(SlackApiNotifier::new(&config.slack)),
+    )];
+    for workspace in &config.slack_workspaces {
+        notifiers.push(notify::dry_run_if(
+            workspace.slack.dry_run,
+            Box::new(SlackApiNotifier::named(&workspace.name, &workspace.slack)),
+        ));
+    }
+    if let (Some(irc_config), Some(irc)) = (config.irc.as_ref(), irc) {
+        notifiers.push(notify::dry_run_if(
+            irc_config.dry_run,
+            Box::new(irc.clone()),
+        ));
+    }
+    if let Some(webhook_config) = config.webhook.as_ref() {
+        match WebhookNotifier::new(webhook_config) {
+            Ok(webhook) => notifiers.push(notify::dry_run_if(
+                webhook_config.dry_run,
+                Box::new(webhook),
+            )),
+            Err(e) => warn!("Failed to set up the webhook notifier: {}", e),
+        }
+    }
+    for notifier_config in &config.notifiers {
+        match notifiers::notifier(notifier_config) {
+            Ok(notifier) => notifiers.push(notify::dry_run_if(notifier_config.dry_run(), notifier)),
+            Err(e) => warn!(
+                "Failed to set up the {} notifier: {}",
+                notifier_config.name, e
+            ),
+        }
+    }
+    for destination_config in &config.endpoint_destinations {
+        notifiers.push(notify::dry_run_if(
+            destination_config.dry_run,
+            Box::new(destination::notifier(destination_config, &config.slack)),
+        ));
+    }
+    notifiers
 }
 
 // The main function that checks the endpoints periodically and posts to Slack on changes
 #[tokio::main]
 async fn main() {
-
     // Collect the config filename from the command line arguments
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: check-pjsip-state <config_file>");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(args) = parse_args(&args) else {
+        eprintln!(
+            "Usage: check-pjsip-state <config_file> [--replay <capture_file>] [--explain] [--preflight] [--once] [--check] [--mute|--unmute] [--ignore <endpoint> --ttl <seconds>|--unignore <endpoint>] [--history <endpoint>|--uptime <endpoint>] [--since <period>] [--emit-events-stdout]"
+        );
         std::process::exit(1);
+    };
+
+    if args.preflight {
+        let readiness = preflight::run(Path::new(&args.config_file)).await;
+        println!("{}", readiness.report());
+        std::process::exit(if readiness.is_ready() { 0 } else { 1 });
     }
 
-    // Read the configuration file
-    let config_content = fs::read_to_string(&args[1]).expect("Failed to read config.toml");
-    let config = read_config(&config_content);
-    
-    match slack_send_message(&config.slack.api_token, "check-pjsip-started").await {
-        Ok(_) => println!("Message sent to Slack"),
-        Err(e) => eprintln!("Failed to send message to Slack: {}", e),
+    // Read the configuration file, along with anything it includes
+    let mut config = match config::load_config(Path::new(&args.config_file)) {
+        Ok(config) => config,
+        Err(e) => {
+            let config_path = Path::new(&args.config_file);
+            eprintln!(
+                "{}",
+                config::mask_config_path(
+                    &format!("Failed to load {}: {}", args.config_file, e),
+                    config_path,
+                    config::wants_redacted_path(config_path),
+                )
+            );
+            std::process::exit(1);
+        }
     };
-    
-    // Store the hash of the previous data for change detection
-    let mut last_hash: Option<String> = None;
+    logging::init(&config.log_level, config.log_format);
+    info!(
+        "{}",
+        config::mask_config_path(
+            &format!("Loaded config from {}", args.config_file),
+            Path::new(&args.config_file),
+            config.redact_config_path,
+        )
+    );
+
+    if args.check {
+        let (status, line) = plugin::run(&config).await;
+        println!("{}", line);
+        std::process::exit(status.exit_code());
+    }
 
-    loop {
-        // Run the asterisk command and get the current pjsip endpoints output
-        let output = match Command::new("asterisk")
-            .arg("-rx")
-            .arg("pjsip list endpoints")
-            .output() {
-            Ok(output) => output,
-            Err(e) => {
+    if let Some(muted) = args.mute {
+        let Some(path) = config.mute_file.as_deref() else {
+            eprintln!("--mute and --unmute need a mute_file in the config");
+            std::process::exit(1);
+        };
+        if let Err(e) = notify::save_mute(path, muted) {
+            eprintln!("Failed to update the mute file {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+        println!(
+            "Notifications are {}",
+            if muted { "muted" } else { "unmuted" }
+        );
+        std::process::exit(0);
+    }
 
-                eprintln!("Failed to run the command: {}", e);
-                
-                // Send a slack message and abort
-                match slack_send_message(&config.slack.api_token, "Failed to run the command").await {
-                    Ok(_) => println!("Message sent to Slack"),
-                    Err(e) => eprintln!("Failed to send message to Slack: {}", e),
-                };
+    if let Some(change) = args.ignore {
+        let Some(path) = config.ignore_file.as_deref() else {
+            eprintln!("--ignore and --unignore need an ignore_file in the config");
+            std::process::exit(1);
+        };
+        let result = match &change {
+            IgnoreChange::Ignore(endpoint, ttl) => {
+                let until = Utc::now() + chrono::Duration::seconds(*ttl as i64);
+                ignore::save_ignore(path, endpoint, until)
+                    .map(|_| format!("{} is ignored until {}", endpoint, until.to_rfc3339()))
+            }
+            IgnoreChange::Unignore(endpoint) => {
+                ignore::save_unignore(path, endpoint).map(|removed| {
+                    if removed {
+                        format!("{} is no longer ignored", endpoint)
+                    } else {
+                        format!("{} was not ignored", endpoint)
+                    }
+                })
+            }
+        };
+        match result {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("Failed to update the ignore file {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
 
+    if let Some(query) = args.query {
+        let Some(changelog) = config.changelog.as_ref() else {
+            eprintln!("--history and --uptime need a [changelog] in the config");
+            std::process::exit(1);
+        };
+        let records = match Changelog::new(changelog).read_all() {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!(
+                    "Failed to read the change log {}: {}",
+                    changelog.path.display(),
+                    e
+                );
                 std::process::exit(1);
             }
         };
+        let now = Utc::now();
+        // Periods were checked with the arguments
+        let since = |period: &str| now - history::parse_since(period).unwrap_or_default();
+        match query {
+            HistoryQuery::History(endpoint, period) => {
+                for line in history::history(&records, &endpoint, period.as_deref().map(since)) {
+                    println!("{}", line);
+                }
+            }
+            HistoryQuery::Uptime(endpoint, period) => {
+                let classifier =
+                    StateClassifier::new(&config.state_aliases, &config.unhealthy_states);
+                match history::uptime(&records, &endpoint, since(&period), now, &classifier) {
+                    Some(uptime) => println!(
+                        "{}",
+                        uptime.message(&endpoint, &period, config.duration_format)
+                    ),
+                    None => {
+                        eprintln!("The change log has nothing on {}", endpoint);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        std::process::exit(0);
+    }
+
+    // Snapshots to work through in place of polling asterisk, if replaying
+    let replay = match args.replay.as_deref() {
+        Some(path) => match replay::load(path) {
+            Ok(snapshots) => Some(VecDeque::from(snapshots)),
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    if replay.is_some() {
+        // A replay only prints what it would have done
+        config.state_file = None;
+        config.mute_file = None;
+        config.ignore_file = None;
+        config.outbox = None;
+        config.api = None;
+        config.metrics = None;
+        config.peer = None;
+        config.changelog = None;
+        config.poll_watchdog_seconds = None;
+        config.enrich_endpoint_details = false;
+        config.otel = None;
+        config.fallback_notifier = None;
+        config.irc = None;
+        config.webhook = None;
+        config.include_asterisk_version = false;
+        config.include_asterisk_uptime = false;
+        config.admin = None;
+        config.email = None;
+        config.notifiers = Vec::new();
+        // Captures only hold the endpoint list, not the contacts
+        config.health_policy = HealthPolicy::DeviceOnly;
+        config.source = source::Source::Asterisk;
+    }
+    without_asterisk_cli(&mut config);
+    let input = source::spawn(&config.source);
+
+    // The IRC connection is kept across config reloads
+    let irc = config.irc.as_ref().map(|irc_config| {
+        let irc = IrcNotifier::new(irc_config);
+        tokio::spawn(irc_notifier::run(irc_config.clone(), irc.connection()));
+        irc
+    });
+    let notifiers: Vec<Box<dyn Notifier>> = if replay.is_some() {
+        vec![Box::new(ConsoleNotifier {
+            stderr: args.emit_events_stdout,
+        })]
+    } else {
+        build_notifiers(&config, irc.as_ref())
+    };
+    let mut dispatcher = Dispatcher::new(notifiers, config.outbox.as_ref().map(Outbox::open))
+        .with_routes(&config.endpoint_routes)
+        .with_destinations(&config.endpoint_destinations)
+        .with_locale(config.locale);
+    if let Some(path) = config.mute_file.as_deref() {
+        dispatcher = dispatcher.with_mute_file(path);
+    }
+    if let Some(concurrency) = config.notifier_concurrency {
+        dispatcher = dispatcher.with_concurrency(concurrency);
+    }
+    if let Some(budget_config) = config.byte_budget.as_ref() {
+        dispatcher = dispatcher.with_budget(ByteBudget::new(budget_config, Arc::new(SystemClock)));
+    }
+    if let Some(limit_config) = config.rate_limit.as_ref() {
+        dispatcher =
+            dispatcher.with_rate_limit(RateLimiter::new(limit_config, Arc::new(SystemClock)));
+    }
+    if let Some(fallback_config) = config.fallback_notifier.as_ref() {
+        dispatcher = dispatcher.with_fallback(fallback::notifier(fallback_config));
+    }
+    let dispatcher = Arc::new(dispatcher);
+    if let Some(outbox_config) = config.outbox.as_ref() {
+        tokio::spawn(notify::run_outbox(
+            dispatcher.clone(),
+            outbox_config.retry_interval_seconds,
+        ));
+    }
+
+    // Anything about asterisk that couldn't be found is left out
+    let asterisk = config.asterisk();
+    let mut about_asterisk = Vec::new();
+    let version = if config.include_asterisk_version {
+        asterisk.version().await
+    } else {
+        None
+    };
+    if let Some(version) = version {
+        info!("Asterisk version: {}", version);
+        about_asterisk.push(format!("Asterisk {}", version));
+    }
+    let uptime = if config.include_asterisk_uptime {
+        asterisk.uptime().await
+    } else {
+        None
+    };
+    if let Some(uptime) = uptime {
+        info!("Asterisk uptime: {}", uptime);
+        about_asterisk.push(config.locale.fill(Phrase::Up, &[&uptime]));
+    }
+    let startup_message = if about_asterisk.is_empty() {
+        "check-pjsip-started".to_string()
+    } else {
+        format!("check-pjsip-started ({})", about_asterisk.join(", "))
+    };
+    dispatcher.send_text(&startup_message).await;
+
+    // Export a span per poll, if configured
+    let telemetry =
+        config
+            .otel
+            .as_ref()
+            .and_then(|otel_config| match Telemetry::new(otel_config) {
+                Ok(telemetry) => Some(Arc::new(telemetry)),
+                Err(e) => {
+                    warn!("Failed to set up telemetry: {}", e);
+                    None
+                }
+            });
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
+    // Watch for the poll loops getting stuck, if configured
+    let watchdog = config
+        .poll_watchdog_seconds
+        .map(|timeout| Arc::new(Watchdog::new(Arc::new(SystemClock), timeout)));
+    if let Some(watchdog) = watchdog.as_ref() {
+        tokio::spawn(watchdog::run(watchdog.clone(), config.poll_watchdog_abort));
+    }
 
-        // Get the current pjsip endpoints data
-        let current_data = get_pjsip_endpoints(&stdout);
-        let current_hash = calculate_hash(&current_data);
+    // Serve the local admin socket, if configured
+    let maintenance = Arc::new(Maintenance::new(&config.maintenance_endpoints));
+    let ignores = Arc::new(
+        config
+            .ignore_file
+            .as_deref()
+            .map_or_else(TemporaryIgnores::default, TemporaryIgnores::with_file),
+    );
+    let admin = config.admin.as_ref().map(|admin_config| {
+        let admin = Arc::new(
+            Admin::new(dispatcher.clone())
+                .with_maintenance(maintenance.clone())
+                .with_ignores(ignores.clone()),
+        );
+        tokio::spawn(admin::serve(admin_config.clone(), admin.clone()));
+        admin
+    });
+
+    // Poll metrics, shared by [api] and [metrics] when both are configured.
+    // [metrics] is there for the per-endpoint series, so always has them.
+    let metrics = (config.api.is_some() || config.metrics.is_some()).then(|| {
+        let per_endpoint = config.metrics.is_some()
+            || config
+                .api
+                .as_ref()
+                .is_some_and(|api_config| api_config.per_endpoint_metrics);
+        Arc::new(Mutex::new(Metrics::new(per_endpoint)))
+    });
+    if let (Some(metrics_config), Some(metrics)) = (config.metrics.as_ref(), metrics.as_ref()) {
+        tokio::spawn(metrics::serve(metrics_config.clone(), metrics.clone()));
+    }
 
-        // Compare the hash with the last one
-        if last_hash.is_none() || last_hash.as_ref().unwrap() != &current_hash {
-            // Data has changed, send a notification to Slack
-            let message = format!("Endpoints have changed: {:?}", current_data);
-            
-            match slack_send_message(&config.slack.api_token, &message).await {
-                Ok(_) => println!("Message sent to Slack"),
-                Err(e) => eprintln!("Failed to send message to Slack: {}", e),
+    // Serve this monitor's hash to its peer and compare with the peer's, if configured
+    let mut peer = config.peer.as_ref().and_then(|peer_config| {
+        let hash = SharedHash::default();
+        tokio::spawn(peer::serve(peer_config.clone(), hash.clone()));
+        PeerSync::new(peer_config, hash)
+            .map_err(|e| warn!("Failed to set up the peer sync: {}", e))
+            .ok()
+    });
+
+    // Keep recent changes in memory and serve them over HTTP, if configured
+    let api = config
+        .api
+        .as_ref()
+        .zip(metrics.as_ref())
+        .map(|(api_config, metrics)| {
+            let history = Arc::new(Mutex::new(ChangeHistory::new(api_config.history_size)));
+            // The slowest source sets how long a poll may take to come round
+            let interval = config
+                .sources
+                .iter()
+                .filter_map(|source_config| source_config.interval_seconds)
+                .chain([config.sleep_time_seconds])
+                .max()
+                .unwrap_or(config.sleep_time_seconds);
+            let health = api::Health::new(
+                metrics.clone(),
+                api_config.max_poll_age_seconds.unwrap_or(interval * 3),
+                config.sources.len(),
+            );
+            tokio::spawn(api::serve(
+                api_config.listen_addr.clone(),
+                history.clone(),
+                health,
+            ));
+            history
+        });
+
+    // While polling, stop cleanly on SIGTERM and read the config again on
+    // SIGHUP, swapping in its notifiers
+    let signals = if replay.is_none() && input.is_none() && !args.once {
+        let path = PathBuf::from(&args.config_file);
+        let redact = config.redact_config_path;
+        let dispatcher = dispatcher.clone();
+        let reload = move || {
+            let mut config = config::load_config(&path)
+                .map_err(|e| config::mask_config_path(&e.to_string(), &path, redact))?;
+            without_asterisk_cli(&mut config);
+            dispatcher.replace_notifiers(build_notifiers(&config, irc.as_ref()));
+            Ok(config)
+        };
+        signals::listen(reload)
+            .map_err(|e| warn!("Failed to listen for signals: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    let mut stopping = signals
+        .as_ref()
+        .map(|sender| Signals::new(sender.subscribe()));
+
+    // One monitor per source, each polling on its own
+    let config = Arc::new(config);
+    let monitors = match (replay, input) {
+        (Some(snapshots), _) => vec![Monitor::new(
+            config.clone(),
+            dispatcher.clone(),
+            PollSource::Replay(snapshots),
+        )],
+        (None, Some(input)) => vec![Monitor::new(
+            config.clone(),
+            dispatcher.clone(),
+            PollSource::Input(input),
+        )],
+        (None, None) if !config.sources.is_empty() => config
+            .sources
+            .iter()
+            .map(|source_config| {
+                Monitor::for_source(config.clone(), dispatcher.clone(), source_config)
+            })
+            .collect(),
+        (None, None) => {
+            let source = match config.ami.as_ref() {
+                Some(ami_config) if config.source == source::Source::Ami => {
+                    PollSource::Ami(AmiClient::new(ami_config))
+                }
+                _ => PollSource::Asterisk,
             };
-
-            // Update the last_hash with the current one
-            last_hash = Some(current_hash);
-        } else {
-            println!("No change detected.");
+            vec![Monitor::new(config.clone(), dispatcher.clone(), source)]
         }
-
-        // Sleep for a certain interval before the next check
-        sleep(Duration::from_secs(config.sleep_time_seconds)).await;
+    };
+    let events = args
+        .emit_events_stdout
+        .then(|| Arc::new(EventEmitter::stdout()));
+    // Under a Type=notify unit, say when the first readings are in and keep
+    // systemd's watchdog fed
+    let systemd = if args.once {
+        None
+    } else {
+        systemd::Systemd::from_env(monitors.len()).map(Arc::new)
+    };
+    let polls: Vec<_> = monitors
+        .into_iter()
+        .map(|mut monitor| {
+            if let Some(events) = events.as_ref() {
+                monitor = monitor.with_events(events.clone());
+            }
+            monitor = monitor
+                .with_explain(args.explain)
+                .with_maintenance(maintenance.clone())
+                .with_ignores(ignores.clone());
+            if let Some(admin) = admin.as_ref() {
+                monitor = monitor.with_admin(admin.clone());
+            }
+            if let Some(peer) = peer.take() {
+                monitor = monitor.with_peer(peer);
+            }
+            match (api.as_ref(), metrics.as_ref()) {
+                (Some(history), Some(metrics)) => {
+                    monitor = monitor.with_api(history.clone(), metrics.clone())
+                }
+                (None, Some(metrics)) => monitor = monitor.with_metrics(metrics.clone()),
+                _ => {}
+            }
+            if let Some(telemetry) = telemetry.as_ref() {
+                monitor = monitor.with_telemetry(telemetry.clone());
+            }
+            if let Some(watchdog) = watchdog.as_ref() {
+                monitor = monitor.with_watchdog(watchdog.clone());
+            }
+            if let Some(systemd) = systemd.as_ref() {
+                monitor = monitor.with_systemd(systemd.clone());
+            }
+            if let Some(sender) = signals.as_ref() {
+                monitor = monitor.with_signals(Signals::new(sender.subscribe()));
+            }
+            tokio::spawn(monitor.poll_loop(args.once))
+        })
+        .collect();
+
+    // Exit once every source has stopped, with the worst of their statuses
+    let mut exit_code = 0;
+    for poll in polls {
+        match poll.await {
+            Ok(result) => exit_code = exit_code.max(result.exit_code()),
+            Err(e) => {
+                error!("A poll loop stopped unexpectedly: {}", e);
+                exit_code = exit_code.max(1);
+            }
+        }
+    }
+    if let Some(systemd) = systemd.as_ref() {
+        systemd.stopping();
+    }
+    // Say so if a signal stopped the monitor, as the config is by then
+    if let Some(stopping) = stopping.as_mut() {
+        let mut notify_on_shutdown = config.notify_on_shutdown;
+        let mut reason = None;
+        for request in stopping.received() {
+            match request {
+                SignalRequest::Reload(config) => notify_on_shutdown = config.notify_on_shutdown,
+                SignalRequest::Shutdown(shutdown) => reason = Some(shutdown),
+            }
+        }
+        if let (true, Some(reason)) = (notify_on_shutdown, reason) {
+            dispatcher
+                .send_text(&format!("check-pjsip-stopping ({})", reason))
+                .await;
+        }
+    }
+    if let Some(telemetry) = telemetry.as_ref() {
+        telemetry.shutdown();
     }
+    std::process::exit(exit_code);
 }
 
 // Unit tests
@@ -180,95 +690,67 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_parse_pjsip_output() {
-        let output = r#"
-            Endpoint:  500/500                                              Unavailable   0 of inf
-            Endpoint:  502/502                                              Not in use    0 of inf
-            Endpoint:  Voipfone                                             Not in use    0 of inf
-        "#;
-
-        let expected_data = EndpointsData {
-            endpoints: vec![
-                Endpoint {
-                    endpoint: "500/500".to_string(),
-                    state: "Unavailable".to_string(),
-                    channels: "0 of inf".to_string(),
-                },
-                Endpoint {
-                    endpoint: "502/502".to_string(),
-                    state: "Not in use".to_string(),
-                    channels: "0 of inf".to_string(),
-                },
-                Endpoint {
-                    endpoint: "Voipfone".to_string(),
-                    state: "Not in use".to_string(),
-                    channels: "0 of inf".to_string(),
-                },
-            ],
-        };
-
-        let parsed_data = get_pjsip_endpoints(output);
-        assert_eq!(parsed_data, expected_data);
-    }
-
-    #[test]
-    fn test_calculate_hash() {
-        let data = EndpointsData {
-            endpoints: vec![
-                Endpoint {
-                    endpoint: "500/500".to_string(),
-                    state: "Unavailable".to_string(),
-                    channels: "0 of inf".to_string(),
-                },
-                Endpoint {
-                    endpoint: "502/502".to_string(),
-                    state: "Not in use".to_string(),
-                    channels: "0 of inf".to_string(),
-                },
-            ],
-        };
-
-        // Calculate the hash for the initial data
-        let initial_hash = calculate_hash(&data);
-
-        // Modify the data and check that the hash changes
-        let modified_data = EndpointsData {
-            endpoints: vec![
-                Endpoint {
-                    endpoint: "500/500".to_string(),
-                    state: "Unavailable".to_string(),
-                    channels: "0 of inf".to_string(),
-                },
-                Endpoint {
-                    endpoint: "502/502".to_string(),
-                    state: "Unavailable".to_string(), // Changed from "Not in use"
-                    channels: "0 of inf".to_string(),
-                },
-            ],
-        };
-
-        let modified_hash = calculate_hash(&modified_data);
-
-        // Ensure that the hash is different after modification
-        assert_ne!(initial_hash, modified_hash);
-    }
-
-    #[test]
-    fn test_read_config() {
-        let config_content = r#"
-            sleep_time_seconds = 60
-            [slack]
-            webhook_url = "https://hooks.slack.com/services/TEST/WEBHOOK/URL"
-        "#;
-
-        let expected_config = Config {
-            sleep_time_seconds: 60,
-            slack: SlackConfig {
-                webhook_url: "https://hooks.slack.com/services/TEST/WEBHOOK/URL".to_string(),
-            },
-        };
-
-        let config = read_config(config_content);
-        assert_eq!(config, expected_config);
+    fn test_parse_args() {
+        let args =
+            |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+
+        assert_eq!(
+            args(&["config.toml", "--replay", "capture.txt", "--explain"]),
+            Some(Args {
+                config_file: "config.toml".to_string(),
+                replay: Some(PathBuf::from("capture.txt")),
+                explain: true,
+                preflight: false,
+                once: false,
+                check: false,
+                mute: None,
+                ignore: None,
+                query: None,
+                emit_events_stdout: false,
+            })
+        );
+        assert_eq!(
+            args(&["config.toml", "--emit-events-stdout"]).map(|args| args.emit_events_stdout),
+            Some(true)
+        );
+        assert_eq!(
+            args(&["config.toml", "--once"]).map(|args| args.once),
+            Some(true)
+        );
+        assert_eq!(
+            args(&["config.toml", "--check"]).map(|args| args.check),
+            Some(true)
+        );
+        assert_eq!(
+            args(&["config.toml", "--unmute"]).map(|args| args.mute),
+            Some(Some(false))
+        );
+        assert_eq!(
+            args(&["config.toml", "--ignore", "Voipfone", "--ttl", "7200"]).map(|args| args.ignore),
+            Some(Some(IgnoreChange::Ignore("Voipfone".to_string(), 7200)))
+        );
+        assert_eq!(args(&["config.toml", "--ignore", "Voipfone"]), None);
+        assert_eq!(
+            args(&["config.toml", "--uptime", "Voipfone"]).map(|args| args.query),
+            Some(Some(HistoryQuery::Uptime(
+                "Voipfone".to_string(),
+                "7d".to_string()
+            )))
+        );
+        assert_eq!(
+            args(&["config.toml", "--history", "Voipfone", "--since", "12h"])
+                .map(|args| args.query),
+            Some(Some(HistoryQuery::History(
+                "Voipfone".to_string(),
+                Some("12h".to_string())
+            )))
+        );
+        assert_eq!(
+            args(&["config.toml", "--uptime", "Voipfone", "--since", "7w"]),
+            None
+        );
+        assert_eq!(args(&["config.toml", "--since", "7d"]), None);
+        assert_eq!(args(&["config.toml", "--replay"]), None);
+        assert_eq!(args(&["--explain"]), None);
     }
 }
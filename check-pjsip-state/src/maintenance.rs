@@ -0,0 +1,84 @@
+use log::warn;
+use regex::Regex;
+use std::sync::Mutex;
+
+// Endpoints under maintenance, whose changes are logged but never notified.
+// Shared with the admin socket, which can change the list at runtime.
+#[derive(Default)]
+pub struct Maintenance {
+    // Each pattern as written, alongside the anchored regex it matches with
+    patterns: Mutex<Vec<(String, Regex)>>,
+}
+
+impl Maintenance {
+    pub fn new(patterns: &[String]) -> Self {
+        let maintenance = Maintenance::default();
+        for pattern in patterns {
+            if let Err(e) = maintenance.add(pattern) {
+                warn!("Ignoring maintenance endpoint {}: {}", pattern, e);
+            }
+        }
+        maintenance
+    }
+
+    // A name or regex, which has to match the whole endpoint name
+    pub fn add(&self, pattern: &str) -> Result<(), regex::Error> {
+        let regex = Regex::new(&format!("^(?:{})$", pattern))?;
+        let mut patterns = self.patterns.lock().unwrap();
+        if !patterns.iter().any(|(existing, _)| existing == pattern) {
+            patterns.push((pattern.to_string(), regex));
+        }
+        Ok(())
+    }
+
+    // Whether the pattern was there to remove
+    pub fn remove(&self, pattern: &str) -> bool {
+        let mut patterns = self.patterns.lock().unwrap();
+        let before = patterns.len();
+        patterns.retain(|(existing, _)| existing != pattern);
+        patterns.len() != before
+    }
+
+    pub fn patterns(&self) -> Vec<String> {
+        let patterns = self.patterns.lock().unwrap();
+        patterns
+            .iter()
+            .map(|(pattern, _)| pattern.clone())
+            .collect()
+    }
+
+    // The pattern putting an endpoint under maintenance, if any
+    pub fn matching(&self, endpoint: &str) -> Option<String> {
+        let patterns = self.patterns.lock().unwrap();
+        patterns
+            .iter()
+            .find(|(_, regex)| regex.is_match(endpoint))
+            .map(|(pattern, _)| pattern.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_and_regexes_match_whole_endpoints() {
+        let maintenance = Maintenance::new(&["Voipfone".to_string(), "50[0-2]/.*".to_string()]);
+
+        assert_eq!(
+            maintenance.matching("Voipfone"),
+            Some("Voipfone".to_string())
+        );
+        assert_eq!(
+            maintenance.matching("501/501"),
+            Some("50[0-2]/.*".to_string())
+        );
+        assert_eq!(maintenance.matching("Voipfone2"), None);
+        assert_eq!(maintenance.matching("503/503"), None);
+
+        assert!(maintenance.remove("Voipfone"));
+        assert!(!maintenance.remove("Voipfone"));
+        assert_eq!(maintenance.matching("Voipfone"), None);
+        assert!(maintenance.add("(unclosed").is_err());
+    }
+}
@@ -0,0 +1,450 @@
+use crate::severity::StateClassifier;
+use crate::EndpointsData;
+use axum::extract::State;
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+// Optional [metrics] config, serving just /metrics for Prometheus to scrape
+// without the rest of the API
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct MetricsConfig {
+    pub listen_addr: String,
+}
+
+// Poll metrics in the Prometheus text format, served at GET /metrics.
+// Counters and gauges are totals across every source.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    per_endpoint: bool,
+    polls: u64,
+    changes: u64,
+    last_check: Option<DateTime<Utc>>,
+    // Notifier name -> failed send attempts, as of the last poll
+    failures: BTreeMap<String, u64>,
+    // Source name -> its last reading, "" for the only source
+    readings: BTreeMap<String, Reading>,
+    // (source, endpoint, from state, to state) -> how often it happened
+    transitions: BTreeMap<(String, String, String, String), u64>,
+}
+
+#[derive(Debug, Default)]
+struct Reading {
+    checked: Option<DateTime<Utc>>,
+    data: EndpointsData,
+    polls: u64,
+    endpoints: usize,
+    healthy: usize,
+    by_endpoint: BTreeMap<String, EndpointSample>,
+}
+
+#[derive(Debug)]
+struct EndpointSample {
+    state: String,
+    up: bool,
+    active_channels: u32,
+}
+
+pub type SharedMetrics = Arc<Mutex<Metrics>>;
+
+// A label value with Prometheus' escapes applied
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+impl Metrics {
+    // Per-endpoint series are one per endpoint, so large deployments may
+    // want just the totals
+    pub fn new(per_endpoint: bool) -> Self {
+        Metrics {
+            per_endpoint,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_poll(
+        &mut self,
+        source: &str,
+        data: &EndpointsData,
+        classifier: &StateClassifier,
+        changes: usize,
+        now: DateTime<Utc>,
+    ) {
+        self.polls += 1;
+        self.changes += changes as u64;
+        self.last_check = Some(now);
+        let reading = self.readings.entry(source.to_string()).or_default();
+        if self.per_endpoint {
+            // Endpoints seen for the first time only set the baseline
+            for endpoint in &data.endpoints {
+                let previous = reading
+                    .data
+                    .endpoints
+                    .iter()
+                    .find(|previous| previous.endpoint == endpoint.endpoint);
+                if let Some(previous) = previous.filter(|p| p.state != endpoint.state) {
+                    let key = (
+                        source.to_string(),
+                        endpoint.endpoint.clone(),
+                        previous.state.to_string(),
+                        endpoint.state.to_string(),
+                    );
+                    *self.transitions.entry(key).or_default() += 1;
+                }
+            }
+        }
+        reading.checked = Some(now);
+        reading.data = data.clone();
+        reading.polls += 1;
+        reading.endpoints = data.endpoints.len();
+        reading.healthy = data
+            .endpoints
+            .iter()
+            .filter(|endpoint| classifier.is_healthy(endpoint.state.as_str()))
+            .count();
+        if self.per_endpoint {
+            reading.by_endpoint = data
+                .endpoints
+                .iter()
+                .map(|endpoint| {
+                    let sample = EndpointSample {
+                        state: endpoint.state.to_string(),
+                        up: classifier.is_healthy(endpoint.state.as_str()),
+                        active_channels: endpoint.active_channels,
+                    };
+                    (endpoint.endpoint.clone(), sample)
+                })
+                .collect();
+        }
+    }
+
+    // When any source last read its endpoints
+    pub fn last_check(&self) -> Option<DateTime<Utc>> {
+        self.last_check
+    }
+
+    // Each source's last reading and when it was taken, "" for the only source
+    pub fn readings(&self) -> Vec<(&str, Option<DateTime<Utc>>, &EndpointsData)> {
+        self.readings
+            .iter()
+            .map(|(source, reading)| (source.as_str(), reading.checked, &reading.data))
+            .collect()
+    }
+
+    // The dispatcher's running failure counts at each poll
+    pub fn record_failures(&mut self, failures: BTreeMap<String, u64>) {
+        self.failures = failures;
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+
+        metric(
+            "pjsip_polls_total",
+            "counter",
+            "Polls of asterisk completed",
+            vec![(String::new(), self.polls.to_string())],
+        );
+        metric(
+            "pjsip_changes_total",
+            "counter",
+            "Endpoint changes detected",
+            vec![(String::new(), self.changes.to_string())],
+        );
+        metric(
+            "pjsip_endpoints",
+            "gauge",
+            "Endpoints in the last reading",
+            vec![(String::new(), self.total(|reading| reading.endpoints))],
+        );
+        metric(
+            "pjsip_endpoints_healthy",
+            "gauge",
+            "Healthy endpoints in the last reading",
+            vec![(String::new(), self.total(|reading| reading.healthy))],
+        );
+        if let Some(last_check) = self.last_check {
+            metric(
+                "pjsip_last_check_timestamp_seconds",
+                "gauge",
+                "When asterisk was last polled, in seconds since the epoch",
+                vec![(String::new(), last_check.timestamp().to_string())],
+            );
+        }
+        metric(
+            "pjsip_notification_failures_total",
+            "counter",
+            "Failed attempts to send to each notifier",
+            self.failures
+                .iter()
+                .map(|(notifier, failures)| {
+                    let label = format!("{{notifier=\"{}\"}}", escape_label(notifier));
+                    (label, failures.to_string())
+                })
+                .collect(),
+        );
+
+        let sources: Vec<(String, String)> = self
+            .readings
+            .iter()
+            .filter(|(source, _)| !source.is_empty())
+            .map(|(source, reading)| {
+                let label = format!("{{source=\"{}\"}}", escape_label(source));
+                (label, reading.polls.to_string())
+            })
+            .collect();
+        if !sources.is_empty() {
+            metric(
+                "pjsip_source_polls_total",
+                "counter",
+                "Polls completed of each source",
+                sources,
+            );
+        }
+
+        if self.per_endpoint {
+            // Endpoints are told apart by source too, once there's more than one
+            let labels = |source: &str, name: &str, extra: &[(&str, &str)]| {
+                let mut labels = Vec::new();
+                if !source.is_empty() {
+                    labels.push(format!("source=\"{}\"", escape_label(source)));
+                }
+                labels.push(format!("endpoint=\"{}\"", escape_label(name)));
+                for (key, value) in extra {
+                    labels.push(format!("{}=\"{}\"", key, escape_label(value)));
+                }
+                format!("{{{}}}", labels.join(","))
+            };
+            let label = |source: &str, name: &str| labels(source, name, &[]);
+            let state_label =
+                |source: &str, name: &str, state: &str| labels(source, name, &[("state", state)]);
+            let by_endpoint = || {
+                self.readings.iter().flat_map(|(source, reading)| {
+                    reading
+                        .by_endpoint
+                        .iter()
+                        .map(move |(name, sample)| (source.as_str(), name.as_str(), sample))
+                })
+            };
+            metric(
+                "pjsip_endpoint_up",
+                "gauge",
+                "Whether the endpoint is in a healthy state",
+                by_endpoint()
+                    .map(|(source, name, sample)| {
+                        (label(source, name), (sample.up as u8).to_string())
+                    })
+                    .collect(),
+            );
+            metric(
+                "pjsip_endpoint_channels",
+                "gauge",
+                "Channels the endpoint has in use",
+                by_endpoint()
+                    .map(|(source, name, sample)| {
+                        (label(source, name), sample.active_channels.to_string())
+                    })
+                    .collect(),
+            );
+            metric(
+                "pjsip_endpoint_state",
+                "gauge",
+                "The endpoint's current state, as a label on a series of 1",
+                by_endpoint()
+                    .map(|(source, name, sample)| {
+                        (
+                            state_label(source, name, sample.state.as_str()),
+                            "1".to_string(),
+                        )
+                    })
+                    .collect(),
+            );
+            metric(
+                "pjsip_active_channels",
+                "gauge",
+                "Active channels parsed from the endpoint's channels column",
+                by_endpoint()
+                    .map(|(source, name, sample)| {
+                        (label(source, name), sample.active_channels.to_string())
+                    })
+                    .collect(),
+            );
+            metric(
+                "pjsip_endpoint_transitions_total",
+                "counter",
+                "Times the endpoint changed from one state to another",
+                self.transitions
+                    .iter()
+                    .map(|((source, name, from, to), count)| {
+                        let label = labels(source, name, &[("from", from), ("to", to)]);
+                        (label, count.to_string())
+                    })
+                    .collect(),
+            );
+        }
+        out
+    }
+
+    // A gauge summed over every source's last reading
+    fn total(&self, gauge: impl Fn(&Reading) -> usize) -> String {
+        self.readings.values().map(gauge).sum::<usize>().to_string()
+    }
+}
+
+// GET /metrics
+pub async fn get_metrics(State(metrics): State<SharedMetrics>) -> String {
+    metrics.lock().unwrap().render()
+}
+
+// Run the [metrics] server until the process exits
+pub async fn serve(config: MetricsConfig, metrics: SharedMetrics) {
+    let listener = match tokio::net::TcpListener::bind(&config.listen_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(
+                "Failed to start metrics server on {}: {}",
+                config.listen_addr, e
+            );
+            return;
+        }
+    };
+
+    info!("Metrics server listening on {}", config.listen_addr);
+    let router = Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(metrics);
+    if let Err(e) = axum::serve(listener, router).await {
+        error!("Metrics server failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+    use chrono::TimeZone;
+
+    fn checked_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+    }
+
+    fn data() -> EndpointsData {
+        let endpoint = |name: &str, state: &str, channels: &str| Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: channels.to_string(),
+            active_channels: crate::parse_channels(channels).unwrap().0,
+            channel_limit: None,
+            extra: Vec::new(),
+        };
+        EndpointsData {
+            endpoints: vec![
+                endpoint("500/500", "In use", "2 of inf"),
+                endpoint("502/502", "Unavailable", "0 of inf"),
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scrape_per_endpoint_metrics() {
+        let metrics = Arc::new(Mutex::new(Metrics::new(true)));
+        {
+            let mut metrics = metrics.lock().unwrap();
+            metrics.record_poll("", &data(), &StateClassifier::default(), 1, checked_at());
+            metrics.record_failures(BTreeMap::from([("slack".to_string(), 3)]));
+        }
+
+        let scraped = get_metrics(State(metrics)).await;
+        for line in [
+            "pjsip_polls_total 1",
+            "pjsip_changes_total 1",
+            "pjsip_endpoints 2",
+            "pjsip_endpoints_healthy 1",
+            "pjsip_endpoint_up{endpoint=\"500/500\"} 1",
+            "pjsip_endpoint_up{endpoint=\"502/502\"} 0",
+            "pjsip_endpoint_channels{endpoint=\"500/500\"} 2",
+            "# TYPE pjsip_endpoint_channels gauge",
+            "pjsip_endpoint_state{endpoint=\"500/500\",state=\"In use\"} 1",
+            "pjsip_endpoint_state{endpoint=\"502/502\",state=\"Unavailable\"} 1",
+            "pjsip_active_channels{endpoint=\"500/500\"} 2",
+            "pjsip_active_channels{endpoint=\"502/502\"} 0",
+            "pjsip_last_check_timestamp_seconds 1714564800",
+            "pjsip_notification_failures_total{notifier=\"slack\"} 3",
+        ] {
+            assert!(
+                scraped.lines().any(|l| l == line),
+                "missing {:?} in\n{}",
+                line,
+                scraped
+            );
+        }
+    }
+
+    #[test]
+    fn test_transitions_are_counted_per_endpoint() {
+        let mut metrics = Metrics::new(true);
+        let classifier = StateClassifier::default();
+        let mut flapped = data();
+        flapped.endpoints[1].state = "Not in use".into();
+        for reading in [data(), flapped.clone(), data(), flapped] {
+            metrics.record_poll("", &reading, &classifier, 1, checked_at());
+        }
+
+        let rendered = metrics.render();
+        for line in [
+            "# TYPE pjsip_endpoint_transitions_total counter",
+            "pjsip_endpoint_transitions_total{endpoint=\"502/502\",from=\"Unavailable\",to=\"Not in use\"} 2",
+            "pjsip_endpoint_transitions_total{endpoint=\"502/502\",from=\"Not in use\",to=\"Unavailable\"} 1",
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "missing {:?}", line);
+        }
+        assert!(!rendered.contains("transitions_total{endpoint=\"500/500\""));
+    }
+
+    #[test]
+    fn test_per_endpoint_metrics_can_be_disabled() {
+        let mut metrics = Metrics::new(false);
+        metrics.record_poll("", &data(), &StateClassifier::default(), 0, checked_at());
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("pjsip_endpoints 2"));
+        assert!(!rendered.contains("endpoint=\""));
+    }
+
+    #[test]
+    fn test_metrics_aggregate_across_sources() {
+        let mut metrics = Metrics::new(true);
+        let classifier = StateClassifier::default();
+        metrics.record_poll("voip1", &data(), &classifier, 1, checked_at());
+        metrics.record_poll("voip2", &data(), &classifier, 0, checked_at());
+        metrics.record_poll("voip1", &data(), &classifier, 0, checked_at());
+
+        let rendered = metrics.render();
+        for line in [
+            "pjsip_polls_total 3",
+            "pjsip_changes_total 1",
+            "pjsip_endpoints 4",
+            "pjsip_endpoints_healthy 2",
+            "pjsip_endpoint_up{source=\"voip2\",endpoint=\"500/500\"} 1",
+            "pjsip_source_polls_total{source=\"voip1\"} 2",
+            "pjsip_endpoint_state{source=\"voip1\",endpoint=\"502/502\",state=\"Unavailable\"} 1",
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "missing {:?}", line);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use log::warn;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Optional [endpoint_names] config, for when the same line shows up under
+// more than one name, e.g. "500" and "500/500". Names with the same key are
+// one endpoint to diffing and alert dedup, while messages keep the raw name.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct NamesConfig {
+    // Raw name -> key, which wins over the pattern
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    // A regex whose first capture group is the key, e.g. "^(\d+)(/\d+)?$"
+    pub pattern: Option<String>,
+}
+
+// Maps raw endpoint names onto the key they're compared by
+#[derive(Clone, Default)]
+pub struct NameCanonicalizer {
+    aliases: HashMap<String, String>,
+    pattern: Option<Regex>,
+}
+
+impl NameCanonicalizer {
+    pub fn new(config: &NamesConfig) -> Self {
+        let pattern = config
+            .pattern
+            .as_deref()
+            .and_then(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!("Ignoring endpoint name pattern {}: {}", pattern, e);
+                    None
+                }
+            });
+        NameCanonicalizer {
+            aliases: config.aliases.clone(),
+            pattern,
+        }
+    }
+
+    // The key for a raw name, which is the name itself unless configured
+    pub fn key(&self, name: &str) -> String {
+        if let Some(key) = self.aliases.get(name) {
+            return key.clone();
+        }
+        self.pattern
+            .as_ref()
+            .and_then(|pattern| pattern.captures(name))
+            .and_then(|captures| captures.get(1))
+            .map_or(name, |key| key.as_str())
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases_win_over_the_pattern() {
+        let names = NameCanonicalizer::new(&NamesConfig {
+            aliases: HashMap::from([("Voipfone".to_string(), "trunk".to_string())]),
+            pattern: Some(r"^(\d+)(/\d+)?$".to_string()),
+        });
+        assert_eq!(names.key("500"), "500");
+        assert_eq!(names.key("500/500"), "500");
+        assert_eq!(names.key("Voipfone"), "trunk");
+        assert_eq!(names.key("Other"), "Other");
+    }
+}
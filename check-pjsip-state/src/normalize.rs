@@ -0,0 +1,100 @@
+use crate::{Endpoint, EndpointsData};
+use serde::Deserialize;
+
+// Endpoint fields whose internal whitespace is collapsed before comparing
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizedField {
+    State,
+    Channels,
+}
+
+pub fn default_normalized_fields() -> Vec<NormalizedField> {
+    vec![NormalizedField::Channels]
+}
+
+// Makes readings from asterisk versions that space columns differently
+// compare equal. Only hashing and diffing see the normalized form; messages
+// still show what asterisk printed.
+pub struct Normalizer {
+    fields: Vec<NormalizedField>,
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+impl Normalizer {
+    pub fn new(fields: &[NormalizedField]) -> Self {
+        Normalizer {
+            fields: fields.to_vec(),
+        }
+    }
+
+    pub fn normalize(&self, endpoint: &Endpoint) -> Endpoint {
+        let mut normalized = endpoint.clone();
+        for field in &self.fields {
+            match field {
+                NormalizedField::State => {
+                    normalized.state = collapse_whitespace(endpoint.state.as_str()).into()
+                }
+                NormalizedField::Channels => {
+                    normalized.channels = collapse_whitespace(&endpoint.channels)
+                }
+            }
+        }
+        normalized
+    }
+
+    pub fn normalize_data(&self, data: &EndpointsData) -> EndpointsData {
+        EndpointsData {
+            endpoints: data.endpoints.iter().map(|e| self.normalize(e)).collect(),
+        }
+    }
+
+    pub fn same(&self, a: &Endpoint, b: &Endpoint) -> bool {
+        self.normalize(a) == self.normalize(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::diff_endpoints_by;
+
+    fn data(state: &str, channels: &str) -> EndpointsData {
+        EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: state.into(),
+                channels: channels.to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_channel_spacing_compares_equal() {
+        let normalizer = Normalizer::new(&default_normalized_fields());
+        let old = data("Not in use", "0 of inf");
+        let new = data("Not in use", "0  of  inf");
+
+        assert!(diff_endpoints_by(&old, &new, |a, b| normalizer.same(a, b)).is_empty());
+        assert_eq!(normalizer.normalize_data(&new), old);
+        // The original spacing is left alone for display
+        assert_eq!(new.endpoints[0].channels, "0  of  inf");
+    }
+
+    #[test]
+    fn test_only_chosen_fields_are_normalized() {
+        let channels_only = Normalizer::new(&default_normalized_fields());
+        let both = Normalizer::new(&[NormalizedField::State, NormalizedField::Channels]);
+        let old = data("Not in use", "0 of inf");
+        let new = data("Not  in use", "0 of inf");
+
+        assert!(!channels_only.same(&old.endpoints[0], &new.endpoints[0]));
+        assert!(both.same(&old.endpoints[0], &new.endpoints[0]));
+    }
+}
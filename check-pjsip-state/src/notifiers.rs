@@ -0,0 +1,90 @@
+use crate::email::{EmailNotifier, MailConfig};
+use crate::notify::Notifier;
+use crate::webhook::{WebhookConfig, WebhookNotifier};
+use serde::Deserialize;
+use std::io;
+
+// A [[notifiers]] entry: a webhook or mailbox sent every notification
+// alongside [slack], as many of each as are configured
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct NotifierConfig {
+    // How it appears in logs, routes and the outbox
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: NotifierKind,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierKind {
+    Webhook(WebhookConfig),
+    Email(MailConfig),
+}
+
+impl NotifierConfig {
+    pub fn dry_run(&self) -> bool {
+        match &self.kind {
+            NotifierKind::Webhook(webhook) => webhook.dry_run,
+            NotifierKind::Email(mail) => mail.dry_run,
+        }
+    }
+}
+
+pub fn notifier(config: &NotifierConfig) -> io::Result<Box<dyn Notifier>> {
+    Ok(match &config.kind {
+        NotifierKind::Webhook(webhook) => Box::new(WebhookNotifier::named(&config.name, webhook)?),
+        NotifierKind::Email(mail) => Box::new(EmailNotifier::named(&config.name, mail)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Notifiers {
+        notifiers: Vec<NotifierConfig>,
+    }
+
+    #[test]
+    fn test_notifiers_of_each_type() {
+        let parsed: Notifiers = toml::from_str(
+            r#"
+            [[notifiers]]
+            name = "ops-hook"
+            type = "webhook"
+            url = "https://hooks.example.com/pjsip"
+            [[notifiers]]
+            name = "ops-mail"
+            type = "email"
+            from = "pbx@example.com"
+            to = ["ops@example.com"]
+            dry_run = true
+            [[notifiers]]
+            name = "noc-hook"
+            type = "webhook"
+            url = "https://noc.example.com/pjsip"
+            "#,
+        )
+        .unwrap();
+
+        let mail = &parsed.notifiers[1];
+        assert_eq!(
+            mail.kind,
+            NotifierKind::Email(MailConfig {
+                from: "pbx@example.com".to_string(),
+                to: vec!["ops@example.com".to_string()],
+                subject: "PJSIP endpoint changes".to_string(),
+                sendmail: "/usr/sbin/sendmail".into(),
+                dry_run: true,
+            })
+        );
+        assert!(mail.dry_run());
+        let names: Vec<String> = parsed
+            .notifiers
+            .iter()
+            .map(|config| notifier(config).unwrap().name().to_string())
+            .collect();
+        assert_eq!(names, vec!["ops-hook", "ops-mail", "noc-hook"]);
+    }
+}
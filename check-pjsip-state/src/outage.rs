@@ -0,0 +1,238 @@
+use crate::diff::EndpointChange;
+use crate::locale::{Locale, Phrase};
+use crate::severity::StateClassifier;
+use crate::EndpointsData;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Optional [outage_detection] config. When most endpoints share the same
+// unhealthy state at once, the registrar or the network is the likelier
+// cause, so one systemic message is sent instead of an alert per endpoint.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct OutageConfig {
+    // Fewer endpoints than this in one state never count as an outage
+    #[serde(default = "default_min_endpoints")]
+    pub min_endpoints: usize,
+    // Share of the monitored endpoints that must be in the same unhealthy
+    // state. The outage is over once the share drops below it again.
+    #[serde(default = "default_min_fraction")]
+    pub min_fraction: f64,
+}
+
+fn default_min_endpoints() -> usize {
+    3
+}
+
+fn default_min_fraction() -> f64 {
+    1.0
+}
+
+// How a poll left a systemic outage
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutageStep {
+    Started {
+        state: String,
+        count: usize,
+        total: usize,
+    },
+    Ongoing {
+        state: String,
+    },
+    Over {
+        state: String,
+        count: usize,
+        total: usize,
+    },
+}
+
+impl OutageStep {
+    // Whether the outage message stands in for this change's own alert.
+    // While it lasts that's anything entering the outage state, and when
+    // it ends anything leaving it.
+    pub fn covers(&self, change: &EndpointChange, classifier: &StateClassifier) -> bool {
+        match (self, change) {
+            (
+                OutageStep::Started { state, .. } | OutageStep::Ongoing { state },
+                EndpointChange::Added(endpoint) | EndpointChange::Changed { new: endpoint, .. },
+            ) => classifier.canonical_state(endpoint.state.as_str()) == state,
+            (OutageStep::Over { state, .. }, EndpointChange::Changed { old, .. }) => {
+                classifier.canonical_state(old.state.as_str()) == state
+            }
+            _ => false,
+        }
+    }
+
+    // The message to send, if the outage started or ended on this poll
+    pub fn message(&self, locale: Locale) -> Option<String> {
+        match self {
+            OutageStep::Started {
+                state,
+                count,
+                total,
+            } => Some(locale.fill(Phrase::OutageStarted, &[count, total, state])),
+            OutageStep::Over {
+                state,
+                count,
+                total,
+            } => Some(locale.fill(Phrase::OutageOver, &[count, total, state])),
+            OutageStep::Ongoing { .. } => None,
+        }
+    }
+}
+
+pub struct OutageDetector {
+    config: OutageConfig,
+    // The state every endpoint went into, while an outage lasts
+    active: Option<String>,
+}
+
+impl OutageDetector {
+    pub fn new(config: &OutageConfig) -> Self {
+        OutageDetector {
+            config: config.clone(),
+            active: None,
+        }
+    }
+
+    pub fn observe(
+        &mut self,
+        data: &EndpointsData,
+        classifier: &StateClassifier,
+    ) -> Option<OutageStep> {
+        let total = data.endpoints.len();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for endpoint in &data.endpoints {
+            if !classifier.is_healthy(endpoint.state.as_str()) {
+                *counts
+                    .entry(classifier.canonical_state(endpoint.state.as_str()))
+                    .or_default() += 1;
+            }
+        }
+        let systemic = |count: usize| {
+            count >= self.config.min_endpoints
+                && count as f64 >= self.config.min_fraction * total as f64
+        };
+
+        match self.active.take() {
+            Some(state) => {
+                let count = counts.get(state.as_str()).copied().unwrap_or_default();
+                if systemic(count) {
+                    self.active = Some(state.clone());
+                    Some(OutageStep::Ongoing { state })
+                } else {
+                    Some(OutageStep::Over {
+                        state,
+                        count,
+                        total,
+                    })
+                }
+            }
+            None => {
+                // The most widespread state, by name on a tie
+                let (state, count) = counts
+                    .into_iter()
+                    .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)))?;
+                if !systemic(count) {
+                    return None;
+                }
+                self.active = Some(state.to_string());
+                Some(OutageStep::Started {
+                    state: state.into(),
+                    count,
+                    total,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::diff_endpoints;
+    use crate::Endpoint;
+
+    fn data(states: &[&str]) -> EndpointsData {
+        EndpointsData {
+            endpoints: states
+                .iter()
+                .enumerate()
+                .map(|(i, state)| Endpoint {
+                    endpoint: format!("50{}/50{}", i, i),
+                    state: (*state).into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn config(min_fraction: f64) -> OutageConfig {
+        OutageConfig {
+            min_endpoints: 3,
+            min_fraction,
+        }
+    }
+
+    #[test]
+    fn test_mass_unavailable_is_one_systemic_alert() {
+        let classifier = StateClassifier::default();
+        let mut detector = OutageDetector::new(&config(1.0));
+        let before = data(&["Not in use"; 4]);
+        let after = data(&["Unavailable"; 4]);
+
+        assert_eq!(detector.observe(&before, &classifier), None);
+        let step = detector.observe(&after, &classifier).unwrap();
+        assert_eq!(
+            step.message(Locale::En).unwrap(),
+            "Systemic outage: 4 of 4 endpoints are Unavailable"
+        );
+        // Every endpoint's own alert is covered by it
+        assert!(diff_endpoints(&before, &after)
+            .iter()
+            .all(|change| step.covers(change, &classifier)));
+
+        let ongoing = detector.observe(&after, &classifier).unwrap();
+        assert_eq!(ongoing.message(Locale::En), None);
+    }
+
+    #[test]
+    fn test_outage_is_over_when_the_fraction_drops() {
+        let classifier = StateClassifier::default();
+        let mut detector = OutageDetector::new(&config(0.75));
+        let down = data(&["Unavailable", "Unavailable", "Unavailable", "In use"]);
+        let recovering = data(&["Not in use", "Not in use", "Unavailable", "In use"]);
+
+        assert!(matches!(
+            detector.observe(&down, &classifier),
+            Some(OutageStep::Started { count: 3, .. })
+        ));
+        let step = detector.observe(&recovering, &classifier).unwrap();
+        assert_eq!(
+            step.message(Locale::En).unwrap(),
+            "Systemic outage over: 1 of 4 endpoints are Unavailable"
+        );
+        let changes = diff_endpoints(&down, &recovering);
+        assert_eq!(changes.len(), 2);
+        assert!(changes
+            .iter()
+            .all(|change| step.covers(change, &classifier)));
+        assert_eq!(detector.observe(&recovering, &classifier), None);
+    }
+
+    #[test]
+    fn test_scattered_failures_are_not_an_outage() {
+        let classifier = StateClassifier::default();
+        let mut detector = OutageDetector::new(&config(0.75));
+
+        let mixed = data(&["Unavailable", "Unavailable", "Invalid", "Invalid"]);
+        assert_eq!(detector.observe(&mixed, &classifier), None);
+        // Too few endpoints to tell, however many of them are down
+        assert_eq!(
+            detector.observe(&data(&["Unavailable", "Unavailable"]), &classifier),
+            None
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use crate::event::{AlertEvent, ChangeKind};
+use crate::severity::Severity;
+use crate::storage;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io;
+use std::path::PathBuf;
+
+// Optional [outbox] config for holding messages that couldn't be delivered
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct OutboxConfig {
+    pub path: PathBuf,
+    // Oldest messages are dropped beyond this
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    #[serde(default = "default_retry_interval_seconds")]
+    pub retry_interval_seconds: u64,
+}
+
+fn default_max_entries() -> usize {
+    1000
+}
+
+fn default_retry_interval_seconds() -> u64 {
+    60
+}
+
+// A message waiting to be delivered to a notifier, with what it was sent
+// as so a retry is routed the same way. Entries queued before these were
+// kept are retried as plain text.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutboxEntry {
+    pub notifier: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ChangeKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<AlertEvent>,
+}
+
+impl OutboxEntry {
+    pub fn text(notifier: &str, message: &str) -> Self {
+        OutboxEntry {
+            notifier: notifier.to_string(),
+            message: message.to_string(),
+            kind: None,
+            severity: None,
+            events: Vec::new(),
+        }
+    }
+}
+
+// Disk-backed queue of undelivered messages, in the order they were sent
+pub struct Outbox {
+    path: PathBuf,
+    max_entries: usize,
+    entries: VecDeque<OutboxEntry>,
+}
+
+impl Outbox {
+    // Open the outbox, picking up anything left over from a previous run
+    pub fn open(config: &OutboxConfig) -> Self {
+        let entries = match storage::read_file(&config.path) {
+            Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt outbox {}: {}", config.path.display(), e);
+                VecDeque::new()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => VecDeque::new(),
+            Err(e) => {
+                warn!("Failed to read outbox {}: {}", config.path.display(), e);
+                VecDeque::new()
+            }
+        };
+
+        Outbox {
+            path: config.path.clone(),
+            max_entries: config.max_entries,
+            entries,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn has_pending(&self, notifier: &str) -> bool {
+        self.entries.iter().any(|entry| entry.notifier == notifier)
+    }
+
+    pub fn push(&mut self, entry: OutboxEntry) {
+        if self.entries.len() >= self.max_entries {
+            if let Some(dropped) = self.entries.pop_front() {
+                warn!(
+                    "Outbox is full, dropping oldest message for {}",
+                    dropped.notifier
+                );
+            }
+        }
+        self.entries.push_back(entry);
+        self.save();
+    }
+
+    // Take every entry out, to be retried and the failures put back
+    pub fn take(&mut self) -> VecDeque<OutboxEntry> {
+        std::mem::take(&mut self.entries)
+    }
+
+    // Put undelivered entries back ahead of anything queued since
+    pub fn restore(&mut self, mut entries: VecDeque<OutboxEntry>) {
+        entries.append(&mut self.entries);
+        self.entries = entries;
+        self.save();
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_vec(&self.entries)
+            .map_err(io::Error::from)
+            .and_then(|serialized| storage::write_file(&self.path, &serialized, false));
+        if let Err(e) = result {
+            error!("Failed to save outbox {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn test_config(name: &str) -> OutboxConfig {
+    let dir = std::env::temp_dir().join(format!(
+        "check-pjsip-state-outbox-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    OutboxConfig {
+        path: dir.join("outbox.json"),
+        max_entries: 3,
+        retry_interval_seconds: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbox_survives_reopen() {
+        let config = test_config("reopen");
+        let mut outbox = Outbox::open(&config);
+        outbox.push(OutboxEntry::text("slack", "first"));
+        outbox.push(OutboxEntry::text("slack", "second"));
+
+        let reopened = Outbox::open(&config);
+        assert_eq!(reopened.len(), 2);
+        assert!(reopened.has_pending("slack"));
+        assert!(!reopened.has_pending("webhook"));
+    }
+
+    #[test]
+    fn test_outbox_is_capped() {
+        let mut outbox = Outbox::open(&test_config("capped"));
+        for message in ["1", "2", "3", "4"] {
+            outbox.push(OutboxEntry::text("slack", message));
+        }
+
+        let messages: Vec<String> = outbox.take().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["2", "3", "4"]);
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+
+pub fn default_parse_log_interval_seconds() -> u64 {
+    300
+}
+
+pub const NUMBERS_PATTERN: &str = r"\d+";
+
+// What a line that couldn't be parsed looks like with its numbers and
+// spacing taken out, so lines that differ only by endpoint or count are
+// logged together
+pub fn pattern(line: &str, numbers_re: &Regex) -> String {
+    let line = numbers_re.replace_all(line, "#");
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// A pattern's occurrences since it was last logged
+struct Pending {
+    count: usize,
+    logged_at: Option<DateTime<Utc>>,
+}
+
+// Logs each pattern of unparseable line at most once per interval, with how
+// many times it came up, so a change in asterisk's format doesn't flood
+// stderr with every line of every poll
+pub struct ParseFailureLog {
+    interval: Duration,
+    patterns: HashMap<String, Pending>,
+}
+
+impl ParseFailureLog {
+    pub fn new(interval_seconds: u64) -> Self {
+        ParseFailureLog {
+            interval: Duration::seconds(interval_seconds as i64),
+            patterns: HashMap::new(),
+        }
+    }
+
+    // Count one poll's unparsed lines, by pattern, returning the entries
+    // that are due to be logged
+    pub fn observe(
+        &mut self,
+        patterns: &BTreeMap<String, usize>,
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        let mut entries = Vec::new();
+        for (pattern, count) in patterns {
+            let pending = self.patterns.entry(pattern.clone()).or_insert(Pending {
+                count: 0,
+                logged_at: None,
+            });
+            pending.count += count;
+            if pending
+                .logged_at
+                .is_none_or(|logged_at| now - logged_at >= self.interval)
+            {
+                entries.push(format!(
+                    "Failed to parse {} lines like: {}",
+                    pending.count, pattern
+                ));
+                pending.count = 0;
+                pending.logged_at = Some(now);
+            }
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_lines_are_logged_once_per_interval() {
+        let mut log = ParseFailureLog::new(300);
+        let started = Utc::now();
+        let numbers_re = Regex::new(NUMBERS_PATTERN).unwrap();
+        let mut patterns = BTreeMap::new();
+        for line in [
+            "Endpoint:  501/501  Avail  0/inf",
+            "Endpoint: 502/502 Avail 1/inf",
+        ] {
+            *patterns.entry(pattern(line, &numbers_re)).or_default() += 1;
+        }
+        assert_eq!(patterns.len(), 1);
+
+        assert_eq!(
+            log.observe(&patterns, started),
+            vec!["Failed to parse 2 lines like: Endpoint: #/# Avail #/inf"]
+        );
+        for minute in 1..5 {
+            assert!(log
+                .observe(&patterns, started + Duration::minutes(minute))
+                .is_empty());
+        }
+        assert_eq!(
+            log.observe(&patterns, started + Duration::minutes(5)),
+            vec!["Failed to parse 10 lines like: Endpoint: #/# Avail #/inf"]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::Uri;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+// Optional [peer] config, for two monitors watching the same asterisk. Each
+// serves the hash of its latest reading and compares it with the other's,
+// since a drift between them likely means one is misreading.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct PeerConfig {
+    // Where this monitor serves its hash, at GET /hash
+    pub listen_addr: String,
+    // The other monitor's /hash, e.g. "http://monitor-b:8091/hash"
+    pub url: String,
+    // Checks in a row the two must disagree before it's a divergence,
+    // since they poll at different moments
+    #[serde(default = "default_divergent_checks")]
+    pub divergent_checks: u32,
+}
+
+fn default_divergent_checks() -> u32 {
+    3
+}
+
+// The hash this monitor last computed, shared with the /hash server
+pub type SharedHash = Arc<Mutex<Option<String>>>;
+
+#[derive(Serialize, Deserialize)]
+struct HashResponse {
+    hash: Option<String>,
+}
+
+// Whether a comparison changed how the two monitors stand
+#[derive(Debug, PartialEq)]
+pub enum PeerStep {
+    Diverged,
+    Agreed,
+}
+
+// Counts how long the two hashes have differed, reporting a divergence once
+// it's lasted long enough and again once they agree
+pub struct Divergence {
+    needed: u32,
+    streak: u32,
+    diverged: bool,
+}
+
+impl Divergence {
+    pub fn new(divergent_checks: u32) -> Self {
+        Divergence {
+            needed: divergent_checks.max(1),
+            streak: 0,
+            diverged: false,
+        }
+    }
+
+    pub fn observe(&mut self, own: &str, peer: &str) -> Option<PeerStep> {
+        if own == peer {
+            self.streak = 0;
+            return std::mem::take(&mut self.diverged).then_some(PeerStep::Agreed);
+        }
+        self.streak += 1;
+        if self.streak >= self.needed && !self.diverged {
+            self.diverged = true;
+            return Some(PeerStep::Diverged);
+        }
+        None
+    }
+}
+
+// Fetches the peer's hash and compares it with this monitor's
+pub struct PeerSync {
+    pub url: String,
+    hash: SharedHash,
+    divergence: Divergence,
+    client: Client<HttpsConnector<HttpConnector>, Empty<Bytes>>,
+}
+
+impl PeerSync {
+    pub fn new(config: &PeerConfig, hash: SharedHash) -> io::Result<Self> {
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(rustls::crypto::ring::default_provider())?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(PeerSync {
+            url: config.url.clone(),
+            hash,
+            divergence: Divergence::new(config.divergent_checks),
+            client: Client::builder(TokioExecutor::new()).build(connector),
+        })
+    }
+
+    // The peer's latest hash, or None if it hasn't polled yet
+    async fn fetch(&self) -> Result<Option<String>, String> {
+        let uri = self.url.parse::<Uri>().map_err(|e| e.to_string())?;
+        let response = self.client.get(uri).await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("peer returned {}", response.status()));
+        }
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| e.to_string())?
+            .to_bytes();
+        let response: HashResponse = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
+        Ok(response.hash)
+    }
+
+    // Publish this monitor's hash and compare it with the peer's. An
+    // unreachable peer or one without a reading yet counts for nothing.
+    pub async fn check(&mut self, own: &str) -> Option<PeerStep> {
+        *self.hash.lock().unwrap() = Some(own.to_string());
+        match self.fetch().await {
+            Ok(Some(peer)) => self.divergence.observe(own, &peer),
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Failed to fetch the peer's hash from {}: {}", self.url, e);
+                None
+            }
+        }
+    }
+}
+
+async fn get_hash(State(hash): State<SharedHash>) -> Json<HashResponse> {
+    Json(HashResponse {
+        hash: hash.lock().unwrap().clone(),
+    })
+}
+
+// Serve GET /hash until the process exits
+pub async fn serve(config: PeerConfig, hash: SharedHash) {
+    let listener = match tokio::net::TcpListener::bind(&config.listen_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(
+                "Failed to start the peer server on {}: {}",
+                config.listen_addr, e
+            );
+            return;
+        }
+    };
+
+    info!("Peer server listening on {}", config.listen_addr);
+    let router = Router::new().route("/hash", get(get_hash)).with_state(hash);
+    if let Err(e) = axum::serve(listener, router).await {
+        error!("Peer server failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divergence_needs_consecutive_differences() {
+        let mut divergence = Divergence::new(2);
+
+        assert_eq!(divergence.observe("a1", "a1"), None);
+        // One poll apart is expected now and then
+        assert_eq!(divergence.observe("b2", "a1"), None);
+        assert_eq!(divergence.observe("b2", "b2"), None);
+
+        assert_eq!(divergence.observe("c3", "b2"), None);
+        assert_eq!(divergence.observe("c3", "b2"), Some(PeerStep::Diverged));
+        // Reported once, not on every check
+        assert_eq!(divergence.observe("c3", "b2"), None);
+        assert_eq!(divergence.observe("c3", "c3"), Some(PeerStep::Agreed));
+        assert_eq!(divergence.observe("c3", "c3"), None);
+    }
+}
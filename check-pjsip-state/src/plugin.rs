@@ -0,0 +1,246 @@
+use crate::ami::AmiClient;
+use crate::config::Config;
+use crate::filter::{endpoint_pattern, EndpointFilter};
+use crate::severity::StateClassifier;
+use crate::source::Source;
+use crate::{get_pjsip_endpoints, EndpointsData};
+use serde::Deserialize;
+
+// [check] config, for --check: what makes a single reading WARNING or
+// CRITICAL to Nagios or Icinga
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CheckConfig {
+    // CRITICAL if any endpoint matching these is unhealthy, e.g. the trunks
+    #[serde(default)]
+    pub critical_endpoints: Vec<String>,
+    // WARNING, or CRITICAL, once at least this many endpoints are unhealthy
+    #[serde(default = "default_warning_unhealthy")]
+    pub warning_unhealthy: usize,
+    pub critical_unhealthy: Option<usize>,
+}
+
+fn default_warning_unhealthy() -> usize {
+    1
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        CheckConfig {
+            critical_endpoints: Vec::new(),
+            warning_unhealthy: default_warning_unhealthy(),
+            critical_unhealthy: None,
+        }
+    }
+}
+
+// A monitoring plugin's status, in the order of its exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PluginStatus {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl PluginStatus {
+    pub fn exit_code(self) -> i32 {
+        self as i32
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PluginStatus::Ok => "OK",
+            PluginStatus::Warning => "WARNING",
+            PluginStatus::Critical => "CRITICAL",
+            PluginStatus::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+// The status of one reading, and the summary line with perfdata to print
+// for it, e.g. "PJSIP CRITICAL - 1 of 3 endpoints unhealthy: Voipfone
+// (Unavailable) | endpoints=3 unhealthy=1;1;;0;3 in_use=1 channels=2"
+pub fn evaluate(
+    config: &CheckConfig,
+    data: &EndpointsData,
+    classifier: &StateClassifier,
+) -> (PluginStatus, String) {
+    // Patterns are checked when the config is loaded
+    let critical: Vec<_> = config
+        .critical_endpoints
+        .iter()
+        .filter_map(|pattern| endpoint_pattern(pattern).ok())
+        .collect();
+    let unhealthy: Vec<_> = data
+        .endpoints
+        .iter()
+        .filter(|endpoint| !classifier.is_healthy(endpoint.state.as_str()))
+        .collect();
+
+    let status = if unhealthy.iter().any(|endpoint| {
+        critical
+            .iter()
+            .any(|pattern| pattern.is_match(&endpoint.endpoint))
+    }) || config
+        .critical_unhealthy
+        .is_some_and(|threshold| unhealthy.len() >= threshold)
+    {
+        PluginStatus::Critical
+    } else if unhealthy.len() >= config.warning_unhealthy {
+        PluginStatus::Warning
+    } else {
+        PluginStatus::Ok
+    };
+
+    let summary = if unhealthy.is_empty() {
+        format!("{} endpoints healthy", data.endpoints.len())
+    } else {
+        let listed: Vec<String> = unhealthy
+            .iter()
+            .map(|endpoint| format!("{} ({})", endpoint.endpoint, endpoint.state))
+            .collect();
+        format!(
+            "{} of {} endpoints unhealthy: {}",
+            unhealthy.len(),
+            data.endpoints.len(),
+            listed.join(", ")
+        )
+    };
+    let in_use = data
+        .endpoints
+        .iter()
+        .filter(|endpoint| endpoint.active_channels > 0)
+        .count();
+    let channels: u32 = data
+        .endpoints
+        .iter()
+        .map(|endpoint| endpoint.active_channels)
+        .sum();
+    let perfdata = format!(
+        "endpoints={} unhealthy={};{};{};0;{} in_use={} channels={}",
+        data.endpoints.len(),
+        unhealthy.len(),
+        config.warning_unhealthy,
+        config
+            .critical_unhealthy
+            .map(|threshold| threshold.to_string())
+            .unwrap_or_default(),
+        data.endpoints.len(),
+        in_use,
+        channels
+    );
+    (status, line(status, &summary, Some(&perfdata)))
+}
+
+fn line(status: PluginStatus, summary: &str, perfdata: Option<&str>) -> String {
+    match perfdata {
+        Some(perfdata) => format!("PJSIP {} - {} | {}", status.label(), summary, perfdata),
+        None => format!("PJSIP {} - {}", status.label(), summary),
+    }
+}
+
+// Poll once and judge the reading, without notifying anyone
+pub async fn run(config: &Config) -> (PluginStatus, String) {
+    let output = match config.ami.as_ref() {
+        Some(ami_config) if config.source == Source::Ami => {
+            AmiClient::new(ami_config).list_endpoints().await
+        }
+        _ => config.asterisk().run_command("pjsip list endpoints").await,
+    };
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            let summary = format!("failed to run the command: {}", e);
+            return (
+                PluginStatus::Unknown,
+                line(PluginStatus::Unknown, &summary, None),
+            );
+        }
+    };
+
+    let classifier = StateClassifier::new(&config.state_aliases, &config.unhealthy_states);
+    let mut data = EndpointFilter::new(&config.filters).apply(&get_pjsip_endpoints(&output));
+    if data.endpoints.is_empty() {
+        return (
+            PluginStatus::Unknown,
+            line(PluginStatus::Unknown, "no endpoints could be parsed", None),
+        );
+    }
+    classifier.canonicalize(&mut data);
+    evaluate(
+        &config.check.clone().unwrap_or_default(),
+        &data,
+        &classifier,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn endpoint(name: &str, state: &str, active_channels: u32) -> Endpoint {
+        Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: format!("{} of inf", active_channels),
+            active_channels,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_status_follows_the_thresholds() {
+        let classifier = StateClassifier::default();
+        let config = CheckConfig {
+            critical_endpoints: vec!["Voip*".to_string()],
+            warning_unhealthy: 1,
+            critical_unhealthy: Some(3),
+        };
+        let data = |states: &[(&str, &str)]| EndpointsData {
+            endpoints: states
+                .iter()
+                .map(|(name, state)| endpoint(name, state, 0))
+                .collect(),
+        };
+
+        let mut healthy = data(&[("500", "Not in use"), ("Voipfone", "In use")]);
+        healthy.endpoints[1].active_channels = 2;
+        assert_eq!(
+            evaluate(&config, &healthy, &classifier),
+            (
+                PluginStatus::Ok,
+                "PJSIP OK - 2 endpoints healthy | endpoints=2 unhealthy=0;1;3;0;2 in_use=1 channels=2"
+                    .to_string()
+            )
+        );
+
+        let (status, line) = evaluate(
+            &config,
+            &data(&[("500", "Unavailable"), ("Voipfone", "Not in use")]),
+            &classifier,
+        );
+        assert_eq!(status, PluginStatus::Warning);
+        assert!(line.starts_with("PJSIP WARNING - 1 of 2 endpoints unhealthy: 500 (Unavailable) |"));
+
+        let (status, _) = evaluate(
+            &config,
+            &data(&[("500", "Not in use"), ("Voipfone", "Unavailable")]),
+            &classifier,
+        );
+        assert_eq!(status, PluginStatus::Critical);
+        assert_eq!(status.exit_code(), 2);
+
+        let (status, _) = evaluate(
+            &config,
+            &data(&[
+                ("500", "Unknown"),
+                ("501", "Invalid"),
+                ("502", "Unavailable"),
+            ]),
+            &classifier,
+        );
+        assert_eq!(status, PluginStatus::Critical);
+    }
+}
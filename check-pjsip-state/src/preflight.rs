@@ -0,0 +1,190 @@
+use crate::ami::AmiClient;
+use crate::config::{self, Config};
+use crate::irc_notifier::IrcNotifier;
+use crate::notify::{Notifier, SlackApiNotifier};
+use crate::source::Source;
+use crate::webhook::WebhookNotifier;
+use crate::{fallback, get_pjsip_endpoints, notifiers};
+use std::io;
+use std::path::Path;
+
+// How one component fared in the preflight checks
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckStatus {
+    Passed(String),
+    // Couldn't be checked without side effects, so it doesn't count against
+    // readiness
+    Skipped(String),
+    Failed(String),
+}
+
+// The results of every check, in the order they ran
+#[derive(Debug, Default)]
+pub struct Readiness {
+    results: Vec<(String, CheckStatus)>,
+}
+
+impl Readiness {
+    pub fn add(&mut self, component: &str, status: CheckStatus) {
+        self.results.push((component.to_string(), status));
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self
+            .results
+            .iter()
+            .any(|(_, status)| matches!(status, CheckStatus::Failed(_)))
+    }
+
+    pub fn report(&self) -> String {
+        let mut lines = vec!["Preflight checks:".to_string()];
+        for (component, status) in &self.results {
+            lines.push(match status {
+                CheckStatus::Passed(detail) => format!("  [ok]   {}: {}", component, detail),
+                CheckStatus::Skipped(detail) => format!("  [skip] {}: {}", component, detail),
+                CheckStatus::Failed(detail) => format!("  [FAIL] {}: {}", component, detail),
+            });
+        }
+
+        let failed = self
+            .results
+            .iter()
+            .filter(|(_, status)| matches!(status, CheckStatus::Failed(_)))
+            .count();
+        lines.push(match failed {
+            0 => "Ready".to_string(),
+            1 => "Not ready: 1 check failed".to_string(),
+            n => format!("Not ready: {} checks failed", n),
+        });
+        lines.join("\n")
+    }
+}
+
+// Check the config, that asterisk answers with something parseable, and
+// that each notifier can be reached, without sending any alerts
+pub async fn run(config_file: &Path) -> Readiness {
+    let mut readiness = Readiness::default();
+
+    let config = match config::load_config(config_file) {
+        Ok(config) => {
+            let loaded = format!("loaded {}", config_file.display());
+            readiness.add(
+                "config",
+                CheckStatus::Passed(config::mask_config_path(
+                    &loaded,
+                    config_file,
+                    config.redact_config_path,
+                )),
+            );
+            config
+        }
+        Err(e) => {
+            let redact = config::wants_redacted_path(config_file);
+            readiness.add(
+                "config",
+                CheckStatus::Failed(config::mask_config_path(
+                    &e.to_string(),
+                    config_file,
+                    redact,
+                )),
+            );
+            return readiness;
+        }
+    };
+
+    let output = match config.ami.as_ref() {
+        Some(ami_config) if config.source == Source::Ami => {
+            AmiClient::new(ami_config).list_endpoints().await
+        }
+        _ => config.asterisk().run_command("pjsip list endpoints").await,
+    };
+    readiness.add("asterisk", check_asterisk(output));
+
+    for notifier in notifiers(&config) {
+        let status = match notifier.check().await {
+            Some(Ok(())) => CheckStatus::Passed("reachable".to_string()),
+            Some(Err(e)) => CheckStatus::Failed(e.to_string()),
+            None => CheckStatus::Skipped("no way to check without sending".to_string()),
+        };
+        readiness.add(notifier.name(), status);
+    }
+
+    readiness
+}
+
+fn check_asterisk(output: io::Result<String>) -> CheckStatus {
+    match output {
+        Ok(output) => match get_pjsip_endpoints(&output).endpoints.len() {
+            0 => CheckStatus::Failed("no endpoints could be parsed from the output".to_string()),
+            count => CheckStatus::Passed(format!("{} endpoints parsed", count)),
+        },
+        Err(e) => CheckStatus::Failed(format!("failed to run the command: {}", e)),
+    }
+}
+
+// Every configured notifier, including the fallback
+fn notifiers(config: &Config) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> =
+        vec![Box::new(SlackApiNotifier::new(&config.slack))];
+    for workspace in &config.slack_workspaces {
+        notifiers.push(Box::new(SlackApiNotifier::named(
+            &workspace.name,
+            &workspace.slack,
+        )));
+    }
+    if let Some(irc_config) = config.irc.as_ref() {
+        notifiers.push(Box::new(IrcNotifier::new(irc_config)));
+    }
+    if let Some(webhook) = config
+        .webhook
+        .as_ref()
+        .and_then(|webhook_config| WebhookNotifier::new(webhook_config).ok())
+    {
+        notifiers.push(Box::new(webhook));
+    }
+    notifiers.extend(
+        config
+            .notifiers
+            .iter()
+            .filter_map(|notifier_config| notifiers::notifier(notifier_config).ok()),
+    );
+    if let Some(fallback_config) = config.fallback_notifier.as_ref() {
+        notifiers.push(fallback::notifier(fallback_config));
+    }
+    notifiers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_report() {
+        let mut readiness = Readiness::default();
+        readiness.add(
+            "config",
+            CheckStatus::Passed("loaded config.toml".to_string()),
+        );
+        readiness.add(
+            "asterisk",
+            CheckStatus::Passed("3 endpoints parsed".to_string()),
+        );
+        readiness.add(
+            "console",
+            CheckStatus::Skipped("no way to check".to_string()),
+        );
+        assert!(readiness.is_ready());
+
+        readiness.add("slack", CheckStatus::Failed("invalid_auth".to_string()));
+        assert!(!readiness.is_ready());
+        assert_eq!(
+            readiness.report(),
+            "Preflight checks:\n\
+             \x20 [ok]   config: loaded config.toml\n\
+             \x20 [ok]   asterisk: 3 endpoints parsed\n\
+             \x20 [skip] console: no way to check\n\
+             \x20 [FAIL] slack: invalid_auth\n\
+             Not ready: 1 check failed"
+        );
+    }
+}
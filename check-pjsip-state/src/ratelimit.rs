@@ -0,0 +1,149 @@
+use crate::clock::Clock;
+use crate::locale::{Locale, Phrase};
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde::Deserialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+
+// Optional [rate_limit] config capping how many messages each notifier is
+// sent per window
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    pub max_messages: usize,
+    #[serde(default = "default_window_seconds")]
+    pub window_seconds: u64,
+}
+
+fn default_window_seconds() -> u64 {
+    3600
+}
+
+// A notifier's sends within the window, and the changes held back from it
+#[derive(Default)]
+struct NotifierLimit {
+    sent: VecDeque<DateTime<Utc>>,
+    suppressed: usize,
+}
+
+// Messages sent to each notifier over a sliding window. Once one reaches
+// the cap, what would have gone to it is counted rather than sent, and it
+// gets one summary of how much it missed as soon as the window allows.
+pub struct RateLimiter {
+    clock: Arc<dyn Clock>,
+    max_messages: usize,
+    window: Duration,
+    notifiers: BTreeMap<String, NotifierLimit>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig, clock: Arc<dyn Clock>) -> Self {
+        RateLimiter {
+            clock,
+            max_messages: config.max_messages,
+            window: Duration::seconds(config.window_seconds as i64),
+            notifiers: BTreeMap::new(),
+        }
+    }
+
+    // Drop the sends that have left the window, saying whether there's room
+    fn has_room(limit: &mut NotifierLimit, max_messages: usize, cutoff: DateTime<Utc>) -> bool {
+        while limit.sent.front().is_some_and(|at| *at <= cutoff) {
+            limit.sent.pop_front();
+        }
+        limit.sent.len() < max_messages
+    }
+
+    // Whether a message may be sent to the notifier now, recording it if so
+    // but leaving it to the caller to deal with it if not
+    pub fn reserve(&mut self, notifier: &str) -> bool {
+        let now = self.clock.now();
+        let limit = self.notifiers.entry(notifier.to_string()).or_default();
+        let room = RateLimiter::has_room(limit, self.max_messages, now - self.window);
+        if room {
+            limit.sent.push_back(now);
+        }
+        room
+    }
+
+    // Whether a message carrying this many changes may be sent to the
+    // notifier now, recording it if so and counting the changes if not
+    pub fn allow(&mut self, notifier: &str, changes: usize) -> bool {
+        if self.reserve(notifier) {
+            return true;
+        }
+        let limit = self.notifiers.entry(notifier.to_string()).or_default();
+        if limit.suppressed == 0 {
+            warn!(
+                "{} has been sent {} messages in {}s, holding back the rest",
+                notifier,
+                self.max_messages,
+                self.window.num_seconds()
+            );
+        }
+        limit.suppressed += changes;
+        false
+    }
+
+    // The notifiers that had changes held back and now have room again,
+    // with how many changes they missed, which are then forgotten
+    pub fn summaries_due(&mut self) -> Vec<(String, usize)> {
+        let cutoff = self.clock.now() - self.window;
+        let mut due = Vec::new();
+        for (notifier, limit) in &mut self.notifiers {
+            if limit.suppressed > 0 && RateLimiter::has_room(limit, self.max_messages, cutoff) {
+                due.push((notifier.clone(), limit.suppressed));
+                limit.suppressed = 0;
+            }
+        }
+        due
+    }
+
+    pub fn summary(&self, notifier: &str, suppressed: usize, locale: Locale) -> String {
+        locale.fill(
+            Phrase::RateLimited,
+            &[
+                &suppressed,
+                &notifier,
+                &self.max_messages,
+                &format!("{}s", self.window.num_seconds()),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_each_notifier_is_capped_then_told_what_it_missed() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let config = RateLimitConfig {
+            max_messages: 2,
+            window_seconds: 3600,
+        };
+        let mut limiter = RateLimiter::new(&config, clock.clone());
+
+        assert!(limiter.allow("slack", 1));
+        clock.advance(Duration::minutes(10));
+        assert!(limiter.allow("slack", 30));
+        assert!(!limiter.allow("slack", 12));
+        assert!(!limiter.allow("slack", 1));
+        // Others have their own allowance
+        assert!(limiter.allow("irc", 12));
+        assert!(limiter.summaries_due().is_empty());
+
+        // The first send leaves the window, making room for the summary
+        clock.advance(Duration::minutes(50));
+        assert_eq!(limiter.summaries_due(), vec![("slack".to_string(), 13)]);
+        assert_eq!(
+            limiter.summary("slack", 13, Locale::En),
+            "13 further changes were suppressed while slack was at its limit of 2 messages per 3600s, see /state for how things stand"
+        );
+        assert!(limiter.summaries_due().is_empty());
+        assert!(limiter.allow("slack", 1));
+        assert!(!limiter.allow("slack", 1));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::locale::{Locale, Phrase};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+// An outbound registration, typically a trunk's, from
+// `pjsip list registrations`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Registration {
+    pub name: String,
+    pub server_uri: String,
+    // "Registered", "Unregistered", "Rejected" and so on
+    pub status: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct RegistrationsData {
+    pub registrations: Vec<Registration>,
+}
+
+impl RegistrationsData {
+    pub fn hash(&self) -> String {
+        let serialized = serde_json::to_string(self).unwrap();
+        Sha256::digest(serialized)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+// Parse lines like
+// ` voipfone/sip:sip.voipfone.net   voipfone   Registered   (exp. 3581s)`
+// where the auth column may be blank
+pub fn parse_registrations(output: &str) -> RegistrationsData {
+    let mut registrations = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if line.starts_with('<') || line.starts_with('=') || line.starts_with("Objects found:") {
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        let Some((name, server_uri)) = tokens.next().and_then(|token| token.split_once('/')) else {
+            continue;
+        };
+        // The expiry, if there is one, comes after the status
+        let Some(status) = tokens.take_while(|token| !token.starts_with('(')).last() else {
+            continue;
+        };
+        registrations.push(Registration {
+            name: name.to_string(),
+            server_uri: server_uri.to_string(),
+            status: status.to_string(),
+        });
+    }
+    RegistrationsData { registrations }
+}
+
+// A registration whose status changed, or that came or went
+#[derive(Debug, PartialEq)]
+pub struct RegistrationChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl RegistrationChange {
+    // "voipfone registration: Registered -> Rejected"
+    pub fn message(&self, locale: Locale) -> String {
+        let old = self
+            .old
+            .as_deref()
+            .unwrap_or_else(|| locale.text(Phrase::Added));
+        let new = self
+            .new
+            .as_deref()
+            .unwrap_or_else(|| locale.text(Phrase::Removed));
+        locale.fill(Phrase::RegistrationChanged, &[&self.name, &old, &new])
+    }
+}
+
+fn statuses(data: &RegistrationsData) -> BTreeMap<&str, &str> {
+    data.registrations
+        .iter()
+        .map(|registration| (registration.name.as_str(), registration.status.as_str()))
+        .collect()
+}
+
+pub fn diff_registrations(
+    old: &RegistrationsData,
+    new: &RegistrationsData,
+) -> Vec<RegistrationChange> {
+    let (old, new) = (statuses(old), statuses(new));
+    let mut names: Vec<&str> = old.keys().chain(new.keys()).copied().collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .filter(|name| old.get(name) != new.get(name))
+        .map(|name| RegistrationChange {
+            name: name.to_string(),
+            old: old.get(name).map(|status| status.to_string()),
+            new: new.get(name).map(|status| status.to_string()),
+        })
+        .collect()
+}
+
+// Follows registrations from poll to poll, the first reading being the
+// baseline, with its own hash so an unchanged list costs no diff
+#[derive(Default)]
+pub struct RegistrationTracker {
+    last_hash: Option<String>,
+    last: Option<RegistrationsData>,
+}
+
+impl RegistrationTracker {
+    pub fn observe(&mut self, data: RegistrationsData) -> Vec<RegistrationChange> {
+        let hash = data.hash();
+        if self.last_hash.as_deref() == Some(hash.as_str()) {
+            return Vec::new();
+        }
+        let changes = self
+            .last
+            .as_ref()
+            .map(|last| diff_registrations(last, &data))
+            .unwrap_or_default();
+        self.last_hash = Some(hash);
+        self.last = Some(data);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTRATIONS: &str = "
+ <Registration/ServerURI..............................>  <Auth..........>  <Status.......>
+==========================================================================================
+
+ voipfone/sip:sip.voipfone.net                           voipfone          Registered        (exp. 3581s)
+ backup/sip:sip.example.com                                                Unregistered
+
+Objects found: 2
+";
+
+    #[test]
+    fn test_registration_changes_are_tracked() {
+        let data = parse_registrations(REGISTRATIONS);
+        assert_eq!(
+            data.registrations[0],
+            Registration {
+                name: "voipfone".to_string(),
+                server_uri: "sip:sip.voipfone.net".to_string(),
+                status: "Registered".to_string(),
+            }
+        );
+        assert_eq!(data.registrations[1].status, "Unregistered");
+
+        let mut tracker = RegistrationTracker::default();
+        assert!(tracker.observe(data.clone()).is_empty());
+        assert!(tracker.observe(data).is_empty());
+
+        let rejected = REGISTRATIONS
+            .replace("Registered        (exp. 3581s)", "Rejected")
+            .replace(" backup/sip:sip.example.com", "");
+        let changes = tracker.observe(parse_registrations(&rejected));
+        let messages: Vec<String> = changes
+            .iter()
+            .map(|change| change.message(Locale::En))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "backup registration: Unregistered -> removed",
+                "voipfone registration: Registered -> Rejected",
+            ]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use crate::diff::EndpointChange;
+use crate::locale::{Locale, Phrase};
+use crate::Endpoint;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+// Optional [reload_detection] config. A reload makes asterisk briefly list
+// few or no endpoints, so one poll sees them all removed and the next sees
+// them all come back.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ReloadConfig {
+    // Fewer removals than this are always reported straight away
+    #[serde(default = "default_min_endpoints")]
+    pub min_endpoints: usize,
+    // Share of the endpoints that must vanish together, and then share of
+    // those that must return, for it to count as a reload
+    #[serde(default = "default_min_fraction")]
+    pub min_fraction: f64,
+}
+
+fn default_min_endpoints() -> usize {
+    5
+}
+
+fn default_min_fraction() -> f64 {
+    0.8
+}
+
+// What to notify about after a poll
+#[derive(Debug, Default, PartialEq)]
+pub struct ReloadStep {
+    pub changes: Vec<EndpointChange>,
+    // Removals held back to see if they return on the next poll
+    pub held: Vec<EndpointChange>,
+    // Set when held removals returned, with how many came back
+    pub reloaded: Option<usize>,
+}
+
+impl ReloadStep {
+    pub fn pass(changes: &[EndpointChange]) -> Self {
+        ReloadStep {
+            changes: changes.to_vec(),
+            ..ReloadStep::default()
+        }
+    }
+}
+
+pub struct ReloadDetector {
+    config: ReloadConfig,
+    held: Vec<Endpoint>,
+}
+
+impl ReloadDetector {
+    pub fn new(config: &ReloadConfig) -> Self {
+        ReloadDetector {
+            config: config.clone(),
+            held: Vec::new(),
+        }
+    }
+
+    // previous_count is how many endpoints there were before this poll
+    pub fn observe(&mut self, changes: &[EndpointChange], previous_count: usize) -> ReloadStep {
+        let (removed, mut current): (Vec<EndpointChange>, Vec<EndpointChange>) = changes
+            .iter()
+            .cloned()
+            .partition(|change| matches!(change, EndpointChange::Removed(_)));
+
+        // Hold a mass removal back until the next poll shows whether it was a reload
+        let mass_removal = removed.len() >= self.config.min_endpoints
+            && removed.len() as f64 >= self.config.min_fraction * previous_count as f64;
+        let (hold, removed) = if mass_removal {
+            (removed, Vec::new())
+        } else {
+            (Vec::new(), removed)
+        };
+        current.extend(removed);
+
+        let mut step = ReloadStep::default();
+        let previous = std::mem::take(&mut self.held);
+        if !previous.is_empty() {
+            (step.changes, step.reloaded) = self.resolve(previous, &mut current);
+        }
+        step.changes.extend(current);
+
+        self.held = hold
+            .iter()
+            .map(|change| match change {
+                EndpointChange::Removed(endpoint) => endpoint.clone(),
+                _ => unreachable!("only removals are held"),
+            })
+            .collect();
+        step.held = hold;
+        step
+    }
+
+    // Match the held removals up with this poll's additions. Returns the
+    // removals that have to be reported after all, and the reload size if
+    // enough endpoints came back for it to be one. On a reload the returning
+    // additions are dropped from current, or turned into changes where the
+    // endpoint came back different.
+    fn resolve(
+        &self,
+        previous: Vec<Endpoint>,
+        current: &mut Vec<EndpointChange>,
+    ) -> (Vec<EndpointChange>, Option<usize>) {
+        let held: HashMap<&str, &Endpoint> = previous
+            .iter()
+            .map(|endpoint| (endpoint.endpoint.as_str(), endpoint))
+            .collect();
+        let returned: HashSet<String> = current
+            .iter()
+            .filter_map(|change| match change {
+                EndpointChange::Added(endpoint)
+                    if held.contains_key(endpoint.endpoint.as_str()) =>
+                {
+                    Some(endpoint.endpoint.clone())
+                }
+                _ => None,
+            })
+            .collect();
+
+        if (returned.len() as f64) < self.config.min_fraction * previous.len() as f64 {
+            let removals = previous
+                .iter()
+                .cloned()
+                .map(EndpointChange::Removed)
+                .collect();
+            return (removals, None);
+        }
+
+        let mut collapsed = Vec::with_capacity(current.len());
+        for change in current.drain(..) {
+            match change {
+                EndpointChange::Added(new) if returned.contains(&new.endpoint) => {
+                    let old = held[new.endpoint.as_str()];
+                    if *old != new {
+                        collapsed.push(EndpointChange::Changed {
+                            old: old.clone(),
+                            new,
+                        });
+                    }
+                }
+                change => collapsed.push(change),
+            }
+        }
+        *current = collapsed;
+
+        let still_gone = previous
+            .iter()
+            .filter(|endpoint| !returned.contains(&endpoint.endpoint))
+            .cloned()
+            .map(EndpointChange::Removed)
+            .collect();
+        (still_gone, Some(returned.len()))
+    }
+}
+
+pub fn reloaded_message(count: usize, locale: Locale) -> String {
+    locale.fill(Phrase::Reloaded, &[&count])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::diff_endpoints;
+    use crate::endpoint_state::EndpointState;
+    use crate::format::Formatter;
+    use crate::EndpointsData;
+
+    fn data(names: &[&str], state: &str) -> EndpointsData {
+        EndpointsData {
+            endpoints: names
+                .iter()
+                .map(|name| Endpoint {
+                    endpoint: name.to_string(),
+                    state: state.into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn config() -> ReloadConfig {
+        ReloadConfig {
+            min_endpoints: 3,
+            min_fraction: 0.8,
+        }
+    }
+
+    #[test]
+    fn test_full_reload_is_collapsed() {
+        let names = ["500/500", "501/501", "502/502", "Voipfone"];
+        let before = data(&names, "Not in use");
+        let mut after = data(&names, "Not in use");
+        after.endpoints[0].state = EndpointState::Unavailable;
+        let mut detector = ReloadDetector::new(&config());
+
+        // Everything vanishes while asterisk reloads
+        let gone = detector.observe(&diff_endpoints(&before, &EndpointsData::default()), 4);
+        assert!(gone.changes.is_empty());
+        assert_eq!(gone.held.len(), 4);
+        assert_eq!(gone.reloaded, None);
+
+        // ...and comes back, one endpoint in a different state
+        let back = detector.observe(&diff_endpoints(&EndpointsData::default(), &after), 0);
+        assert_eq!(back.reloaded, Some(4));
+        assert_eq!(
+            reloaded_message(4, Locale::En),
+            "Configuration reloaded, 4 endpoints"
+        );
+        assert_eq!(
+            Formatter::Plain.format_events(
+                &back
+                    .changes
+                    .into_iter()
+                    .map(|change| crate::event::AlertEvent::new(
+                        change,
+                        crate::severity::Severity::Info
+                    ))
+                    .collect::<Vec<_>>(),
+                Locale::En
+            ),
+            "Endpoints have changed:\n500/500: Not in use -> Unavailable"
+        );
+    }
+
+    #[test]
+    fn test_removals_that_stay_gone_are_released() {
+        let names = ["500/500", "501/501", "502/502"];
+        let mut detector = ReloadDetector::new(&config());
+
+        detector.observe(
+            &diff_endpoints(&data(&names, "Not in use"), &EndpointsData::default()),
+            3,
+        );
+        let next = detector.observe(&[], 0);
+        assert_eq!(next.reloaded, None);
+        assert_eq!(next.changes.len(), 3);
+        assert!(next
+            .changes
+            .iter()
+            .all(|change| matches!(change, EndpointChange::Removed(_))));
+    }
+
+    #[test]
+    fn test_small_removal_is_not_held() {
+        let before = data(&["500/500", "501/501", "502/502", "503/503"], "Not in use");
+        let after = data(&["500/500", "501/501", "502/502"], "Not in use");
+        let mut detector = ReloadDetector::new(&config());
+
+        let step = detector.observe(&diff_endpoints(&before, &after), 4);
+        assert_eq!(step.changes.len(), 1);
+        assert!(step.held.is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use std::io;
+use std::path::Path;
+
+// Split a capture of repeated `pjsip list endpoints` output into one
+// snapshot per poll. Each listing ends with its "Objects found:" footer.
+pub fn split_snapshots(capture: &str) -> Vec<String> {
+    let mut snapshots = Vec::new();
+    let mut current = String::new();
+    for line in capture.lines() {
+        current.push_str(line);
+        current.push('\n');
+        if line.trim().starts_with("Objects found:") {
+            snapshots.push(std::mem::take(&mut current));
+        }
+    }
+
+    // A capture cut off mid-listing still counts for what it has
+    if current.lines().any(|line| !line.trim().is_empty()) {
+        snapshots.push(current);
+    }
+    snapshots
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<String>> {
+    Ok(split_snapshots(&std::fs::read_to_string(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_snapshots() {
+        let capture = "\
+ Endpoint:  500/500      Not in use    0 of inf
+
+Objects found: 1
+
+ Endpoint:  500/500      Unavailable   0 of inf
+
+Objects found: 1
+ Endpoint:  500/500      Not in use    0 of inf
+";
+
+        let snapshots = split_snapshots(capture);
+        assert_eq!(snapshots.len(), 3);
+        assert!(snapshots[1].contains("Unavailable"));
+        assert!(!snapshots[2].contains("Objects found"));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::storage;
+use crate::EndpointsData;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+// Summary of a run, written to `report_file` when the process exits
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SessionReport {
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    pub exit_reason: Option<String>,
+    pub polls: u64,
+    pub changes_detected: u64,
+    pub notifications_sent: u64,
+    pub notifications_failed: u64,
+    pub final_state: Option<EndpointsData>,
+}
+
+impl SessionReport {
+    pub fn new(started: DateTime<Utc>) -> Self {
+        SessionReport {
+            started,
+            finished: None,
+            exit_reason: None,
+            polls: 0,
+            changes_detected: 0,
+            notifications_sent: 0,
+            notifications_failed: 0,
+            final_state: None,
+        }
+    }
+
+    pub fn record_poll(&mut self, changes: usize) {
+        self.polls += 1;
+        self.changes_detected += changes as u64;
+    }
+
+    // Fill in how the run ended. Delivery counts are (sent, failed).
+    pub fn finish(
+        &mut self,
+        now: DateTime<Utc>,
+        reason: &str,
+        deliveries: (u64, u64),
+        final_state: Option<&EndpointsData>,
+    ) {
+        self.finished = Some(now);
+        self.exit_reason = Some(reason.to_string());
+        (self.notifications_sent, self.notifications_failed) = deliveries;
+        self.final_state = final_state.cloned();
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let serialized = serde_json::to_vec_pretty(self)?;
+        storage::write_file(path, &serialized, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    #[test]
+    fn test_report_after_short_run() {
+        let dir =
+            std::env::temp_dir().join(format!("check-pjsip-state-report-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("report.json");
+
+        let started = Utc::now();
+        let mut report = SessionReport::new(started);
+        report.record_poll(1);
+        report.record_poll(0);
+        report.record_poll(2);
+        let data = EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: "Unavailable".into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        };
+        report.finish(started, "replay finished", (2, 1), Some(&data));
+        report.write(&path).unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["polls"], 3);
+        assert_eq!(written["changes_detected"], 3);
+        assert_eq!(written["notifications_sent"], 2);
+        assert_eq!(written["notifications_failed"], 1);
+        assert_eq!(written["exit_reason"], "replay finished");
+        assert_eq!(
+            written["final_state"]["endpoints"][0]["state"],
+            "Unavailable"
+        );
+    }
+}
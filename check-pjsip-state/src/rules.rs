@@ -0,0 +1,107 @@
+use crate::diff::EndpointChange;
+use crate::filter::glob_to_regex;
+use crate::severity::Severity;
+use regex::Regex;
+use serde::Deserialize;
+
+// A [[rule]] entry, matched against each change's endpoint and new state
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct RuleConfig {
+    // Glob pattern, e.g. "911-*"
+    pub endpoint: String,
+    // Matches any state when not set
+    pub state: Option<String>,
+    // Send only to the notifier with this name, or "none" to not send at all
+    pub notify: Option<String>,
+    // Use this severity instead of the classifier's
+    pub severity: Option<Severity>,
+}
+
+// The rules in order, so the first match wins
+pub struct Rules {
+    rules: Vec<(Regex, RuleConfig)>,
+}
+
+impl Rules {
+    pub fn new(configs: &[RuleConfig]) -> Self {
+        Rules {
+            rules: configs
+                .iter()
+                .map(|config| (glob_to_regex(&config.endpoint), config.clone()))
+                .collect(),
+        }
+    }
+
+    // The first rule matching a change, with its 1-based position
+    pub fn first_match(&self, change: &EndpointChange) -> Option<(usize, &RuleConfig)> {
+        let state = match change {
+            EndpointChange::Removed(_) => "Removed",
+            EndpointChange::Added(endpoint) | EndpointChange::Changed { new: endpoint, .. } => {
+                endpoint.state.as_str()
+            }
+        };
+        self.rules
+            .iter()
+            .position(|(pattern, rule)| {
+                pattern.is_match(change.endpoint())
+                    && rule.state.as_deref().is_none_or(|wanted| wanted == state)
+            })
+            .map(|i| (i + 1, &self.rules[i].1))
+    }
+}
+
+impl RuleConfig {
+    pub fn suppresses(&self) -> bool {
+        self.notify.as_deref() == Some("none")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn change(name: &str, state: &str) -> EndpointChange {
+        EndpointChange::Added(Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        })
+    }
+
+    fn rule(endpoint: &str, state: Option<&str>, notify: &str) -> RuleConfig {
+        RuleConfig {
+            endpoint: endpoint.to_string(),
+            state: state.map(str::to_string),
+            notify: Some(notify.to_string()),
+            severity: None,
+        }
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let rules = Rules::new(&[
+            rule("911-*", Some("Unavailable"), "pagerduty"),
+            rule("911-*", None, "none"),
+        ]);
+
+        let (position, matched) = rules
+            .first_match(&change("911-trunk", "Unavailable"))
+            .unwrap();
+        assert_eq!(position, 1);
+        assert_eq!(matched.notify.as_deref(), Some("pagerduty"));
+
+        let (position, matched) = rules
+            .first_match(&change("911-trunk", "Not in use"))
+            .unwrap();
+        assert_eq!(position, 2);
+        assert!(matched.suppresses());
+
+        assert!(rules
+            .first_match(&change("500/500", "Unavailable"))
+            .is_none());
+    }
+}
@@ -0,0 +1,289 @@
+use crate::filter::endpoint_pattern;
+use crate::locale::{Locale, Phrase};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveTime, Timelike, Utc, Weekday};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+
+// A [[maintenance_windows]] entry: a weekly spell, e.g. Sundays 02:00 to
+// 04:00, when alerts are held back or sent as info. With no days it's
+// every day, for quiet hours. An end before the start runs past midnight.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WindowConfig {
+    pub name: String,
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: NaiveTime,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: NaiveTime,
+    #[serde(default)]
+    pub timezone: WindowTimezone,
+    #[serde(default)]
+    pub action: WindowAction,
+    // Endpoints it covers, all of them if unset
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+}
+
+// "HH:MM", e.g. "02:00"
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&time, "%H:%M")
+        .map_err(|e| serde::de::Error::custom(format!("invalid time {:?}: {}", time, e)))
+}
+
+// What the clock in a window's times is: the host's ("local", following
+// its daylight saving), "utc", or a fixed offset such as "+01:00"
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WindowTimezone {
+    #[default]
+    Local,
+    Utc,
+    Offset(FixedOffset),
+}
+
+impl<'de> Deserialize<'de> for WindowTimezone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let timezone = String::deserialize(deserializer)?;
+        match timezone.to_lowercase().as_str() {
+            "local" => Ok(WindowTimezone::Local),
+            "utc" => Ok(WindowTimezone::Utc),
+            _ => timezone
+                .parse::<FixedOffset>()
+                .map(WindowTimezone::Offset)
+                .map_err(|_| {
+                    serde::de::Error::custom(format!(
+                        "invalid timezone {:?}, expected \"local\", \"utc\" or an offset such as \"+01:00\"",
+                        timezone
+                    ))
+                }),
+        }
+    }
+}
+
+impl WindowTimezone {
+    // The weekday and time of day at that moment
+    fn clock(self, now: DateTime<Utc>) -> (Weekday, NaiveTime) {
+        match self {
+            WindowTimezone::Local => {
+                let now = now.with_timezone(&Local);
+                (now.weekday(), now.time())
+            }
+            WindowTimezone::Utc => (now.weekday(), now.time()),
+            WindowTimezone::Offset(offset) => {
+                let now = now.with_timezone(&offset);
+                (now.weekday(), now.time())
+            }
+        }
+    }
+}
+
+// What happens to alerts while a window is on
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowAction {
+    // Held back, then listed in one message once the window is over
+    #[default]
+    Suppress,
+    // Sent anyway, but as info
+    Downgrade,
+}
+
+struct Window {
+    config: WindowConfig,
+    // Patterns are checked when the config is loaded
+    endpoints: Vec<Regex>,
+}
+
+impl Window {
+    fn is_on(&self, now: DateTime<Utc>) -> bool {
+        let (day, time) = self.config.timezone.clock(now);
+        let time = time.with_second(0).unwrap_or(time);
+        let (start, end) = (self.config.start, self.config.end);
+        let on_day = |day: Weekday| self.config.days.is_empty() || self.config.days.contains(&day);
+        if start <= end {
+            on_day(day) && time >= start && time < end
+        } else {
+            // Past midnight, the window is the previous day's
+            (on_day(day) && time >= start) || (on_day(day.pred()) && time < end)
+        }
+    }
+
+    fn covers(&self, endpoint: &str) -> bool {
+        self.endpoints.is_empty() || self.endpoints.iter().any(|regex| regex.is_match(endpoint))
+    }
+}
+
+// The configured maintenance windows, shared by the policy and the monitor
+#[derive(Default)]
+pub struct MaintenanceWindows {
+    windows: Vec<Window>,
+}
+
+impl MaintenanceWindows {
+    pub fn new(configs: &[WindowConfig]) -> Self {
+        let windows = configs
+            .iter()
+            .map(|config| Window {
+                config: config.clone(),
+                endpoints: config
+                    .endpoints
+                    .iter()
+                    .filter_map(|pattern| endpoint_pattern(pattern).ok())
+                    .collect(),
+            })
+            .collect();
+        MaintenanceWindows { windows }
+    }
+
+    // The first window on for the endpoint, with what to do with its alerts
+    pub fn active(&self, endpoint: &str, now: DateTime<Utc>) -> Option<(&str, WindowAction)> {
+        self.windows
+            .iter()
+            .find(|window| window.covers(endpoint) && window.is_on(now))
+            .map(|window| (window.config.name.as_str(), window.config.action))
+    }
+
+    fn is_on(&self, name: &str, now: DateTime<Utc>) -> bool {
+        self.windows
+            .iter()
+            .any(|window| window.config.name == name && window.is_on(now))
+    }
+}
+
+// The changes held back by each window, for the message when it's over
+#[derive(Default)]
+pub struct WindowSummary {
+    held: BTreeMap<String, Vec<String>>,
+}
+
+impl WindowSummary {
+    pub fn hold(&mut self, window: &str, change: String) {
+        self.held
+            .entry(window.to_string())
+            .or_default()
+            .push(change);
+    }
+
+    // A message for each window that held something back and is now over
+    pub fn over(
+        &mut self,
+        windows: &MaintenanceWindows,
+        now: DateTime<Utc>,
+        locale: Locale,
+    ) -> Vec<String> {
+        let over: Vec<String> = self
+            .held
+            .keys()
+            .filter(|name| !windows.is_on(name, now))
+            .cloned()
+            .collect();
+        over.into_iter()
+            .filter_map(|name| {
+                let changes = self.held.remove(&name)?;
+                Some(format!(
+                    "{}\n{}",
+                    locale.fill(Phrase::WindowOver, &[&name, &changes.len()]),
+                    changes.join("\n")
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[derive(Deserialize)]
+    struct Windows {
+        maintenance_windows: Vec<WindowConfig>,
+    }
+
+    fn windows(toml: &str) -> MaintenanceWindows {
+        let parsed: Windows = toml::from_str(toml).unwrap();
+        MaintenanceWindows::new(&parsed.maintenance_windows)
+    }
+
+    #[test]
+    fn test_windows_by_weekday_time_and_timezone() {
+        let windows = windows(
+            r#"
+            [[maintenance_windows]]
+            name = "sunday"
+            days = ["Sun"]
+            start = "02:00"
+            end = "04:00"
+            timezone = "+01:00"
+            [[maintenance_windows]]
+            name = "nights"
+            start = "22:00"
+            end = "07:00"
+            timezone = "utc"
+            action = "downgrade"
+            endpoints = ["50?/*"]
+            "#,
+        );
+        // 2024-05-05 is a Sunday, so 02:30 at +01:00 is 01:30 UTC
+        let sunday = |h, m| Utc.with_ymd_and_hms(2024, 5, 5, h, m, 0).unwrap();
+        assert_eq!(
+            windows.active("Voipfone", sunday(1, 30)),
+            Some(("sunday", WindowAction::Suppress))
+        );
+        assert_eq!(windows.active("Voipfone", sunday(3, 0)), None);
+        assert_eq!(windows.active("Voipfone", sunday(0, 59)), None);
+        assert_eq!(
+            windows.active("500/500", sunday(5, 0)),
+            Some(("nights", WindowAction::Downgrade))
+        );
+        // Past midnight into Monday, and not on endpoints it doesn't cover
+        let monday = Utc.with_ymd_and_hms(2024, 5, 6, 6, 59, 0).unwrap();
+        assert_eq!(
+            windows.active("501/501", monday),
+            Some(("nights", WindowAction::Downgrade))
+        );
+        assert_eq!(windows.active("Voipfone", monday), None);
+        assert_eq!(
+            windows.active("501/501", monday + chrono::Duration::minutes(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_held_changes_are_listed_once_the_window_is_over() {
+        let windows = windows(
+            r#"
+            [[maintenance_windows]]
+            name = "sunday"
+            days = ["Sunday"]
+            start = "02:00"
+            end = "04:00"
+            timezone = "utc"
+            "#,
+        );
+        let at = |h| Utc.with_ymd_and_hms(2024, 5, 5, h, 0, 0).unwrap();
+        let mut summary = WindowSummary::default();
+        summary.hold("sunday", "500/500: Not in use -> Unavailable".to_string());
+        summary.hold("sunday", "500/500: Unavailable -> Not in use".to_string());
+
+        assert!(summary.over(&windows, at(3), Locale::En).is_empty());
+        assert_eq!(
+            summary.over(&windows, at(4), Locale::En),
+            vec!["Maintenance window sunday is over, 2 changes were held back during it:\n500/500: Not in use -> Unavailable\n500/500: Unavailable -> Not in use"]
+        );
+        assert!(summary.over(&windows, at(5), Locale::En).is_empty());
+    }
+
+    #[test]
+    fn test_bad_times_and_timezones_are_rejected() {
+        for window in [
+            "start = \"2am\"\nend = \"04:00\"",
+            "start = \"02:00\"\nend = \"04:00\"\ntimezone = \"Europe/London\"",
+        ] {
+            let toml = format!("[[maintenance_windows]]\nname = \"w\"\n{}", window);
+            assert!(toml::from_str::<Windows>(&toml).is_err());
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::diff::EndpointChange;
+use crate::EndpointsData;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// How urgent a change is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+// States asterisk reports for endpoints that can't take calls
+pub fn default_unhealthy_states() -> Vec<String> {
+    vec![
+        "Unavailable".to_string(),
+        "Invalid".to_string(),
+        "Unknown".to_string(),
+    ]
+}
+
+// Canonicalises raw asterisk states and decides which of them are healthy
+pub struct StateClassifier {
+    aliases: HashMap<String, String>,
+    unhealthy: HashSet<String>,
+}
+
+impl Default for StateClassifier {
+    fn default() -> Self {
+        StateClassifier::new(&HashMap::new(), &default_unhealthy_states())
+    }
+}
+
+impl StateClassifier {
+    pub fn new(aliases: &HashMap<String, String>, unhealthy_states: &[String]) -> Self {
+        StateClassifier {
+            aliases: aliases.clone(),
+            unhealthy: unhealthy_states.iter().cloned().collect(),
+        }
+    }
+
+    // Map a raw state onto its configured alias, if any
+    pub fn canonical_state<'a>(&'a self, state: &'a str) -> &'a str {
+        self.aliases.get(state).map(String::as_str).unwrap_or(state)
+    }
+
+    // Rewrite every state in a reading to its canonical form before diffing
+    pub fn canonicalize(&self, data: &mut EndpointsData) {
+        for endpoint in &mut data.endpoints {
+            if let Some(alias) = self.aliases.get(endpoint.state.as_str()) {
+                endpoint.state = alias.as_str().into();
+            }
+        }
+    }
+
+    pub fn is_healthy(&self, state: &str) -> bool {
+        !self.unhealthy.contains(self.canonical_state(state))
+    }
+
+    // Losing an endpoint, or it becoming unhealthy, is a warning; anything else is info
+    pub fn classify(&self, change: &EndpointChange) -> Severity {
+        match change {
+            EndpointChange::Removed(_) => Severity::Warning,
+            EndpointChange::Added(endpoint) | EndpointChange::Changed { new: endpoint, .. } => {
+                if self.is_healthy(endpoint.state.as_str()) {
+                    Severity::Info
+                } else {
+                    Severity::Warning
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn endpoint(state: &str) -> Endpoint {
+        Endpoint {
+            endpoint: "500/500".to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_classify_changes() {
+        let classifier = StateClassifier::default();
+        let down = EndpointChange::Changed {
+            old: endpoint("Not in use"),
+            new: endpoint("Unavailable"),
+        };
+        let up = EndpointChange::Changed {
+            old: endpoint("Unavailable"),
+            new: endpoint("Not in use"),
+        };
+
+        assert_eq!(classifier.classify(&down), Severity::Warning);
+        assert_eq!(classifier.classify(&up), Severity::Info);
+        assert_eq!(
+            classifier.classify(&EndpointChange::Removed(endpoint("Not in use"))),
+            Severity::Warning
+        );
+    }
+
+    #[test]
+    fn test_aliased_states_compare_and_classify() {
+        let aliases = HashMap::from([
+            ("Unknown".to_string(), "NoQualify".to_string()),
+            ("".to_string(), "NoQualify".to_string()),
+        ]);
+        let classifier = StateClassifier::new(&aliases, &default_unhealthy_states());
+
+        let mut first = EndpointsData {
+            endpoints: vec![endpoint("Unknown")],
+        };
+        let mut second = EndpointsData {
+            endpoints: vec![endpoint("")],
+        };
+        classifier.canonicalize(&mut first);
+        classifier.canonicalize(&mut second);
+
+        // Both raw states become the same canonical state, so nothing changed
+        assert_eq!(first, second);
+        assert_eq!(first.endpoints[0].state, "NoQualify");
+
+        // "Unknown" would be unhealthy, but its alias is not
+        assert!(classifier.is_healthy("Unknown"));
+        assert_eq!(
+            classifier.classify(&EndpointChange::Added(endpoint("NoQualify"))),
+            Severity::Info
+        );
+        assert!(!classifier.is_healthy("Unavailable"));
+    }
+}
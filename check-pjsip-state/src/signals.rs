@@ -0,0 +1,126 @@
+use crate::config::Config;
+use log::{info, warn};
+use std::io;
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::broadcast;
+
+// What a signal asks of the poll loops
+#[derive(Debug, Clone)]
+pub enum SignalRequest {
+    // SIGTERM or SIGINT: stop once the check under way is done
+    Shutdown(&'static str),
+    // SIGHUP, once the config has been read again and found valid
+    Reload(Arc<Config>),
+}
+
+// One poll loop's view of the signals
+pub struct Signals {
+    requests: broadcast::Receiver<SignalRequest>,
+}
+
+impl Signals {
+    pub fn new(requests: broadcast::Receiver<SignalRequest>) -> Self {
+        Signals { requests }
+    }
+
+    // Wait for the next request, dropping the oldest if many came while busy
+    pub async fn next(&mut self) -> SignalRequest {
+        loop {
+            match self.requests.recv().await {
+                Ok(request) => return request,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+            }
+        }
+    }
+
+    // The requests that have arrived, without waiting for more
+    pub fn received(&mut self) -> Vec<SignalRequest> {
+        let mut received = Vec::new();
+        loop {
+            match self.requests.try_recv() {
+                Ok(request) => received.push(request),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => return received,
+            }
+        }
+    }
+}
+
+// Turn SIGTERM, SIGINT and SIGHUP into requests for every poll loop to
+// subscribe to. reload reads the config again, or says why it couldn't.
+// A second SIGTERM or SIGINT exits at once, in case a check is stuck.
+pub fn listen<F>(reload: F) -> io::Result<broadcast::Sender<SignalRequest>>
+where
+    F: Fn() -> Result<Config, String> + Send + 'static,
+{
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    let (sender, _) = broadcast::channel(16);
+    let requests = sender.clone();
+    tokio::spawn(async move {
+        let mut stopping = false;
+        loop {
+            let request = tokio::select! {
+                _ = terminate.recv() => SignalRequest::Shutdown("terminated"),
+                _ = interrupt.recv() => SignalRequest::Shutdown("interrupted"),
+                _ = hangup.recv() => match reload() {
+                    Ok(config) => {
+                        info!("Reloaded the config");
+                        SignalRequest::Reload(Arc::new(config))
+                    }
+                    Err(e) => {
+                        warn!("Keeping the running config, as the new one failed to load: {}", e);
+                        continue;
+                    }
+                },
+            };
+            if let SignalRequest::Shutdown(reason) = request {
+                if stopping {
+                    warn!("Exiting now, {} again", reason);
+                    std::process::exit(1);
+                }
+                info!("Stopping after the check under way, {}", reason);
+                stopping = true;
+            }
+            // Nothing is listening once every poll loop has stopped
+            let _ = requests.send(request);
+        }
+    });
+    Ok(sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_every_loop_hears_each_request() {
+        let (sender, _) = broadcast::channel(2);
+        let mut first = Signals::new(sender.subscribe());
+        let mut second = Signals::new(sender.subscribe());
+
+        for _ in 0..3 {
+            sender.send(SignalRequest::Shutdown("interrupted")).unwrap();
+        }
+        sender.send(SignalRequest::Shutdown("terminated")).unwrap();
+
+        // Those missed while busy are skipped, but the latest still arrive
+        assert!(matches!(
+            first.next().await,
+            SignalRequest::Shutdown("interrupted")
+        ));
+        assert!(matches!(
+            first.next().await,
+            SignalRequest::Shutdown("terminated")
+        ));
+        let received = second.received();
+        assert!(matches!(
+            received.last(),
+            Some(SignalRequest::Shutdown("terminated"))
+        ));
+        assert!(second.received().is_empty());
+    }
+}
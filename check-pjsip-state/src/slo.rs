@@ -0,0 +1,197 @@
+use crate::downtime::DurationFormat;
+use crate::locale::{Locale, Phrase};
+use crate::severity::StateClassifier;
+use crate::EndpointsData;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+// Optional [slo] config for alerting on per-endpoint availability
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SloConfig {
+    // Availability is measured over this much trailing time
+    #[serde(default = "default_window_seconds")]
+    pub window_seconds: u64,
+    // e.g. 99.9
+    pub target_percent: f64,
+}
+
+fn default_window_seconds() -> u64 {
+    30 * 86400
+}
+
+// A change in whether an endpoint is meeting its availability target
+#[derive(Debug, Clone, PartialEq)]
+pub enum SloAlert {
+    Breached { endpoint: String, availability: f64 },
+    Recovered { endpoint: String, availability: f64 },
+}
+
+impl SloAlert {
+    pub fn message(&self, config: &SloConfig, format: DurationFormat, locale: Locale) -> String {
+        let window = format.format(Duration::seconds(config.window_seconds as i64));
+        let (phrase, endpoint, availability) = match self {
+            SloAlert::Breached {
+                endpoint,
+                availability,
+            } => (Phrase::SloBreached, endpoint, availability),
+            SloAlert::Recovered {
+                endpoint,
+                availability,
+            } => (Phrase::SloRecovered, endpoint, availability),
+        };
+        locale.fill(
+            phrase,
+            &[
+                endpoint,
+                &format!("{:.3}%", availability),
+                &window,
+                &format!("{}%", config.target_percent),
+            ],
+        )
+    }
+}
+
+// Time-weighted availability per endpoint over a sliding window. Each
+// observation is taken to hold until the next one.
+pub struct SloTracker {
+    window: Duration,
+    target_percent: f64,
+    // Endpoint -> (when it was seen, whether it was healthy), oldest first
+    samples: BTreeMap<String, VecDeque<(DateTime<Utc>, bool)>>,
+    breached: HashSet<String>,
+}
+
+impl SloTracker {
+    pub fn new(config: &SloConfig) -> Self {
+        SloTracker {
+            window: Duration::seconds(config.window_seconds as i64),
+            target_percent: config.target_percent,
+            samples: BTreeMap::new(),
+            breached: HashSet::new(),
+        }
+    }
+
+    // Record a poll, returning the endpoints that have just fallen below or
+    // climbed back above the target
+    pub fn observe(
+        &mut self,
+        data: &EndpointsData,
+        classifier: &StateClassifier,
+        now: DateTime<Utc>,
+    ) -> Vec<SloAlert> {
+        let start = now - self.window;
+        for endpoint in &data.endpoints {
+            let samples = self.samples.entry(endpoint.endpoint.clone()).or_default();
+            samples.push_back((now, classifier.is_healthy(endpoint.state.as_str())));
+            // Keep the last sample before the window, it covers its start
+            while samples.len() > 1 && samples[1].0 <= start {
+                samples.pop_front();
+            }
+        }
+
+        let mut alerts = Vec::new();
+        for endpoint in &data.endpoints {
+            let name = &endpoint.endpoint;
+            let Some(availability) = self.availability(name, now) else {
+                continue;
+            };
+            let below = availability < self.target_percent;
+            if below && self.breached.insert(name.clone()) {
+                alerts.push(SloAlert::Breached {
+                    endpoint: name.clone(),
+                    availability,
+                });
+            } else if !below && self.breached.remove(name) {
+                alerts.push(SloAlert::Recovered {
+                    endpoint: name.clone(),
+                    availability,
+                });
+            }
+        }
+        alerts
+    }
+
+    // Percentage of the observed part of the window the endpoint was
+    // healthy for, None until there's any time to measure
+    pub fn availability(&self, endpoint: &str, now: DateTime<Utc>) -> Option<f64> {
+        let samples = self.samples.get(endpoint)?;
+        let start = now - self.window;
+        let mut healthy = Duration::zero();
+        let mut total = Duration::zero();
+        for (i, (seen, is_healthy)) in samples.iter().enumerate() {
+            let until = samples.get(i + 1).map_or(now, |(next, _)| *next);
+            let from = (*seen).max(start);
+            if until <= from {
+                continue;
+            }
+            total += until - from;
+            if *is_healthy {
+                healthy += until - from;
+            }
+        }
+
+        (total > Duration::zero())
+            .then(|| healthy.num_milliseconds() as f64 * 100.0 / total.num_milliseconds() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn data(state: &str) -> EndpointsData {
+        EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: state.into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_windowed_availability_breaches_and_recovers() {
+        let config = SloConfig {
+            window_seconds: 1000,
+            target_percent: 99.0,
+        };
+        let classifier = StateClassifier::default();
+        let mut tracker = SloTracker::new(&config);
+        let start = Utc::now();
+        let at = |seconds: i64| start + Duration::seconds(seconds);
+
+        assert!(tracker
+            .observe(&data("Not in use"), &classifier, at(0))
+            .is_empty());
+        assert!(tracker
+            .observe(&data("Not in use"), &classifier, at(500))
+            .is_empty());
+        assert_eq!(tracker.availability("500/500", at(500)), Some(100.0));
+
+        // Down for 20s of the 1000s so far: 98%
+        assert!(tracker
+            .observe(&data("Unavailable"), &classifier, at(980))
+            .is_empty());
+        assert_eq!(
+            tracker.observe(&data("Not in use"), &classifier, at(1000)),
+            vec![SloAlert::Breached {
+                endpoint: "500/500".to_string(),
+                availability: 98.0,
+            }]
+        );
+
+        // Once the outage slides out of the window, it recovers
+        let alerts = tracker.observe(&data("Not in use"), &classifier, at(1990));
+        assert_eq!(tracker.availability("500/500", at(1990)), Some(99.0));
+        assert!(matches!(alerts[..], [SloAlert::Recovered { .. }]));
+        assert_eq!(
+            alerts[0].message(&config, DurationFormat::Humanized, Locale::En),
+            "500/500 is back within its SLO: 99.000% available over 16m 40s (target 99%)"
+        );
+    }
+}
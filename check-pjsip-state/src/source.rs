@@ -0,0 +1,187 @@
+use crate::ami::AmiConfig;
+use crate::asterisk::{default_asterisk_binary, default_asterisk_command, Asterisk};
+use log::warn;
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+// Where poll output comes from
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    // Run `pjsip list endpoints` through asterisk
+    #[default]
+    #[serde(alias = "cli")]
+    Asterisk,
+    // Ask the Asterisk Manager Interface in [ami] instead, from any host
+    Ami,
+    // Read successive dumps from stdin, stopping at the end of input
+    Stdin,
+    // Read successive dumps from a FIFO, reopening it whenever a writer
+    // closes it
+    Pipe(PathBuf),
+}
+
+// One of several asterisks polled side by side from [[sources]], each on
+// its own schedule so a slow one doesn't hold up the rest
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SourceConfig {
+    pub name: String,
+    // Seconds between polls, sleep_time_seconds if unset
+    pub interval_seconds: Option<u64>,
+    #[serde(default = "default_asterisk_binary")]
+    pub asterisk_binary: String,
+    #[serde(default = "default_asterisk_command")]
+    pub asterisk_command: Vec<String>,
+    // Poll this one over the manager interface instead of the CLI
+    pub ami: Option<AmiConfig>,
+}
+
+impl SourceConfig {
+    pub fn asterisk(&self) -> Asterisk {
+        Asterisk::new(&self.asterisk_binary, &self.asterisk_command)
+    }
+}
+
+// A message as sent on behalf of a named source, e.g.
+// "[voip1] 500/500: Not in use -> Unavailable"
+pub fn tagged(name: Option<&str>, message: &str) -> String {
+    match name {
+        Some(name) => format!("[{}] {}", name, message),
+        None => message.to_string(),
+    }
+}
+
+// A per-source file alongside a configured one, e.g. state-voip1.json
+// for the voip1 source of state.json
+pub fn named_path(path: &Path, name: &str) -> PathBuf {
+    let mut file_name = path.file_stem().map(OsString::from).unwrap_or_default();
+    file_name.push(format!("-{}", name));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+// Splits a stream into dumps of `pjsip list endpoints` output, each ending
+// with its "Objects found:" footer
+pub struct DumpReader<R> {
+    reader: R,
+}
+
+impl<R: BufRead> DumpReader<R> {
+    pub fn new(reader: R) -> Self {
+        DumpReader { reader }
+    }
+
+    // The next dump, or None once the input is exhausted
+    pub fn next_dump(&mut self) -> io::Result<Option<String>> {
+        let mut dump = String::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                // Whatever was left before the end still counts
+                let has_content = dump.lines().any(|line| !line.trim().is_empty());
+                return Ok(has_content.then_some(dump));
+            }
+            dump.push_str(&line);
+            if line.trim().starts_with("Objects found:") {
+                return Ok(Some(dump));
+            }
+        }
+    }
+}
+
+// Read dumps on a thread of their own, since the reads block, and hand
+// them over one per poll. None when polls should come from asterisk.
+pub fn spawn(source: &Source) -> Option<mpsc::Receiver<String>> {
+    let (sender, receiver) = mpsc::channel(1);
+    match source.clone() {
+        Source::Asterisk | Source::Ami => return None,
+        Source::Stdin => {
+            std::thread::spawn(move || {
+                forward(DumpReader::new(io::stdin().lock()), &sender);
+            });
+        }
+        Source::Pipe(path) => {
+            std::thread::spawn(move || loop {
+                match File::open(&path) {
+                    Ok(file) => {
+                        if !forward(DumpReader::new(BufReader::new(file)), &sender) {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to open {}: {}", path.display(), e);
+                        return;
+                    }
+                }
+            });
+        }
+    }
+    Some(receiver)
+}
+
+// Send every dump from the reader, returning false once nobody's listening
+fn forward<R: BufRead>(mut reader: DumpReader<R>, sender: &mpsc::Sender<String>) -> bool {
+    loop {
+        match reader.next_dump() {
+            Ok(Some(dump)) => {
+                if sender.blocking_send(dump).is_err() {
+                    return false;
+                }
+            }
+            Ok(None) => return true,
+            Err(e) => {
+                warn!("Failed to read poll output: {}", e);
+                return true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_pjsip_endpoints;
+
+    #[test]
+    fn test_named_path() {
+        assert_eq!(
+            named_path(Path::new("/var/lib/check-pjsip-state/state.json"), "voip1"),
+            PathBuf::from("/var/lib/check-pjsip-state/state-voip1.json")
+        );
+        assert_eq!(
+            named_path(Path::new("report"), "voip1"),
+            PathBuf::from("report-voip1")
+        );
+    }
+
+    #[test]
+    fn test_delimited_dumps_are_separate_polls() {
+        let input = "\
+ Endpoint:  500/500      Not in use    0 of inf
+ Endpoint:  502/502      Not in use    0 of inf
+
+Objects found: 2
+
+ Endpoint:  500/500      Unavailable   0 of inf
+
+Objects found: 1
+";
+        let mut reader = DumpReader::new(io::Cursor::new(input));
+
+        let mut polls = Vec::new();
+        while let Some(dump) = reader.next_dump().unwrap() {
+            polls.push(get_pjsip_endpoints(&dump));
+        }
+        assert_eq!(polls.len(), 2);
+        assert_eq!(polls[0].endpoints.len(), 2);
+        assert_eq!(polls[1].endpoints[0].state, "Unavailable");
+    }
+}
@@ -0,0 +1,236 @@
+use crate::locale::{Locale, Phrase};
+use crate::severity::StateClassifier;
+use crate::storage;
+use crate::EndpointsData;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// What is remembered between runs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PersistedState {
+    pub hash: String,
+    pub data: EndpointsData,
+}
+
+pub fn save(path: &Path, state: &PersistedState, compress: bool) -> io::Result<()> {
+    let serialized = serde_json::to_vec(state)?;
+    storage::write_file(path, &serialized, compress)
+}
+
+// Whether state can be saved at path, creating the directory it goes in
+// if need be. Checked by writing where save writes before renaming.
+pub fn check_writable(path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut probe = path.as_os_str().to_owned();
+    probe.push(".tmp");
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&probe)?;
+    std::fs::remove_file(&probe)
+}
+
+// Load the previous state, treating a missing or unreadable file as no
+// state, and so is one last written longer than max_age ago
+pub fn load(path: &Path, max_age: Option<Duration>) -> Option<PersistedState> {
+    if let Some(max_age) = max_age {
+        let age = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        if let Some(age) = age.filter(|age| *age > max_age) {
+            info!(
+                "Discarding stale state file {}, last written {}s ago",
+                path.display(),
+                age.as_secs()
+            );
+            return None;
+        }
+    }
+
+    let contents = match storage::read_file(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Failed to read state file {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    match serde_json::from_slice::<PersistedState>(&contents) {
+        Ok(mut state) => {
+            // Older state files only have the raw channels column
+            for endpoint in &mut state.data.endpoints {
+                if let Some((active, limit)) = crate::parse_channels(&endpoint.channels) {
+                    endpoint.active_channels = active;
+                    endpoint.channel_limit = limit;
+                }
+            }
+            Some(state)
+        }
+        Err(e) => {
+            warn!("Ignoring corrupt state file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Endpoints that were unhealthy in the persisted state and still are now
+pub fn still_unhealthy(
+    previous: &EndpointsData,
+    current: &EndpointsData,
+    classifier: &StateClassifier,
+) -> Vec<String> {
+    current
+        .endpoints
+        .iter()
+        .filter(|endpoint| !classifier.is_healthy(endpoint.state.as_str()))
+        .filter(|endpoint| {
+            previous.endpoints.iter().any(|prev| {
+                prev.endpoint == endpoint.endpoint && !classifier.is_healthy(prev.state.as_str())
+            })
+        })
+        .map(|endpoint| format!("{} ({})", endpoint.endpoint, endpoint.state))
+        .collect()
+}
+
+pub fn still_unhealthy_message(
+    endpoints: &[String],
+    max_listed: Option<usize>,
+    locale: Locale,
+) -> String {
+    locale.fill(Phrase::StillDown, &[&locale.list(endpoints, max_listed)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn state() -> PersistedState {
+        let data = EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: "Not in use".into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        };
+        PersistedState {
+            hash: crate::calculate_hash(&data),
+            data,
+        }
+    }
+
+    fn state_path(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("check-pjsip-state-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn test_compressed_state_round_trip() {
+        let path = state_path("compressed.json");
+        save(&path, &state(), true).unwrap();
+        assert_eq!(load(&path, None), Some(state()));
+    }
+
+    #[test]
+    fn test_stale_state_is_discarded() {
+        let max_age = Some(Duration::from_secs(3600));
+        let fresh = state_path("fresh.json");
+        save(&fresh, &state(), false).unwrap();
+        assert_eq!(load(&fresh, max_age), Some(state()));
+
+        let stale = state_path("stale.json");
+        save(&stale, &state(), false).unwrap();
+        let two_hours_ago = SystemTime::now() - Duration::from_secs(7200);
+        std::fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(two_hours_ago)
+            .unwrap();
+        assert_eq!(load(&stale, max_age), None);
+        // Without a limit, any age will do
+        assert_eq!(load(&stale, None), Some(state()));
+    }
+
+    #[test]
+    fn test_channel_counts_filled_in_for_older_state() {
+        let path = state_path("older.json");
+        std::fs::write(
+            &path,
+            r#"{"hash":"abc","data":{"endpoints":[
+                {"endpoint":"500/500","state":"In use","channels":"2 of 10"}
+            ]}}"#,
+        )
+        .unwrap();
+        let endpoint = &load(&path, None).unwrap().data.endpoints[0];
+        assert_eq!(endpoint.active_channels, 2);
+        assert_eq!(endpoint.channel_limit, Some(10));
+    }
+
+    fn endpoint(name: &str, state: &str) -> Endpoint {
+        Endpoint {
+            endpoint: name.to_string(),
+            state: state.into(),
+            channels: "0 of inf".to_string(),
+            active_channels: 0,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_still_unhealthy_after_restart() {
+        let persisted = EndpointsData {
+            endpoints: vec![
+                endpoint("500/500", "Unavailable"),
+                endpoint("501/501", "Unavailable"),
+                endpoint("502/502", "Not in use"),
+            ],
+        };
+        let first_poll = EndpointsData {
+            endpoints: vec![
+                endpoint("500/500", "Unavailable"),
+                endpoint("501/501", "Not in use"),
+                endpoint("502/502", "Unavailable"),
+            ],
+        };
+
+        // Only 500/500 was down before the restart and is still down
+        let still_down = still_unhealthy(&persisted, &first_poll, &StateClassifier::default());
+        assert_eq!(still_down, vec!["500/500 (Unavailable)".to_string()]);
+        assert_eq!(
+            still_unhealthy_message(&still_down, None, Locale::En),
+            "Still down since before restart: 500/500 (Unavailable)"
+        );
+    }
+
+    #[test]
+    fn test_missing_state_file() {
+        assert_eq!(load(&state_path("missing.json"), None), None);
+    }
+
+    #[test]
+    fn test_state_file_writability() {
+        let path = state_path("new/state.json");
+        assert!(check_writable(&path).is_ok());
+        assert!(!path.exists());
+
+        // Nowhere to put it when its directory would have to be a file
+        let blocked = state_path("blocked");
+        std::fs::write(&blocked, b"").unwrap();
+        assert!(check_writable(&blocked.join("state.json")).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+// Every gzip stream starts with these two bytes
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Write a file atomically, gzipping it if asked to
+pub fn write_file(path: &Path, contents: &[u8], compress: bool) -> io::Result<()> {
+    let bytes = if compress {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents)?;
+        encoder.finish()?
+    } else {
+        contents.to_vec()
+    };
+
+    // Write alongside and rename so a crash never leaves a partial file
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    fs::write(&tmp_name, bytes)?;
+    fs::rename(&tmp_name, path)
+}
+
+// Read a file, transparently decompressing it if it is gzipped
+pub fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let bytes = fs::read(path)?;
+    if !bytes.starts_with(&GZIP_MAGIC) {
+        return Ok(bytes);
+    }
+
+    let mut contents = Vec::new();
+    GzDecoder::new(bytes.as_slice()).read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("check-pjsip-state-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let path = test_dir("storage").join("compressed.json");
+        write_file(&path, b"{\"hello\":\"world\"}", true).unwrap();
+
+        assert!(fs::read(&path).unwrap().starts_with(&GZIP_MAGIC));
+        assert_eq!(read_file(&path).unwrap(), b"{\"hello\":\"world\"}");
+    }
+
+    #[test]
+    fn test_plain_file_still_loads() {
+        let path = test_dir("storage").join("plain.json");
+        write_file(&path, b"{\"hello\":\"world\"}", false).unwrap();
+
+        assert_eq!(read_file(&path).unwrap(), b"{\"hello\":\"world\"}");
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{Endpoint, EndpointsData};
+use std::collections::HashMap;
+
+// Holds back per-endpoint changes until they've been seen on enough
+// consecutive polls, so blips that revert never count as changes
+pub struct Sustainer {
+    polls: u32,
+    // Endpoint -> the value it's heading for (None if removed), and how many
+    // polls in a row it's been seen
+    pending: HashMap<String, (Option<Endpoint>, u32)>,
+}
+
+impl Sustainer {
+    pub fn new(polls: u32) -> Self {
+        Sustainer {
+            polls,
+            pending: HashMap::new(),
+        }
+    }
+
+    // The reading as far as sustained changes go: endpoints whose new value
+    // hasn't held for long enough keep their committed value
+    pub fn apply(&mut self, committed: &EndpointsData, reading: &EndpointsData) -> EndpointsData {
+        let find = |data: &EndpointsData, name: &str| -> Option<Endpoint> {
+            data.endpoints
+                .iter()
+                .find(|endpoint| endpoint.endpoint == name)
+                .cloned()
+        };
+
+        let mut endpoints = Vec::with_capacity(reading.endpoints.len());
+        let mut seen = Vec::new();
+        for endpoint in &reading.endpoints {
+            let old = find(committed, &endpoint.endpoint);
+            if self.sustained(&endpoint.endpoint, old.as_ref(), Some(endpoint)) {
+                endpoints.push(endpoint.clone());
+            } else if let Some(old) = old {
+                endpoints.push(old);
+            }
+            seen.push(endpoint.endpoint.clone());
+        }
+
+        for old in &committed.endpoints {
+            if find(reading, &old.endpoint).is_none()
+                && !self.sustained(&old.endpoint, Some(old), None)
+            {
+                endpoints.push(old.clone());
+                seen.push(old.endpoint.clone());
+            }
+        }
+
+        // Anything neither read nor committed has nothing left to wait for
+        self.pending.retain(|name, _| seen.contains(name));
+        EndpointsData { endpoints }
+    }
+
+    // Whether the endpoint's new value should be committed
+    fn sustained(&mut self, name: &str, old: Option<&Endpoint>, new: Option<&Endpoint>) -> bool {
+        if old == new {
+            self.pending.remove(name);
+            return true;
+        }
+
+        let (value, count) = self
+            .pending
+            .entry(name.to_string())
+            .or_insert_with(|| (new.cloned(), 0));
+        if value.as_ref() != new {
+            *value = new.cloned();
+            *count = 0;
+        }
+        *count += 1;
+
+        if *count >= self.polls {
+            self.pending.remove(name);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(state: &str) -> EndpointsData {
+        EndpointsData {
+            endpoints: vec![Endpoint {
+                endpoint: "500/500".to_string(),
+                state: state.into(),
+                channels: "0 of inf".to_string(),
+                active_channels: 0,
+                channel_limit: None,
+                extra: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_blip_reverting_before_sustained_is_ignored() {
+        let mut sustainer = Sustainer::new(3);
+        let committed = data("Not in use");
+
+        assert_eq!(sustainer.apply(&committed, &data("Unavailable")), committed);
+        assert_eq!(sustainer.apply(&committed, &data("Unavailable")), committed);
+        assert_eq!(sustainer.apply(&committed, &data("Not in use")), committed);
+        // The count starts again after reverting
+        assert_eq!(sustainer.apply(&committed, &data("Unavailable")), committed);
+    }
+
+    #[test]
+    fn test_change_sustained_for_enough_polls_is_committed() {
+        let mut sustainer = Sustainer::new(3);
+        let committed = data("Not in use");
+
+        assert_eq!(sustainer.apply(&committed, &data("Unavailable")), committed);
+        assert_eq!(sustainer.apply(&committed, &data("Unavailable")), committed);
+        assert_eq!(
+            sustainer.apply(&committed, &data("Unavailable")),
+            data("Unavailable")
+        );
+    }
+
+    #[test]
+    fn test_removal_must_be_sustained_too() {
+        let mut sustainer = Sustainer::new(2);
+        let committed = data("Not in use");
+        let empty = EndpointsData::default();
+
+        assert_eq!(sustainer.apply(&committed, &empty), committed);
+        assert_eq!(sustainer.apply(&committed, &empty), empty);
+    }
+}
@@ -0,0 +1,202 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::sync::Mutex;
+
+// What the latest good reading from a source found
+struct Reading {
+    endpoints: usize,
+    unhealthy: usize,
+}
+
+#[derive(Default)]
+struct NotifyState {
+    ready: bool,
+    // Sources that have had a good reading since starting
+    collected: HashSet<String>,
+    // Sources whose loop has come round since the last WATCHDOG=1
+    beats: HashSet<String>,
+    readings: BTreeMap<String, Reading>,
+    last_change: Option<DateTime<Utc>>,
+}
+
+// Tells systemd how a Type=notify unit is doing over $NOTIFY_SOCKET: ready
+// once every source has been read, alive while every poll loop keeps coming
+// round (so WatchdogSec= wants to be longer than a poll interval plus a
+// slow check), and a STATUS= line systemctl status shows
+pub struct Systemd {
+    socket: UnixDatagram,
+    sources: usize,
+    state: Mutex<NotifyState>,
+}
+
+impl Systemd {
+    // None unless systemd started us and asked to be told
+    pub fn from_env(sources: usize) -> Option<Systemd> {
+        let address = std::env::var("NOTIFY_SOCKET").ok()?;
+        match Systemd::connect(&address, sources) {
+            Ok(systemd) => {
+                info!("Notifying systemd at {}", address);
+                Some(systemd)
+            }
+            Err(e) => {
+                warn!("Failed to connect to systemd at {}: {}", address, e);
+                None
+            }
+        }
+    }
+
+    // A path, or an abstract socket name after "@"
+    fn connect(address: &str, sources: usize) -> io::Result<Systemd> {
+        let socket = UnixDatagram::unbound()?;
+        match address.strip_prefix('@') {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                socket.connect_addr(&address)?;
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "abstract sockets are only on Linux",
+                ))
+            }
+            None => socket.connect(address)?,
+        }
+        Ok(Systemd {
+            socket,
+            sources: sources.max(1),
+            state: Mutex::new(NotifyState::default()),
+        })
+    }
+
+    fn send(&self, message: &str) {
+        debug!("Telling systemd {:?}", message);
+        if let Err(e) = self.socket.send(message.as_bytes()) {
+            warn!("Failed to notify systemd: {}", e);
+        }
+    }
+
+    // A source's poll loop came round, whether or not its check worked
+    pub fn heartbeat(&self, source: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.beats.insert(source.to_string());
+        if state.beats.len() >= self.sources {
+            state.beats.clear();
+            drop(state);
+            self.send("WATCHDOG=1");
+        }
+    }
+
+    // A source was read, with what it found and whether anything changed
+    pub fn collected(
+        &self,
+        source: &str,
+        endpoints: usize,
+        unhealthy: usize,
+        changed_at: Option<DateTime<Utc>>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        state.readings.insert(
+            source.to_string(),
+            Reading {
+                endpoints,
+                unhealthy,
+            },
+        );
+        if changed_at.is_some() {
+            state.last_change = changed_at;
+        }
+        state.collected.insert(source.to_string());
+        let status = format!("STATUS={}", status_line(&state));
+        let message = if !state.ready && state.collected.len() >= self.sources {
+            state.ready = true;
+            format!("READY=1\n{}", status)
+        } else {
+            status
+        };
+        drop(state);
+        self.send(&message);
+    }
+
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+}
+
+// "24 endpoints, 2 unavailable, last change 2024-05-05T02:30:00Z"
+fn status_line(state: &NotifyState) -> String {
+    let endpoints: usize = state
+        .readings
+        .values()
+        .map(|reading| reading.endpoints)
+        .sum();
+    let unhealthy: usize = state
+        .readings
+        .values()
+        .map(|reading| reading.unhealthy)
+        .sum();
+    let last_change = state.last_change.map_or_else(
+        || "no changes yet".to_string(),
+        |at| {
+            format!(
+                "last change {}",
+                at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )
+        },
+    );
+    format!(
+        "{} endpoints, {} unavailable, {}",
+        endpoints, unhealthy, last_change
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_ready_and_alive_once_every_source_is() {
+        let path =
+            std::env::temp_dir().join(format!("check-pjsip-state-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixDatagram::bind(&path).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let received = || {
+            let mut buffer = [0; 256];
+            let mut messages = Vec::new();
+            while let Ok(size) = listener.recv(&mut buffer) {
+                messages.push(String::from_utf8_lossy(&buffer[..size]).to_string());
+            }
+            messages
+        };
+        let systemd = Systemd::connect(path.to_str().unwrap(), 2).unwrap();
+
+        systemd.collected("pbx1", 20, 1, None);
+        systemd.heartbeat("pbx1");
+        systemd.heartbeat("pbx1");
+        assert_eq!(
+            received(),
+            vec!["STATUS=20 endpoints, 1 unavailable, no changes yet"]
+        );
+
+        let changed = Utc.with_ymd_and_hms(2024, 5, 5, 2, 30, 0).unwrap();
+        systemd.collected("pbx2", 4, 0, Some(changed));
+        systemd.heartbeat("pbx2");
+        systemd.collected("pbx2", 4, 1, None);
+        assert_eq!(
+            received(),
+            vec![
+                "READY=1\nSTATUS=24 endpoints, 1 unavailable, last change 2024-05-05T02:30:00Z",
+                "WATCHDOG=1",
+                "STATUS=24 endpoints, 2 unavailable, last change 2024-05-05T02:30:00Z",
+            ]
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,143 @@
+use log::warn;
+use opentelemetry::trace::{Span, Tracer, TracerProvider};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{ExporterBuildError, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use serde::Deserialize;
+use std::time::Instant;
+
+// Optional [otel] config for exporting a span per poll over OTLP/HTTP
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct OtelConfig {
+    // Collector traces endpoint, e.g. "http://localhost:4318/v1/traces"
+    pub endpoint: String,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+fn default_service_name() -> String {
+    "check-pjsip-state".to_string()
+}
+
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+    tracer: SdkTracer,
+}
+
+impl Telemetry {
+    pub fn new(config: &OtelConfig) -> Result<Self, ExporterBuildError> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(&config.endpoint)
+            .build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(config.service_name.clone())
+                    .build(),
+            )
+            .with_batch_exporter(exporter)
+            .build();
+
+        Ok(Telemetry::with_provider(provider))
+    }
+
+    fn with_provider(provider: SdkTracerProvider) -> Self {
+        let tracer = provider.tracer("check-pjsip-state");
+        Telemetry { provider, tracer }
+    }
+
+    pub fn start_poll(&self) -> PollSpan {
+        PollSpan {
+            span: Some(self.tracer.start("poll")),
+            stage_started: Instant::now(),
+        }
+    }
+
+    // Export anything still buffered
+    pub fn shutdown(&self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!("Failed to shut down telemetry: {}", e);
+        }
+    }
+}
+
+// The span covering one poll, from running the command through to notifying
+pub struct PollSpan {
+    span: Option<opentelemetry_sdk::trace::Span>,
+    stage_started: Instant,
+}
+
+impl PollSpan {
+    // Stands in when telemetry isn't configured
+    pub fn disabled() -> Self {
+        PollSpan {
+            span: None,
+            stage_started: Instant::now(),
+        }
+    }
+
+    // Close off the stage that just finished, recording how long it took
+    pub fn stage(&mut self, name: &str) {
+        let elapsed = self.stage_started.elapsed();
+        self.stage_started = Instant::now();
+        if let Some(span) = self.span.as_mut() {
+            span.set_attribute(KeyValue::new(
+                format!("stage.{}.ms", name),
+                elapsed.as_secs_f64() * 1000.0,
+            ));
+        }
+    }
+
+    pub fn count(&mut self, name: &'static str, value: usize) {
+        if let Some(span) = self.span.as_mut() {
+            span.set_attribute(KeyValue::new(name, value as i64));
+        }
+    }
+
+    pub fn end(self) {
+        if let Some(mut span) = self.span {
+            span.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::Value;
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+
+    #[test]
+    fn test_poll_produces_span() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let telemetry = Telemetry::with_provider(provider);
+
+        let mut span = telemetry.start_poll();
+        span.stage("command");
+        span.count("endpoints", 3);
+        span.stage("diff");
+        span.count("changes", 1);
+        span.end();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "poll");
+
+        let attribute = |name: &str| {
+            spans[0]
+                .attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == name)
+                .map(|kv| kv.value.clone())
+        };
+        assert_eq!(attribute("endpoints"), Some(Value::I64(3)));
+        assert_eq!(attribute("changes"), Some(Value::I64(1)));
+        assert!(matches!(attribute("stage.command.ms"), Some(Value::F64(_))));
+        assert!(matches!(attribute("stage.diff.ms"), Some(Value::F64(_))));
+    }
+}
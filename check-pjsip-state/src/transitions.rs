@@ -0,0 +1,206 @@
+use crate::endpoint_state::EndpointState;
+use crate::locale::{Locale, Phrase};
+use crate::EndpointsData;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+// Optional [transitions] config for counting per-endpoint state changes
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TransitionsConfig {
+    // Alert when an endpoint changes state more than this many times in one period
+    pub budget: Option<u32>,
+    // How often the counts are reported and reset, daily by default
+    #[serde(default = "default_reset_interval_seconds")]
+    pub reset_interval_seconds: u64,
+}
+
+fn default_reset_interval_seconds() -> u64 {
+    24 * 60 * 60
+}
+
+// Counts how many times each endpoint changed state in the current period
+pub struct TransitionCounter {
+    last_states: HashMap<String, EndpointState>,
+    counts: BTreeMap<String, u32>,
+    over_budget: HashSet<String>,
+    budget: Option<u32>,
+    period: Duration,
+    period_start: Instant,
+}
+
+impl TransitionCounter {
+    pub fn new(config: &TransitionsConfig, now: Instant) -> Self {
+        TransitionCounter {
+            last_states: HashMap::new(),
+            counts: BTreeMap::new(),
+            over_budget: HashSet::new(),
+            budget: config.budget,
+            period: Duration::from_secs(config.reset_interval_seconds),
+            period_start: now,
+        }
+    }
+
+    // Record a poll, returning the endpoints that have just gone over budget.
+    // Endpoints seen for the first time only set the baseline.
+    pub fn observe(&mut self, data: &EndpointsData) -> Vec<String> {
+        let mut newly_over_budget = Vec::new();
+
+        for endpoint in &data.endpoints {
+            let previous = self
+                .last_states
+                .insert(endpoint.endpoint.clone(), endpoint.state.clone());
+
+            match previous {
+                Some(previous) if previous != endpoint.state => {
+                    let count = self.counts.entry(endpoint.endpoint.clone()).or_insert(0);
+                    *count += 1;
+
+                    if let Some(budget) = self.budget {
+                        if *count > budget && self.over_budget.insert(endpoint.endpoint.clone()) {
+                            newly_over_budget.push(endpoint.endpoint.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        newly_over_budget
+    }
+
+    pub fn count(&self, endpoint: &str) -> u32 {
+        self.counts.get(endpoint).copied().unwrap_or(0)
+    }
+
+    // Message sent when an endpoint exceeds the transition budget
+    pub fn budget_message(&self, endpoint: &str, locale: Locale) -> String {
+        locale.fill(
+            Phrase::TransitionBudget,
+            &[&endpoint, &self.count(endpoint), &self.budget.unwrap_or(0)],
+        )
+    }
+
+    // Summary of the counts for the current period
+    pub fn digest(&self, locale: Locale) -> String {
+        if self.counts.is_empty() {
+            return locale.text(Phrase::NoTransitions).to_string();
+        }
+
+        let counts: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(endpoint, count)| format!("{}: {}", endpoint, count))
+            .collect();
+        locale.fill(Phrase::TransitionCounts, &[&counts.join(", ")])
+    }
+
+    // Once the period has elapsed, return the digest and reset the counters
+    pub fn maybe_reset(&mut self, now: Instant, locale: Locale) -> Option<String> {
+        if now.duration_since(self.period_start) < self.period {
+            return None;
+        }
+
+        let digest = self.digest(locale);
+        self.counts.clear();
+        self.over_budget.clear();
+        self.period_start = now;
+        Some(digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoint;
+
+    fn data(state: &str) -> EndpointsData {
+        EndpointsData {
+            endpoints: vec![
+                Endpoint {
+                    endpoint: "500/500".to_string(),
+                    state: "Not in use".into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+                Endpoint {
+                    endpoint: "Voipfone".to_string(),
+                    state: state.into(),
+                    channels: "0 of inf".to_string(),
+                    active_channels: 0,
+                    channel_limit: None,
+                    extra: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    fn counter(budget: Option<u32>) -> TransitionCounter {
+        let config = TransitionsConfig {
+            budget,
+            reset_interval_seconds: 60,
+        };
+        TransitionCounter::new(&config, Instant::now())
+    }
+
+    #[test]
+    fn test_counter_increments_on_state_change() {
+        let mut counter = counter(None);
+
+        // The first poll is only the baseline
+        counter.observe(&data("Not in use"));
+        assert_eq!(counter.count("Voipfone"), 0);
+
+        counter.observe(&data("Unavailable"));
+        counter.observe(&data("Unavailable"));
+        counter.observe(&data("Not in use"));
+
+        assert_eq!(counter.count("Voipfone"), 2);
+        assert_eq!(counter.count("500/500"), 0);
+        assert_eq!(counter.digest(Locale::En), "Transition counts: Voipfone: 2");
+    }
+
+    #[test]
+    fn test_budget_exceeded_triggers_once() {
+        let mut counter = counter(Some(2));
+
+        counter.observe(&data("Not in use"));
+        assert!(counter.observe(&data("Unavailable")).is_empty());
+        assert!(counter.observe(&data("Not in use")).is_empty());
+        assert_eq!(
+            counter.observe(&data("Unavailable")),
+            vec!["Voipfone".to_string()]
+        );
+
+        // Only alert once per period
+        assert!(counter.observe(&data("Not in use")).is_empty());
+        assert_eq!(
+            counter.budget_message("Voipfone", Locale::En),
+            "Voipfone has changed state 4 times this period, exceeding the budget of 2"
+        );
+    }
+
+    #[test]
+    fn test_reset_after_period() {
+        let mut counter = counter(Some(0));
+        let start = counter.period_start;
+
+        counter.observe(&data("Not in use"));
+        assert_eq!(counter.observe(&data("Unavailable")).len(), 1);
+
+        assert_eq!(
+            counter.maybe_reset(start + Duration::from_secs(30), Locale::En),
+            None
+        );
+        assert_eq!(
+            counter.maybe_reset(start + Duration::from_secs(60), Locale::En),
+            Some("Transition counts: Voipfone: 1".to_string())
+        );
+        assert_eq!(counter.count("Voipfone"), 0);
+
+        // The budget alert can fire again in the new period
+        assert_eq!(counter.observe(&data("Not in use")).len(), 1);
+    }
+}
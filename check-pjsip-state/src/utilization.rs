@@ -0,0 +1,311 @@
+use crate::filter::endpoint_pattern;
+use crate::locale::{Locale, Phrase};
+use crate::{Endpoint, EndpointsData};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// The threshold when channel_capacities is set without one
+pub const DEFAULT_UTILIZATION_PERCENT: u8 = 80;
+
+// A [[channel_thresholds]] entry: when to alert for the endpoints matching
+// its patterns, instead of channel_count_threshold and
+// channel_utilization_percent. Either or both of a count of channels in
+// use and a share of capacity, whichever is reached first.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ChannelThreshold {
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    pub channels: Option<u32>,
+    pub percent: Option<u8>,
+}
+
+// The limit an endpoint reached
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelLimit {
+    Channels(u32),
+    Percent(u8),
+}
+
+// An endpoint crossing its threshold, one way or the other
+#[derive(Debug, PartialEq)]
+pub enum UtilizationStep {
+    High {
+        endpoint: String,
+        active: u32,
+        // None when nothing says how many it can carry
+        capacity: Option<u32>,
+    },
+    Normal {
+        endpoint: String,
+        limit: ChannelLimit,
+    },
+}
+
+impl UtilizationStep {
+    pub fn message(&self, locale: Locale) -> String {
+        match self {
+            UtilizationStep::High {
+                endpoint,
+                active,
+                capacity: Some(capacity),
+            } => {
+                let percent = active * 100 / capacity;
+                locale.fill(
+                    Phrase::ChannelsHigh,
+                    &[endpoint, active, capacity, &percent],
+                )
+            }
+            UtilizationStep::High {
+                endpoint,
+                active,
+                capacity: None,
+            } => locale.fill(Phrase::ChannelsHighCount, &[endpoint, active]),
+            UtilizationStep::Normal {
+                endpoint,
+                limit: ChannelLimit::Percent(percent),
+            } => locale.fill(Phrase::ChannelsNormal, &[endpoint, percent]),
+            UtilizationStep::Normal {
+                endpoint,
+                limit: ChannelLimit::Channels(channels),
+            } => locale.fill(Phrase::ChannelsNormalCount, &[endpoint, channels]),
+        }
+    }
+}
+
+struct Threshold {
+    // Patterns are checked when the config is loaded
+    endpoints: Vec<Regex>,
+    channels: Option<u32>,
+    percent: Option<u8>,
+}
+
+impl Threshold {
+    fn new(config: &ChannelThreshold) -> Self {
+        Threshold {
+            endpoints: config
+                .endpoints
+                .iter()
+                .filter_map(|pattern| endpoint_pattern(pattern).ok())
+                .collect(),
+            channels: config.channels,
+            percent: config.percent,
+        }
+    }
+
+    // The limit reached with this many channels in use, if any. A share
+    // needs a capacity to be a share of.
+    fn reached(&self, active: u32, capacity: Option<u32>) -> Option<ChannelLimit> {
+        if let Some(channels) = self.channels.filter(|channels| active >= *channels) {
+            return Some(ChannelLimit::Channels(channels));
+        }
+        let (percent, capacity) = (self.percent?, capacity?);
+        (u64::from(active) * 100 >= u64::from(capacity) * u64::from(percent))
+            .then_some(ChannelLimit::Percent(percent))
+    }
+}
+
+// Alerts when an endpoint's channels in use reach a count, or a share of
+// its capacity: the one in channel_capacities, which wins, or else the
+// limit asterisk reports. Endpoints asterisk reports as "of inf" need a
+// configured one for a share.
+pub struct UtilizationAlerts {
+    capacities: HashMap<String, u32>,
+    // The first whose patterns match wins, then the global one
+    thresholds: Vec<Threshold>,
+    global: Threshold,
+    // Endpoints at or over their threshold as of the last reading, and the
+    // limit they reached
+    high: HashMap<String, ChannelLimit>,
+}
+
+impl UtilizationAlerts {
+    pub fn new(
+        capacities: &HashMap<String, u32>,
+        global: &ChannelThreshold,
+        thresholds: &[ChannelThreshold],
+    ) -> Self {
+        UtilizationAlerts {
+            capacities: capacities.clone(),
+            thresholds: thresholds.iter().map(Threshold::new).collect(),
+            global: Threshold::new(global),
+            high: HashMap::new(),
+        }
+    }
+
+    fn threshold(&self, endpoint: &str) -> &Threshold {
+        self.thresholds
+            .iter()
+            .find(|threshold| {
+                threshold
+                    .endpoints
+                    .iter()
+                    .any(|regex| regex.is_match(endpoint))
+            })
+            .unwrap_or(&self.global)
+    }
+
+    fn capacity(&self, endpoint: &Endpoint) -> Option<u32> {
+        self.capacities
+            .get(&endpoint.endpoint)
+            .copied()
+            .or(endpoint.channel_limit)
+            .filter(|capacity| *capacity > 0)
+    }
+
+    // Endpoints that went over their threshold or came back under it
+    pub fn observe(&mut self, data: &EndpointsData) -> Vec<UtilizationStep> {
+        let mut steps = Vec::new();
+        for endpoint in &data.endpoints {
+            let capacity = self.capacity(endpoint);
+            let active = endpoint.active_channels;
+            let reached = self.threshold(&endpoint.endpoint).reached(active, capacity);
+            match (reached, self.high.get(&endpoint.endpoint).copied()) {
+                (Some(limit), None) => {
+                    self.high.insert(endpoint.endpoint.clone(), limit);
+                    steps.push(UtilizationStep::High {
+                        endpoint: endpoint.endpoint.clone(),
+                        active,
+                        capacity,
+                    });
+                }
+                (None, Some(limit)) => {
+                    self.high.remove(&endpoint.endpoint);
+                    steps.push(UtilizationStep::Normal {
+                        endpoint: endpoint.endpoint.clone(),
+                        limit,
+                    });
+                }
+                _ => {}
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(name: &str, active_channels: u32) -> Endpoint {
+        Endpoint {
+            endpoint: name.to_string(),
+            state: "In use".into(),
+            channels: format!("{} of inf", active_channels),
+            active_channels,
+            channel_limit: None,
+            extra: Vec::new(),
+        }
+    }
+
+    fn data(active_channels: u32) -> EndpointsData {
+        EndpointsData {
+            endpoints: vec![endpoint("Voipfone", active_channels)],
+        }
+    }
+
+    fn percent(percent: u8) -> ChannelThreshold {
+        ChannelThreshold {
+            percent: Some(percent),
+            ..ChannelThreshold::default()
+        }
+    }
+
+    #[test]
+    fn test_configured_capacity_for_an_inf_endpoint() {
+        let capacities = HashMap::from([("Voipfone".to_string(), 10)]);
+        let mut alerts = UtilizationAlerts::new(&capacities, &percent(80), &[]);
+
+        assert!(alerts.observe(&data(7)).is_empty());
+        let steps = alerts.observe(&data(8));
+        assert_eq!(
+            steps,
+            vec![UtilizationStep::High {
+                endpoint: "Voipfone".to_string(),
+                active: 8,
+                capacity: Some(10),
+            }]
+        );
+        assert_eq!(
+            steps[0].message(Locale::En),
+            "Voipfone: 8 of 10 channels in use (80%)"
+        );
+        // Once until it drops back
+        assert!(alerts.observe(&data(9)).is_empty());
+        assert_eq!(
+            alerts.observe(&data(3)),
+            vec![UtilizationStep::Normal {
+                endpoint: "Voipfone".to_string(),
+                limit: ChannelLimit::Percent(80),
+            }]
+        );
+
+        // Without a capacity, "of inf" has nothing to measure against
+        let mut unconfigured = UtilizationAlerts::new(&HashMap::new(), &percent(80), &[]);
+        assert!(unconfigured.observe(&data(50)).is_empty());
+    }
+
+    #[test]
+    fn test_counts_and_per_endpoint_thresholds() {
+        let thresholds = [
+            ChannelThreshold {
+                endpoints: vec!["Voip*".to_string()],
+                channels: Some(20),
+                percent: None,
+            },
+            ChannelThreshold {
+                endpoints: vec!["re:^50[0-9]/".to_string()],
+                channels: None,
+                percent: Some(50),
+            },
+        ];
+        let global = ChannelThreshold {
+            channels: Some(2),
+            ..ChannelThreshold::default()
+        };
+        let capacities = HashMap::from([("500/500".to_string(), 4)]);
+        let mut alerts = UtilizationAlerts::new(&capacities, &global, &thresholds);
+        let data = |voipfone, desk, other| EndpointsData {
+            endpoints: vec![
+                endpoint("Voipfone", voipfone),
+                endpoint("500/500", desk),
+                endpoint("600/600", other),
+            ],
+        };
+
+        // The trunk's own count wins over the global one
+        let steps = alerts.observe(&data(19, 2, 2));
+        assert_eq!(
+            steps
+                .iter()
+                .map(|step| step.message(Locale::En))
+                .collect::<Vec<_>>(),
+            vec![
+                "500/500: 2 of 4 channels in use (50%)",
+                "600/600: 2 channels in use"
+            ]
+        );
+        assert_eq!(
+            alerts.observe(&data(20, 1, 1)),
+            vec![
+                UtilizationStep::High {
+                    endpoint: "Voipfone".to_string(),
+                    active: 20,
+                    capacity: None,
+                },
+                UtilizationStep::Normal {
+                    endpoint: "500/500".to_string(),
+                    limit: ChannelLimit::Percent(50),
+                },
+                UtilizationStep::Normal {
+                    endpoint: "600/600".to_string(),
+                    limit: ChannelLimit::Channels(2),
+                },
+            ]
+        );
+        assert_eq!(
+            alerts.observe(&data(3, 1, 1))[0].message(Locale::En),
+            "Voipfone: channels in use back under 20"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::clock::Clock;
+use chrono::{DateTime, Utc};
+use log::error;
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration};
+
+// Tracks when the last poll completed so a stuck loop can be detected
+pub struct Watchdog {
+    clock: Arc<dyn Clock>,
+    last_poll: Mutex<DateTime<Utc>>,
+    timeout: chrono::Duration,
+}
+
+impl Watchdog {
+    pub fn new(clock: Arc<dyn Clock>, timeout_seconds: u64) -> Self {
+        let now = clock.now();
+        Watchdog {
+            clock,
+            last_poll: Mutex::new(now),
+            timeout: chrono::Duration::seconds(timeout_seconds as i64),
+        }
+    }
+
+    // Called by the poll loop each time a poll completes
+    pub fn heartbeat(&self) {
+        *self.last_poll.lock().unwrap() = self.clock.now();
+    }
+
+    // How long polling has been stalled, if longer than the timeout
+    pub fn stalled_for(&self) -> Option<chrono::Duration> {
+        let since = self.clock.now() - *self.last_poll.lock().unwrap();
+        if since > self.timeout {
+            Some(since)
+        } else {
+            None
+        }
+    }
+}
+
+// Periodically check the watchdog, optionally exiting so a supervisor restarts us
+pub async fn run(watchdog: Arc<Watchdog>, abort: bool) {
+    let interval = Duration::from_secs((watchdog.timeout.num_seconds() as u64 / 2).max(1));
+    loop {
+        sleep(interval).await;
+
+        if let Some(stalled) = watchdog.stalled_for() {
+            error!(
+                "Watchdog: no poll has completed for {} seconds",
+                stalled.num_seconds()
+            );
+            if abort {
+                error!("Watchdog: exiting so the supervisor can restart the monitor");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_stale_heartbeat_triggers_watchdog() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let watchdog = Watchdog::new(clock.clone(), 120);
+
+        clock.advance(chrono::Duration::seconds(60));
+        assert_eq!(watchdog.stalled_for(), None);
+
+        clock.advance(chrono::Duration::seconds(61));
+        assert_eq!(watchdog.stalled_for(), Some(chrono::Duration::seconds(121)));
+    }
+
+    #[test]
+    fn test_heartbeat_resets_watchdog() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let watchdog = Watchdog::new(clock.clone(), 120);
+
+        clock.advance(chrono::Duration::seconds(200));
+        assert!(watchdog.stalled_for().is_some());
+
+        watchdog.heartbeat();
+        assert_eq!(watchdog.stalled_for(), None);
+    }
+}
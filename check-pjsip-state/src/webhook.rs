@@ -0,0 +1,175 @@
+use crate::notify::{Notifier, NotifyError};
+use async_trait::async_trait;
+use hmac::{Hmac, KeyInit, Mac};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::Request;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::io;
+
+// Optional [webhook] config for a generic HTTP receiver, which is sent each
+// message as a JSON body of {"text": "..."}
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookConfig {
+    pub url: String,
+    // Signs each request with HMAC-SHA256 when set. The signature covers
+    // "<timestamp>.<body>", so the receiver can reject replayed requests.
+    pub secret: Option<String>,
+    #[serde(default = "default_signature_header")]
+    pub signature_header: String,
+    #[serde(default = "default_timestamp_header")]
+    pub timestamp_header: String,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_signature_header() -> String {
+    "X-Signature".to_string()
+}
+
+fn default_timestamp_header() -> String {
+    "X-Signature-Timestamp".to_string()
+}
+
+// HMAC (RFC 2104) over SHA-256
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+// The signature header's value, e.g. "sha256=5bdc..."
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let signed = format!("{}.{}", timestamp, body);
+    let mac = hmac_sha256(secret.as_bytes(), signed.as_bytes());
+    let hex: String = mac.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", hex)
+}
+
+// Posts each message to the configured URL
+pub struct WebhookNotifier {
+    name: String,
+    config: WebhookConfig,
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &WebhookConfig) -> io::Result<Self> {
+        WebhookNotifier::named("webhook", config)
+    }
+
+    // Each of several, as from [[notifiers]], under a name of its own
+    pub fn named(name: &str, config: &WebhookConfig) -> io::Result<Self> {
+        // An explicit provider, since other dependencies may enable a
+        // second one and leave the process default ambiguous
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(rustls::crypto::ring::default_provider())?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(WebhookNotifier {
+            name: name.to_string(),
+            config: config.clone(),
+            client: Client::builder(TokioExecutor::new()).build(connector),
+        })
+    }
+
+    // The request for a message, signed as of timestamp if there's a secret
+    fn request(&self, message: &str, timestamp: i64) -> Result<Request<Full<Bytes>>, NotifyError> {
+        let body = serde_json::json!({ "text": message }).to_string();
+        let mut request =
+            Request::post(&self.config.url).header("Content-Type", "application/json");
+        if let Some(secret) = self.config.secret.as_deref() {
+            request = request
+                .header(&self.config.timestamp_header, timestamp)
+                .header(
+                    &self.config.signature_header,
+                    signature(secret, timestamp, &body),
+                );
+        }
+        request
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| NotifyError::Send(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, message: &str) -> Result<(), NotifyError> {
+        let request = self.request(message, chrono::Utc::now().timestamp())?;
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| NotifyError::Network(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(NotifyError::Send(format!(
+                "webhook returned {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        let hex: String = hmac_sha256(b"Jefe", b"what do ya want for nothing?")
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_is_signed() {
+        let notifier = WebhookNotifier::new(&WebhookConfig {
+            url: "https://alerts.example.com/pjsip".to_string(),
+            secret: Some("Jefe".to_string()),
+            signature_header: default_signature_header(),
+            timestamp_header: default_timestamp_header(),
+            dry_run: false,
+        })
+        .unwrap();
+
+        let request = notifier
+            .request("500/500: Not in use -> Unavailable", 1700000000)
+            .unwrap();
+        assert_eq!(request.headers()["X-Signature-Timestamp"], "1700000000");
+        let signature = request.headers()["X-Signature"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = request.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            body,
+            r#"{"text":"500/500: Not in use -> Unavailable"}"#.as_bytes()
+        );
+
+        let mut signed = b"1700000000.".to_vec();
+        signed.extend_from_slice(&body);
+        let hex: String = hmac_sha256(b"Jefe", &signed)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        assert_eq!(signature, format!("sha256={}", hex));
+    }
+}